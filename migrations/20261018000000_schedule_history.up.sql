@@ -0,0 +1,22 @@
+create table if not exists schedule_history (
+    id BIGSERIAL PRIMARY KEY,
+    time timestamptz not null,
+    sequence_id text not null,
+    sequence_title text not null,
+    schedule_date date,
+    repeat_number integer,
+    task_index integer,
+    task_title text,
+    topics text[] not null default '{}',
+    payload text,
+    -- Must match HistoryOutcome::as_str in robotica-common.
+    outcome text not null check (outcome in (
+        'sent', 'skipped', 'confirmed', 'failed', 'cancelled', 'missed',
+        'mark_done', 'mark_cancelled', 'mark_postponed', 'mark_removed'
+    )),
+    detail text
+);
+create index if not exists schedule_history_time_idx on schedule_history (time);
+create index if not exists schedule_history_sequence_id_idx on schedule_history (sequence_id, time);
+-- Each task is only recorded once, later outcomes replace earlier ones.
+create unique index if not exists schedule_history_task_idx on schedule_history (schedule_date, sequence_id, repeat_number, task_index) where task_index is not null;
@@ -38,13 +38,17 @@ let
 
     mkdir -p "${cfg.data_dir}"
     mkdir -p "${cfg.data_dir}/state"
-    exec "${robotica-backend}/bin/robotica-backend"
+    exec "${robotica-backend}/bin/robotica-backend" "$@"
   '';
 
   executor_type = types.submodule {
     options = {
       instance = mkOption { type = types.str; };
       classifications_file = mkOption { type = types.path; };
+      holiday_files = mkOption {
+        type = types.listOf types.path;
+        default = [ ];
+      };
       schedule_file = mkOption { type = types.path; };
       sequences_file = mkOption { type = types.path; };
     };
@@ -137,6 +141,17 @@ let
   lifx_type = types.submodule {
     options = {
       broadcast = mkOption { type = types.str; };
+      state_poll_time = mkOption {
+        type = types.nullOr types.str;
+        default = null;
+      };
+      reconcile = mkOption {
+        type = types.enum [
+          "reassert"
+          "adopt"
+        ];
+        default = "reassert";
+      };
     };
   };
 
@@ -170,6 +185,14 @@ let
         ];
       };
       lifx_id = mkOption { type = types.number; };
+      ip = mkOption {
+        type = types.nullOr types.str;
+        default = null;
+      };
+      port = mkOption {
+        type = types.int;
+        default = 56700;
+      };
     };
   };
 
@@ -310,6 +333,23 @@ let
         ];
       };
       topic = mkOption { type = types.str; };
+      weight = mkOption {
+        type = types.float;
+        default = 1.0;
+      };
+    };
+  };
+
+  occupancy_room_type = types.submodule {
+    options = {
+      vacancy_hold = mkOption {
+        type = types.str;
+        default = "00:00:00";
+      };
+      threshold = mkOption {
+        type = types.float;
+        default = 1.0;
+      };
     };
   };
 
@@ -402,6 +442,10 @@ let
       };
       presence_trackers = mkOption { type = types.listOf presence_tracker_type; };
       occupancy_sensors = mkOption { type = types.listOf occupancy_sensor_type; };
+      occupancy_rooms = mkOption {
+        type = types.attrsOf occupancy_room_type;
+        default = { };
+      };
       night_mode = mkOption { type = types.listOf night_mode_type; };
       owntracks = mkOption {
         type = types.listOf owntracks_source_type;
@@ -5,12 +5,17 @@ use robotica_common::datetime::utc_now;
 use robotica_common::robotica::entities::Id;
 use robotica_macro::{duration_constant, time_delta_constant};
 use robotica_tokio::{
-    pipes::stateful::{create_pipe, Receiver},
+    pipes::{
+        stateful::{create_pipe, Receiver, Sender},
+        Subscriber, Subscription,
+    },
+    services::persistent_state::PersistentStateRow,
+    sources::timer::{scheduled_timer, CatchUp, Schedule, ScheduledTimerConfig},
     spawn,
 };
 use tap::Pipe;
 use thiserror::Error;
-use tokio::time::{interval, sleep_until, Instant, MissedTickBehavior};
+use tokio::time::{sleep_until, Instant};
 use tracing::{error, info};
 use tracing_log_error::log_error;
 
@@ -125,10 +130,20 @@ const RETRY_TIME: TimeDelta = time_delta_constant!(1 minutes);
 const MIN_POLL_TIME: TimeDelta = time_delta_constant!(1 minutes);
 const MAX_POLL_TIME: TimeDelta = time_delta_constant!(5 minutes);
 const DEFAULT_INTERVAL: Duration = duration_constant!(5 minutes);
+const USAGE_PERIOD: TimeDelta = time_delta_constant!(1 hours);
 
 type Outputs = (Receiver<Arc<Prices>>, Receiver<Arc<Usage>>);
 
-pub fn run(id: &Id, config: api::Config) -> Result<Outputs, Error> {
+/// Poll the Amber API for prices and usage.
+///
+/// Usage is fetched every hour on the hour, `usage_state` remembers the last fetch so a
+/// fetch missed while not running happens straight away. Usage is also fetched at startup
+/// if there is no saved state.
+pub fn run(
+    id: &Id,
+    config: api::Config,
+    usage_state: PersistentStateRow<DateTime<Utc>>,
+) -> Result<Outputs, Error> {
     let (tx_prices, rx_prices) = create_pipe("amber_prices");
     let (tx_usage, rx_usage) = create_pipe("amber_usage");
 
@@ -136,14 +151,32 @@ pub fn run(id: &Id, config: api::Config) -> Result<Outputs, Error> {
         .ok_or_else(|| Error::Internal("Failed to create NEM timezone".to_string()))?;
 
     let id = id.clone();
+    let first_run = usage_state.load().is_err();
+
+    // Update usage once an hour
+    let usage_timer = scheduled_timer(
+        "amber_usage_timer",
+        ScheduledTimerConfig {
+            schedule: Schedule::Aligned {
+                period: USAGE_PERIOD,
+                offset: TimeDelta::zero(),
+            },
+            timezone: nem_timezone,
+            catch_up: CatchUp::Latest,
+            state: Some(usage_state),
+        },
+    );
 
     spawn(async move {
         // Update prices maximum every 5 minutes
         let mut price_instant = Instant::now() + tokio::time::Duration::from_secs(0);
 
-        // Update usage once an hour
-        let mut usage_interval = interval(tokio::time::Duration::from_secs(hours(1).into()));
-        usage_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut usage_s = usage_timer.subscribe().await;
+
+        // Nothing to catch up on the first run, so get usage now.
+        if first_run {
+            update_usage(&id, &config, nem_timezone, &tx_usage).await;
+        }
 
         // {
         //     #[allow(clippy::unwrap_used)]
@@ -243,24 +276,9 @@ pub fn run(id: &Id, config: api::Config) -> Result<Outputs, Error> {
                     let next_delay: std::time::Duration = next_delay.to_std().unwrap_or(std::time::Duration::from_mins(5));
                     price_instant = Instant::now() + next_delay;
                 }
-                _ = usage_interval.tick() => {
+                Ok(_) = usage_s.recv() => {
                     // Update the amber usage once an hour.
-                    let now = utc_now();
-                    let today = now.with_timezone(&nem_timezone).date_naive();
-                    let yesterday = today - ONE_DAY;
-                    let tomorrow = today + ONE_DAY;
-
-                    // Get usage for the current interval.
-                    match api::get_usage(&config, yesterday, tomorrow).await {
-                        Ok(usage) => {
-                            tx_usage.try_send(Arc::new(Usage {
-                                list: usage,
-                            }));
-                        }
-                        Err(err) => {
-                            log_error!(err, %id, "Failed to get usage");
-                        }
-                    }
+                    update_usage(&id, &config, nem_timezone, &tx_usage).await;
                 }
             }
         }
@@ -269,6 +287,28 @@ pub fn run(id: &Id, config: api::Config) -> Result<Outputs, Error> {
     Ok((rx_prices, rx_usage))
 }
 
+async fn update_usage(
+    id: &Id,
+    config: &api::Config,
+    nem_timezone: FixedOffset,
+    tx_usage: &Sender<Arc<Usage>>,
+) {
+    let now = utc_now();
+    let today = now.with_timezone(&nem_timezone).date_naive();
+    let yesterday = today - ONE_DAY;
+    let tomorrow = today + ONE_DAY;
+
+    // Get usage for the current interval.
+    match api::get_usage(config, yesterday, tomorrow).await {
+        Ok(usage) => {
+            tx_usage.try_send(Arc::new(Usage { list: usage }));
+        }
+        Err(err) => {
+            log_error!(err, %id, "Failed to get usage");
+        }
+    }
+}
+
 fn is_period_current(pr: &api::PriceResponse, dt: &DateTime<Utc>) -> bool {
     pr.start_time <= *dt && pr.end_time > *dt
 }
@@ -1,8 +1,9 @@
 use crate::{
-    amber, car, influxdb,
+    amber, car, effects, influxdb,
     lights::{self, Scene},
     metrics, InitState,
 };
+use chrono::TimeDelta;
 use envconfig::Envconfig;
 use robotica_common::{
     mqtt::Json,
@@ -11,15 +12,24 @@ use robotica_common::{
         lights::{PowerColor, SceneName},
         message::Audience,
     },
+    solar::{Location, TimeOfDay},
 };
+use robotica_macro::naive_time_constant;
 use robotica_tokio::{
-    devices::{lifx::LifxId, occupancy, presence_tracker},
+    devices::{
+        lifx::{self, LifxId},
+        occupancy, presence_tracker, zigbee2mqtt,
+    },
     pipes::stateful,
     scheduling::executor,
-    services::{http, mqtt, persistent_state},
+    services::{http, mqtt, persistent_state, scheduler},
 };
 use serde::Deserialize;
-use std::path::{Path, PathBuf};
+use std::{
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    time::Duration,
+};
 use thiserror::Error;
 
 #[derive(Envconfig)]
@@ -103,10 +113,16 @@ pub struct Config {
     pub calendar_message: Option<CalendarMessageConfig>,
     pub presence_trackers: Vec<PresenceTrackerConfig>,
     pub occupancy_sensors: Vec<OccupancySensorConfig>,
+    #[serde(default)]
+    pub occupancy_rooms: std::collections::HashMap<String, occupancy::FusionConfig>,
     pub night_mode: Vec<NightModeConfig>,
     pub message_routes: Vec<MessageRouteConfig>,
     #[serde(default)]
     pub owntracks: Vec<OwnTracksSourceConfig>,
+    pub location: Option<Location>,
+    #[serde(default)]
+    pub auto_light: AutoLightConfig,
+    pub vacation: Option<VacationConfig>,
 }
 
 /// An error loading the Config
@@ -125,25 +141,39 @@ pub enum Error {
     Merge(#[from] robotica_tokio::serde::Error),
 }
 
-#[allow(clippy::module_name_repetitions)]
 #[derive(Debug, Deserialize, Clone)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "type", content = "value")]
-pub enum LightSceneConfig {
+pub enum LightSceneSource {
     FixedColor(PowerColor),
     MqttFeed(String),
+    Effect(effects::EffectConfig),
+}
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Deserialize, Clone)]
+pub struct LightSceneConfig {
+    #[serde(flatten)]
+    pub source: LightSceneSource,
+    /// How long to fade to the scene, unless the command gives a transition.
+    #[serde(with = "robotica_common::datetime::with_option_duration")]
+    #[serde(default)]
+    pub transition: Option<Duration>,
 }
 
 impl LightSceneConfig {
     pub fn get_scene(&self, state: &mut InitState, name: SceneName) -> Scene {
-        let entity = match self {
-            Self::FixedColor(pc) => stateful::static_pipe(pc.clone(), "FixedColor"),
-            Self::MqttFeed(topic) => state
+        let entity = match &self.source {
+            LightSceneSource::FixedColor(pc) => stateful::static_pipe(pc.clone(), "FixedColor"),
+            LightSceneSource::MqttFeed(topic) => state
                 .subscriptions
                 .subscribe_into_stateful::<Json<PowerColor>>(topic)
                 .map(|(_, Json(c))| c),
+            LightSceneSource::Effect(effect) => {
+                effects::effect_entity(effect.clone(), format!("{name}_effect"))
+            }
         };
-        lights::Scene::new(entity, name)
+        lights::Scene::new(entity, name).with_transition(self.transition.unwrap_or_default())
     }
 }
 
@@ -173,8 +203,38 @@ pub struct StripConfig {
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "type")]
 pub enum LightDeviceConfig {
-    Lifx { lifx_id: LifxId },
-    Debug { lifx_id: LifxId },
+    Lifx {
+        lifx_id: LifxId,
+        #[serde(default)]
+        ip: Option<IpAddr>,
+        #[serde(default = "default_lifx_port")]
+        port: u16,
+    },
+    Zigbee2mqtt(zigbee2mqtt::LightConfig),
+    Debug {
+        lifx_id: LifxId,
+    },
+}
+
+const fn default_lifx_port() -> u16 {
+    56700
+}
+
+impl LightDeviceConfig {
+    /// Get the LIFX device to probe directly, if it has a static address.
+    pub const fn static_lifx_device(&self) -> Option<lifx::StaticDevice> {
+        match self {
+            Self::Lifx {
+                lifx_id,
+                ip: Some(ip),
+                port,
+            } => Some(lifx::StaticDevice {
+                id: *lifx_id,
+                addr: SocketAddr::new(*ip, *port),
+            }),
+            _ => None,
+        }
+    }
 }
 
 #[allow(clippy::module_name_repetitions)]
@@ -192,6 +252,105 @@ pub struct SplitLightConfig {
 #[derive(Debug, Deserialize)]
 pub struct LifxConfig {
     pub broadcast: String,
+    #[serde(default, with = "robotica_common::datetime::with_option_duration")]
+    pub state_poll_time: Option<Duration>,
+    #[serde(default)]
+    pub reconcile: lifx::Reconcile,
+}
+
+/// When the automatic light levels change.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Deserialize)]
+pub struct AutoLightConfig {
+    /// When the lights reach full brightness in the morning.
+    pub morning: TimeOfDay,
+    /// When the lights start dimming in the evening.
+    pub evening: TimeOfDay,
+    /// Brightness levels to use instead of the default curve.
+    #[serde(default)]
+    pub brightness: Option<Vec<scheduler::Entry<f32>>>,
+    /// Color temperatures to use instead of the default curve.
+    #[serde(default)]
+    pub temperature: Option<Vec<scheduler::Entry<u16>>>,
+    /// Follow the height of the sun instead of fixed times, if the location is set.
+    #[serde(default)]
+    pub circadian: Option<CircadianConfig>,
+    /// Circadian levels for particular rooms, instead of the default.
+    #[serde(default)]
+    pub circadian_rooms: std::collections::HashMap<String, CircadianConfig>,
+}
+
+impl Default for AutoLightConfig {
+    fn default() -> Self {
+        Self {
+            morning: naive_time_constant!(08:00:00).into(),
+            evening: naive_time_constant!(19:00:00).into(),
+            brightness: None,
+            temperature: None,
+            circadian: None,
+            circadian_rooms: std::collections::HashMap::new(),
+        }
+    }
+}
+
+/// How a light level follows the height of the sun.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct CircadianCurve {
+    /// The level when the sun is at or below `low_elevation`.
+    pub min: f32,
+    /// The level when the sun is at or above `high_elevation`.
+    pub max: f32,
+    /// The height of the sun in degrees where the level starts to rise.
+    pub low_elevation: f64,
+    /// The height of the sun in degrees where the level reaches the maximum.
+    pub high_elevation: f64,
+}
+
+impl CircadianCurve {
+    /// Get the level for the height of the sun, rising smoothly between the elevations.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn level(&self, elevation: f64) -> f32 {
+        let range = self.high_elevation - self.low_elevation;
+        let fraction = if range > 0.0 {
+            ((elevation - self.low_elevation) / range).clamp(0.0, 1.0)
+        } else if elevation >= self.high_elevation {
+            1.0
+        } else {
+            0.0
+        };
+        let fraction = fraction * fraction * 2.0f64.mul_add(-fraction, 3.0);
+        (self.max - self.min).mul_add(fraction as f32, self.min)
+    }
+}
+
+/// Light levels that follow the height of the sun.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct CircadianConfig {
+    /// The color temperature in kelvin.
+    #[serde(default = "default_circadian_kelvin")]
+    pub kelvin: CircadianCurve,
+    /// The brightness in percent.
+    #[serde(default = "default_circadian_brightness")]
+    pub brightness: CircadianCurve,
+}
+
+const fn default_circadian_kelvin() -> CircadianCurve {
+    CircadianCurve {
+        min: 2200.0,
+        max: 5000.0,
+        low_elevation: -6.0,
+        high_elevation: 30.0,
+    }
+}
+
+const fn default_circadian_brightness() -> CircadianCurve {
+    CircadianCurve {
+        min: 5.0,
+        max: 100.0,
+        low_elevation: -12.0,
+        high_elevation: 10.0,
+    }
 }
 
 #[allow(clippy::module_name_repetitions)]
@@ -240,15 +399,75 @@ pub struct PresenceTrackerConfig {
 #[derive(Debug, Deserialize)]
 pub struct OccupancySensorConfig {
     pub id: IdWithRoom,
+    #[serde(default = "default_occupancy_weight")]
+    pub weight: f32,
     #[serde(flatten)]
     pub config: occupancy::Config,
 }
 
+const fn default_occupancy_weight() -> f32 {
+    1.0
+}
+
 #[derive(Debug, Deserialize)]
 pub struct NightModeConfig {
     pub id: IdWithRoom,
 }
 
+/// Make the house look occupied while we are away.
+#[derive(Debug, Deserialize)]
+pub struct VacationConfig {
+    /// The switch that turns vacation mode on and off.
+    pub id: IdWithRoom,
+    /// The activities to simulate each day.
+    pub activities: Vec<VacationActivityConfig>,
+}
+
+/// Something that might happen on a day while we are away.
+#[derive(Debug, Deserialize, Clone)]
+pub struct VacationActivityConfig {
+    pub title: String,
+    pub target: VacationTargetConfig,
+    /// When the activity starts.
+    pub start: TimeOfDay,
+    /// How long the activity lasts.
+    #[serde(with = "robotica_common::datetime::with_time_delta")]
+    pub duration: TimeDelta,
+    /// The start and end times are moved randomly by up to this much.
+    #[serde(with = "robotica_common::datetime::with_time_delta", default)]
+    pub jitter: TimeDelta,
+    /// The chance the activity happens on any given day, from 0 to 1.
+    #[serde(default = "default_probability")]
+    pub probability: f64,
+}
+
+const fn default_probability() -> f64 {
+    1.0
+}
+
+/// What a vacation activity controls.
+///
+/// This is deliberately limited to lights and music, so the simulation can never make
+/// announcements or switch energy managed loads.
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "type")]
+pub enum VacationTargetConfig {
+    Light {
+        id: IdWithRoom,
+        #[serde(default = "default_vacation_scene")]
+        scene: SceneName,
+    },
+    Music {
+        id: IdWithRoom,
+        play_list: String,
+    },
+}
+
+fn default_vacation_scene() -> SceneName {
+    SceneName::new("auto")
+}
+
 #[derive(Debug, Deserialize)]
 pub struct PresenceRequirements {
     pub presence_id: Id,
@@ -320,6 +539,8 @@ impl OwnTracksSourceConfig {
 mod tests {
     #![allow(clippy::unwrap_used)]
     use super::*;
+    use float_cmp::assert_approx_eq;
+    use robotica_common::datetime::duration;
     use serde_json::json;
 
     #[test]
@@ -329,9 +550,30 @@ mod tests {
         assert_eq!(
             device,
             LightDeviceConfig::Lifx {
-                lifx_id: LifxId::new(0x1234_5678_90ab_cdef)
+                lifx_id: LifxId::new(0x1234_5678_90ab_cdef),
+                ip: None,
+                port: 56700,
             }
         );
+        assert!(device.static_lifx_device().is_none());
+
+        let json = json!({"type": "lifx", "lifx_id": 0x1234_5678_90ab_cdefu64, "ip": "10.1.2.3"});
+        let device: LightDeviceConfig = serde_json::from_value(json).unwrap();
+        let static_device = device.static_lifx_device().unwrap();
+        assert_eq!(static_device.id, LifxId::new(0x1234_5678_90ab_cdef));
+        assert_eq!(static_device.addr, "10.1.2.3:56700".parse().unwrap());
+
+        let json = json!({"type": "zigbee2mqtt", "topic": "zigbee2mqtt/Brian/Light", "capability": "color_temperature"});
+        let device: LightDeviceConfig = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            device,
+            LightDeviceConfig::Zigbee2mqtt(zigbee2mqtt::LightConfig {
+                topic: "zigbee2mqtt/Brian/Light".to_string(),
+                capability: zigbee2mqtt::LightCapability::ColorTemperature,
+                min_kelvin: 2200,
+                max_kelvin: 6500,
+            })
+        );
 
         let json = json!({"type": "debug", "lifx_id": 0x1234_5678_90ab_cdefu64});
         let device: LightDeviceConfig = serde_json::from_value(json).unwrap();
@@ -342,4 +584,50 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_circadian_curve() {
+        let curve = default_circadian_kelvin();
+        assert_approx_eq!(f32, curve.level(-90.0), 2200.0);
+        assert_approx_eq!(f32, curve.level(-6.0), 2200.0);
+        assert_approx_eq!(f32, curve.level(12.0), 3600.0);
+        assert_approx_eq!(f32, curve.level(30.0), 5000.0);
+        assert_approx_eq!(f32, curve.level(75.0), 5000.0);
+        assert!(curve.level(0.0) < curve.level(1.0));
+
+        let json = json!({"brightness": {"min": 2.0, "max": 80.0, "low_elevation": -18.0, "high_elevation": 0.0}});
+        let config: CircadianConfig = serde_json::from_value(json).unwrap();
+        assert_eq!(config.kelvin, default_circadian_kelvin());
+        assert_approx_eq!(f32, config.brightness.level(-9.0), 41.0);
+    }
+
+    #[test]
+    fn test_light_scene_deserialize() {
+        let json = json!({"type": "mqtt_feed", "value": "state/Brian/Light/scene"});
+        let scene: LightSceneConfig = serde_json::from_value(json).unwrap();
+        assert!(
+            matches!(scene.source, LightSceneSource::MqttFeed(topic) if topic == "state/Brian/Light/scene")
+        );
+        assert_eq!(scene.transition, None);
+
+        let json =
+            json!({"type": "fixed_color", "value": {"power": "off"}, "transition": "00:10:00"});
+        let scene: LightSceneConfig = serde_json::from_value(json).unwrap();
+        assert!(matches!(
+            scene.source,
+            LightSceneSource::FixedColor(PowerColor::Off)
+        ));
+        assert_eq!(scene.transition, Some(duration::minutes(10)));
+
+        let json = json!({"type": "effect", "value": {"keyframes": [
+            {"colors": [{"hue": 30.0, "saturation": 80.0, "brightness": 60.0, "kelvin": 2500}], "fade_ms": 200, "easing": "ease_in_out"}
+        ]}});
+        let scene: LightSceneConfig = serde_json::from_value(json).unwrap();
+        let LightSceneSource::Effect(effect) = scene.source else {
+            unreachable!()
+        };
+        assert_eq!(effect.keyframes.len(), 1);
+        assert_eq!(effect.keyframes[0].fade_ms, 200);
+        assert_eq!(effect.repeat, None);
+    }
 }
@@ -0,0 +1,384 @@
+//! Animated light effects defined by keyframes.
+use std::time::Duration;
+
+use rand::Rng;
+use robotica_common::robotica::lights::{Colors, PowerColor, HSBK};
+use robotica_tokio::{pipes::stateful, spawn};
+use serde::Deserialize;
+use tokio::time::sleep;
+
+/// The shortest time between two frames of a fade.
+const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How the colors change during a fade to a keyframe.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    /// Change at a constant rate.
+    #[default]
+    Linear,
+
+    /// Start slowly and speed up.
+    EaseIn,
+
+    /// Start quickly and slow down.
+    EaseOut,
+
+    /// Start and end slowly.
+    EaseInOut,
+
+    /// Jump to the new colors at the end of the fade.
+    Step,
+}
+
+impl Easing {
+    /// Get the fraction of the change done at a fraction of the time.
+    #[must_use]
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => t,
+            Self::EaseIn => t * t,
+            Self::EaseOut => t * (2.0 - t),
+            Self::EaseInOut => t * t * 2.0f32.mul_add(-t, 3.0),
+            Self::Step if t < 1.0 => 0.0,
+            Self::Step => 1.0,
+        }
+    }
+}
+
+/// How much to randomly vary a keyframe each time it is shown.
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+pub struct Randomise {
+    /// Vary the hue by up to this many degrees either way.
+    #[serde(default)]
+    pub hue: f32,
+
+    /// Vary the saturation by up to this many percent either way.
+    #[serde(default)]
+    pub saturation: f32,
+
+    /// Vary the brightness by up to this many percent either way.
+    #[serde(default)]
+    pub brightness: f32,
+
+    /// Vary the color temperature by up to this many kelvin either way.
+    #[serde(default)]
+    pub kelvin: u16,
+
+    /// Hold the keyframe for up to this many milliseconds longer.
+    #[serde(default)]
+    pub hold_ms: u64,
+}
+
+/// One step of an effect.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Keyframe {
+    /// The colors to show, either one for all zones or one per zone.
+    ///
+    /// Per zone colors are repeated to fill the zones of a strip.
+    pub colors: Vec<HSBK>,
+
+    /// How long to take to fade from the previous keyframe.
+    #[serde(default)]
+    pub fade_ms: u64,
+
+    /// How long to show the colors before the next keyframe.
+    #[serde(default)]
+    pub hold_ms: u64,
+
+    /// How the colors change while fading.
+    #[serde(default)]
+    pub easing: Easing,
+
+    /// How much to vary the keyframe each time it is shown.
+    #[serde(default)]
+    pub random: Option<Randomise>,
+}
+
+/// An animated light effect.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct EffectConfig {
+    /// The keyframes to show, in order.
+    pub keyframes: Vec<Keyframe>,
+
+    /// How many times to play the keyframes, or forever if not set.
+    #[serde(default)]
+    pub repeat: Option<u32>,
+}
+
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn randomise_color(color: HSBK, random: &Randomise, rng: &mut impl Rng) -> HSBK {
+    let mut vary = |value: f32, amount: f32| {
+        let amount = amount.abs();
+        if amount > 0.0 {
+            value + rng.random_range(-amount..=amount)
+        } else {
+            value
+        }
+    };
+
+    let hue = vary(color.hue, random.hue).rem_euclid(360.0);
+    let saturation = vary(color.saturation, random.saturation).clamp(0.0, 100.0);
+    let brightness = vary(color.brightness, random.brightness).clamp(0.0, 100.0);
+    let kelvin = vary(f32::from(color.kelvin), f32::from(random.kelvin))
+        .clamp(1500.0, 9000.0)
+        .round() as u16;
+
+    HSBK {
+        hue,
+        saturation,
+        brightness,
+        kelvin,
+    }
+}
+
+/// Get the colors and hold time for a keyframe, with any randomisation applied.
+fn render_keyframe(keyframe: &Keyframe, rng: &mut impl Rng) -> (PowerColor, Duration) {
+    let (colors, extra) = keyframe.random.as_ref().map_or_else(
+        || (keyframe.colors.clone(), 0),
+        |random| {
+            let colors = keyframe
+                .colors
+                .iter()
+                .map(|color| randomise_color(*color, random, rng))
+                .collect();
+            let extra = if random.hold_ms > 0 {
+                rng.random_range(0..=random.hold_ms)
+            } else {
+                0
+            };
+            (colors, extra)
+        },
+    );
+
+    let pc = match colors.as_slice() {
+        [] => PowerColor::Off,
+        [color] => PowerColor::On(Colors::Single(*color)),
+        _ => PowerColor::On(Colors::Sequence(colors)),
+    };
+
+    (pc, Duration::from_millis(keyframe.hold_ms + extra))
+}
+
+/// Get the frames to show while fading from one keyframe to the next.
+fn fade_frames(
+    from: &PowerColor,
+    to: &PowerColor,
+    fade: Duration,
+    easing: Easing,
+) -> Vec<(Duration, PowerColor)> {
+    let frames = (fade.as_millis() / FRAME_INTERVAL.as_millis()).max(1);
+    let frames = u32::try_from(frames).unwrap_or(u32::MAX);
+    let interval = fade / frames;
+
+    (1..=frames)
+        .map(|frame| {
+            #[allow(clippy::cast_precision_loss)]
+            let t = frame as f32 / frames as f32;
+            (interval, from.interpolate(to, easing.apply(t)))
+        })
+        .collect()
+}
+
+/// Play an effect.
+///
+/// The effect only plays while something is subscribed, such as a light showing the scene.
+pub fn effect_entity(
+    config: EffectConfig,
+    name: impl Into<String>,
+) -> stateful::Receiver<PowerColor> {
+    let (tx, rx) = stateful::create_pipe(name);
+
+    spawn(async move {
+        if config.keyframes.is_empty() {
+            tx.try_send(PowerColor::Off);
+            tx.closed().await;
+            return;
+        }
+
+        let mut current = PowerColor::Off;
+        let mut plays = 0;
+
+        while !tx.is_closed() && config.repeat.is_none_or(|repeat| plays < repeat) {
+            for keyframe in &config.keyframes {
+                // Pause until a light selects the scene.
+                tx.subscribed().await;
+
+                let (target, hold) = render_keyframe(keyframe, &mut rand::rng());
+                let fade = Duration::from_millis(keyframe.fade_ms);
+
+                if fade.is_zero() {
+                    tx.try_send(target.clone());
+                } else {
+                    for (interval, pc) in fade_frames(&current, &target, fade, keyframe.easing) {
+                        sleep(interval).await;
+                        tx.try_send(pc);
+                    }
+                }
+
+                // Always wait between keyframes, so an effect with no times can't spin.
+                let hold = if fade.is_zero() {
+                    hold.max(FRAME_INTERVAL)
+                } else {
+                    hold
+                };
+                current = target;
+                sleep(hold).await;
+            }
+            plays += 1;
+        }
+
+        // Keep showing the last keyframe.
+        tx.closed().await;
+    });
+
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use float_cmp::assert_approx_eq;
+    use rand::{rngs::StdRng, SeedableRng};
+    use robotica_tokio::pipes::{Subscriber, Subscription};
+    use tokio::time::timeout;
+
+    use super::*;
+
+    fn white(brightness: f32) -> HSBK {
+        HSBK {
+            hue: 0.0,
+            saturation: 0.0,
+            brightness,
+            kelvin: 3500,
+        }
+    }
+
+    #[test]
+    fn test_easing() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+            Easing::Step,
+        ] {
+            assert_approx_eq!(f32, easing.apply(0.0), 0.0);
+            assert_approx_eq!(f32, easing.apply(1.0), 1.0);
+        }
+
+        assert_approx_eq!(f32, Easing::Linear.apply(0.25), 0.25);
+        assert_approx_eq!(f32, Easing::EaseIn.apply(0.5), 0.25);
+        assert_approx_eq!(f32, Easing::EaseOut.apply(0.5), 0.75);
+        assert_approx_eq!(f32, Easing::EaseInOut.apply(0.5), 0.5);
+        assert_approx_eq!(f32, Easing::Step.apply(0.9), 0.0);
+    }
+
+    #[test]
+    fn test_fade_frames() {
+        let from = PowerColor::On(Colors::Single(white(0.0)));
+        let to = PowerColor::On(Colors::Single(white(100.0)));
+
+        let frames = fade_frames(&from, &to, Duration::from_millis(400), Easing::Linear);
+        let brightness: Vec<_> = frames
+            .iter()
+            .map(|(interval, pc)| {
+                assert_eq!(*interval, Duration::from_millis(100));
+                match pc {
+                    PowerColor::On(Colors::Single(color)) => color.brightness,
+                    _ => unreachable!(),
+                }
+            })
+            .collect();
+        assert_eq!(brightness, vec![25.0, 50.0, 75.0, 100.0]);
+
+        let frames = fade_frames(&from, &to, Duration::from_millis(50), Easing::Linear);
+        assert_eq!(frames, vec![(Duration::from_millis(50), to)]);
+    }
+
+    #[test]
+    fn test_render_keyframe() {
+        let keyframe = Keyframe {
+            colors: vec![white(50.0), white(50.0)],
+            fade_ms: 0,
+            hold_ms: 100,
+            easing: Easing::Linear,
+            random: Some(Randomise {
+                brightness: 10.0,
+                hold_ms: 50,
+                ..Randomise::default()
+            }),
+        };
+        let mut rng = StdRng::seed_from_u64(1);
+
+        for _ in 0..20 {
+            let (pc, hold) = render_keyframe(&keyframe, &mut rng);
+            assert!(hold >= Duration::from_millis(100));
+            assert!(hold <= Duration::from_millis(150));
+
+            let PowerColor::On(Colors::Sequence(colors)) = pc else {
+                unreachable!()
+            };
+            assert_eq!(colors.len(), 2);
+            for color in colors {
+                assert!((40.0..=60.0).contains(&color.brightness));
+                assert_eq!(color.kelvin, 3500);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_effect_waits_for_subscriber() {
+        let keyframe = |brightness| Keyframe {
+            colors: vec![white(brightness)],
+            fade_ms: 0,
+            hold_ms: 0,
+            easing: Easing::Step,
+            random: None,
+        };
+        let config = EffectConfig {
+            keyframes: vec![keyframe(10.0), keyframe(20.0)],
+            repeat: None,
+        };
+        let rx = effect_entity(config, "test_effect");
+
+        sleep(Duration::from_millis(300)).await;
+        assert_eq!(rx.get().await, None);
+
+        let mut rx_s = rx.subscribe().await;
+        let pc = timeout(Duration::from_secs(1), rx_s.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(pc, PowerColor::On(Colors::Single(white(10.0))));
+    }
+
+    #[test]
+    fn test_deserialize_effect() {
+        let yaml = r"
+            repeat: 3
+            keyframes:
+              - colors:
+                  - {hue: 30.0, saturation: 80.0, brightness: 60.0, kelvin: 2500}
+                fade_ms: 100
+                easing: ease_in_out
+                random:
+                  brightness: 20.0
+                  hold_ms: 200
+              - colors:
+                  - {hue: 0.0, saturation: 0.0, brightness: 100.0, kelvin: 3500}
+                  - {hue: 0.0, saturation: 0.0, brightness: 0.0, kelvin: 3500}
+                hold_ms: 250
+        ";
+        let effect: EffectConfig = serde_yaml_ng::from_str(yaml).unwrap();
+        assert_eq!(effect.repeat, Some(3));
+        assert_eq!(effect.keyframes.len(), 2);
+        assert_eq!(effect.keyframes[0].easing, Easing::EaseInOut);
+        assert_eq!(effect.keyframes[0].hold_ms, 0);
+        assert_eq!(effect.keyframes[1].easing, Easing::Linear);
+        assert_eq!(effect.keyframes[1].random, None);
+        assert_eq!(effect.keyframes[1].colors.len(), 2);
+    }
+}
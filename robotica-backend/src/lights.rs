@@ -1,18 +1,25 @@
 use std::{
     collections::HashMap,
     iter::{empty, zip},
+    ops::Range,
     time::Duration,
 };
 
+use chrono::{TimeDelta, Utc};
+use chrono_tz::Tz;
 use robotica_common::robotica::entities::IdWithRoom;
+use robotica_common::solar::{sun_elevation, Location, TimeOfDay};
 use robotica_common::{
     mqtt::Json,
     robotica::{
         commands::Command,
-        lights::{Colors, LightCommand, PowerColor, PowerLevel, SceneName, HSBK},
+        lights::{
+            Colors, FlashPattern, LightCommand, PowerColor, PowerColorTransition, PowerLevel,
+            SceneName, SceneOverride, HSBK,
+        },
     },
 };
-use robotica_macro::naive_time_constant;
+use robotica_macro::time_delta_constant;
 use robotica_tokio::{
     devices::occupancy::OccupiedState,
     pipes::{
@@ -22,18 +29,33 @@ use robotica_tokio::{
     services::{persistent_state::PersistentStateRow, scheduler},
     spawn,
 };
-use tokio::time::sleep;
+use tokio::time::{sleep, sleep_until, Instant};
 use tracing::{debug, error};
 
+use crate::config::{AutoLightConfig, CircadianConfig};
+use crate::effects::{effect_entity, Easing, EffectConfig, Keyframe};
+
 #[derive(Debug, Clone)]
 pub struct Scene {
     rx: stateful::Receiver<PowerColor>,
     name: SceneName,
+    transition: Duration,
 }
 
 impl Scene {
     pub const fn new(rx: stateful::Receiver<PowerColor>, name: SceneName) -> Self {
-        Self { rx, name }
+        Self {
+            rx,
+            name,
+            transition: Duration::ZERO,
+        }
+    }
+
+    /// Fade to this scene over the given duration, unless the command says otherwise.
+    #[must_use]
+    pub const fn with_transition(mut self, transition: Duration) -> Self {
+        self.transition = transition;
+        self
     }
 }
 
@@ -41,7 +63,7 @@ impl Default for Scene {
     fn default() -> Self {
         let name = SceneName::default();
         let rx = static_entity(PowerColor::Off, "default");
-        Self { rx, name }
+        Self::new(rx, name)
     }
 }
 
@@ -85,12 +107,15 @@ pub fn get_default_scenes() -> SceneMap {
 
     map.insert(
         SceneName::new("rainbow"),
-        Scene::new(rainbow_entity("rainbow"), SceneName::new("rainbow")),
+        Scene::new(
+            effect_entity(rainbow_effect(), "rainbow"),
+            SceneName::new("rainbow"),
+        ),
     );
 
     map.insert(
         SceneName::new("busy"),
-        Scene::new(busy_entity("busy"), SceneName::new("busy")),
+        Scene::new(effect_entity(busy_effect(), "busy"), SceneName::new("busy")),
     );
 
     map.insert(
@@ -101,44 +126,46 @@ pub fn get_default_scenes() -> SceneMap {
     SceneMap::new(map)
 }
 
-fn busy_entity(name: impl Into<String>) -> stateful::Receiver<PowerColor> {
-    let (tx, rx) = stateful::create_pipe(name);
-    spawn(async move {
-        while !tx.is_closed() {
-            let on_color = HSBK {
-                hue: 0.0,
-                saturation: 100.0,
-                brightness: 100.0,
-                kelvin: 3500,
-            };
+fn hold_keyframe(colors: Vec<HSBK>) -> Keyframe {
+    Keyframe {
+        colors,
+        fade_ms: 0,
+        hold_ms: 500,
+        easing: Easing::Step,
+        random: None,
+    }
+}
 
-            let off_color = HSBK {
-                hue: 0.0,
-                saturation: 20.0,
-                brightness: 0.0,
-                kelvin: 3500,
-            };
+fn busy_effect() -> EffectConfig {
+    let on_color = HSBK {
+        hue: 0.0,
+        saturation: 100.0,
+        brightness: 100.0,
+        kelvin: 3500,
+    };
 
-            let colors = Colors::Sequence(vec![on_color, off_color]);
-            tx.try_send(PowerColor::On(colors));
-            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    let off_color = HSBK {
+        hue: 0.0,
+        saturation: 20.0,
+        brightness: 0.0,
+        kelvin: 3500,
+    };
 
-            let colors = Colors::Sequence(vec![off_color, on_color]);
-            tx.try_send(PowerColor::On(colors));
-            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-        }
-    });
-    rx
+    EffectConfig {
+        keyframes: vec![
+            hold_keyframe(vec![on_color, off_color]),
+            hold_keyframe(vec![off_color, on_color]),
+        ],
+        repeat: None,
+    }
 }
 
-fn rainbow_entity(name: impl Into<String>) -> stateful::Receiver<PowerColor> {
-    let (tx, rx) = stateful::create_pipe(name);
-    spawn(async move {
-        let mut i = 0u16;
-        let num_per_cycle = 10u16;
+fn rainbow_effect() -> EffectConfig {
+    let num_per_cycle = 10u16;
 
-        while !tx.is_closed() {
-            let colors: Vec<HSBK> = (0..num_per_cycle)
+    let keyframes = (0..num_per_cycle)
+        .map(|i| {
+            let colors = (0..num_per_cycle)
                 .map(|j| {
                     let mut hue = f32::from(i + j) * 360.0 / f32::from(num_per_cycle);
                     hue = hue.rem_euclid(360.0);
@@ -150,14 +177,14 @@ fn rainbow_entity(name: impl Into<String>) -> stateful::Receiver<PowerColor> {
                     }
                 })
                 .collect();
-            let colors = Colors::Sequence(colors);
+            hold_keyframe(colors)
+        })
+        .collect();
 
-            tx.try_send(PowerColor::On(colors));
-            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-            i = (i + 1) % num_per_cycle;
-        }
-    });
-    rx
+    EffectConfig {
+        keyframes,
+        repeat: None,
+    }
 }
 
 pub struct Inputs {
@@ -166,7 +193,8 @@ pub struct Inputs {
 
 pub struct Outputs {
     pub scene: stateful::Receiver<SceneName>,
-    pub pc: stateful::Receiver<PowerColor>,
+    pub scene_override: stateful::Receiver<Option<SceneOverride>>,
+    pub pc: stateful::Receiver<PowerColorTransition>,
 }
 
 #[must_use]
@@ -178,12 +206,13 @@ pub fn run_auto_light(
     id: &IdWithRoom,
 ) -> Outputs {
     // let (state_tx, state_rx) = stateful::create_pipe(format!("{lifx_id}-state"));
-    let (pc_rx, scene_rx) = switch_entity(
+    let (pc_rx, scene_rx, override_rx) = switch_entity(
         inputs.commands,
         persistent_state_database,
         id,
         scene_map,
         flash_color,
+        1,
     );
 
     // device_entity(pc_rx, state_tx, lifx_id, discover, DeviceConfig::default());
@@ -192,6 +221,7 @@ pub fn run_auto_light(
         // state: state_rx,
         pc: pc_rx,
         scene: scene_rx,
+        scene_override: override_rx,
     }
 }
 
@@ -199,11 +229,13 @@ pub fn run_auto_light(
 pub struct SplitPowerColor {
     priority: usize,
     pc: PowerColor,
+    duration: Duration,
 }
 
 pub struct SplitOutputs {
     pub spc: stateful::Receiver<SplitPowerColor>,
     pub scene: stateful::Receiver<SceneName>,
+    pub scene_override: stateful::Receiver<Option<SceneOverride>>,
 }
 
 #[must_use]
@@ -216,22 +248,31 @@ pub fn run_split_light(
     id: &IdWithRoom,
     // lifx_id: LifxId,
     priority: usize,
+    number_of_zones: usize,
 ) -> SplitOutputs {
     // let (state_tx, state_rx) = stateful::create_pipe(format!("{lifx_id}-state"));
-    let (pc_rx, scene_rx) = switch_entity(
+    let (pc_rx, scene_rx, override_rx) = switch_entity(
         inputs.commands,
         persistent_state_database,
         id,
         scene_map,
         flash_color,
+        number_of_zones,
     );
 
-    let pc_rx = pc_rx.map(move |(_, pc)| SplitPowerColor { priority, pc });
+    let pc_rx = pc_rx.map(
+        move |(_, PowerColorTransition { pc, duration })| SplitPowerColor {
+            priority,
+            pc,
+            duration,
+        },
+    );
     // let pc_rx = run_merge_light(pc_rx, lifx_id, DeviceConfig::default());
 
     SplitOutputs {
         // state: state_rx,
         scene: scene_rx,
+        scene_override: override_rx,
         spc: pc_rx,
     }
 }
@@ -252,7 +293,7 @@ pub fn run_merge_light(
     split_rx: stateful::Receiver<SplitPowerColor>,
     id: &IdWithRoom,
     config: MergeLightConfig,
-) -> stateful::Receiver<PowerColor> {
+) -> stateful::Receiver<PowerColorTransition> {
     let (merged_tx, merged_rx) = stateful::create_pipe(format!("{id}/merged"));
     // let (state_tx, state_rx) = stateful::create_pipe(format!("{lifx_id}-state"));
 
@@ -264,7 +305,12 @@ pub fn run_merge_light(
             buffers.push(PowerColor::Off);
         }
 
-        while let Ok(SplitPowerColor { priority, pc }) = rx.recv().await {
+        while let Ok(SplitPowerColor {
+            priority,
+            pc,
+            duration,
+        }) = rx.recv().await
+        {
             if priority > len {
                 error!("Priority out of range: {}", priority);
                 continue;
@@ -298,7 +344,7 @@ pub fn run_merge_light(
                 PowerLevel::Off => PowerColor::Off,
             };
 
-            merged_tx.try_send(pc);
+            merged_tx.try_send(PowerColorTransition { pc, duration });
         }
     });
     merged_rx
@@ -307,10 +353,20 @@ pub fn run_merge_light(
 struct LightState {
     entity_s: stateful::Subscription<PowerColor>,
     psr: PersistentStateRow<SceneName>,
-    pc_tx: stateful::Sender<PowerColor>,
+    override_psr: PersistentStateRow<Option<SceneOverride>>,
+    pc_tx: stateful::Sender<PowerColorTransition>,
     scene_tx: stateful::Sender<SceneName>,
+    override_tx: stateful::Sender<Option<SceneOverride>>,
     flash_color: PowerColor,
+    number_of_zones: usize,
     last_value: Option<PowerColor>,
+    /// How long to take to fade to the next value from the scene.
+    transition: Duration,
+    /// The flash in progress, if any.
+    flash: Option<FlashState>,
+    scene_name: SceneName,
+    /// The scene to go back to, and when.
+    scene_override: Option<SceneOverride>,
 }
 
 fn switch_entity(
@@ -319,17 +375,33 @@ fn switch_entity(
     id: &IdWithRoom,
     scene_map: SceneMap,
     flash_color: PowerColor,
+    number_of_zones: usize,
 ) -> (
-    stateful::Receiver<PowerColor>,
+    stateful::Receiver<PowerColorTransition>,
     stateful::Receiver<SceneName>,
+    stateful::Receiver<Option<SceneOverride>>,
 ) {
     let (pc_tx, pc_rx) = stateful::create_pipe(format!("{id}/pc"));
     let (scene_tx, scene_rx) = stateful::create_pipe(format!("{id}/scenes"));
+    let (override_tx, override_rx) = stateful::create_pipe(format!("{id}/override"));
 
     {
         let psr = persistent_state_database.for_name(id, "scene");
-        let scene_name: SceneName = psr.load().unwrap_or_default();
-        let scene = scene_map.get(&scene_name).cloned().unwrap_or_default();
+        let override_psr = persistent_state_database.for_name(id, "override");
+        let mut scene_name: SceneName = psr.load().unwrap_or_default();
+        let mut scene_override: Option<SceneOverride> = override_psr.load().unwrap_or_default();
+
+        // An override that ended while we were not running.
+        if let Some(expired) = scene_override.take_if(|o| o.remaining(Utc::now()).is_zero()) {
+            scene_name = expired.revert_to;
+            psr.save(&scene_name)
+                .unwrap_or_else(|e| error!("Failed to save scene: {}", e));
+            override_psr
+                .save(&None)
+                .unwrap_or_else(|e| error!("Failed to save override: {}", e));
+        }
+
+        let scene = get_revert_scene(&scene_map, &scene_name);
 
         spawn(async move {
             let mut state = {
@@ -339,15 +411,23 @@ fn switch_entity(
                 LightState {
                     entity_s,
                     psr,
+                    override_psr,
                     pc_tx,
                     scene_tx,
+                    override_tx,
                     flash_color,
+                    number_of_zones,
                     last_value: None,
+                    transition: Duration::ZERO,
+                    flash: None,
+                    scene_name: scene.name.clone(),
+                    scene_override,
                 }
             };
 
             let mut rx_command_s = rx_command.subscribe().await;
             state.scene_tx.try_send(scene.name);
+            state.override_tx.try_send(state.scene_override.clone());
 
             loop {
                 tokio::select! {
@@ -364,48 +444,259 @@ fn switch_entity(
                     }
                     Ok(pc) = state.entity_s.recv() => {
                         state.last_value = Some(pc.clone());
-                        state.pc_tx.try_send(pc);
+                        // Changes during a flash are shown between flashes and when it ends.
+                        if state.flash.is_none() {
+                            let duration = std::mem::take(&mut state.transition);
+                            state.pc_tx.try_send(PowerColorTransition { pc, duration });
+                        }
+                    }
+                    Some(()) = maybe_sleep_until_flash(state.flash.as_ref()) => {
+                        flash_step(&mut state);
+                    }
+                    Some(()) = maybe_sleep_until_override(state.scene_override.as_ref()) => {
+                        end_override(&mut state, &scene_map).await;
                     }
                 }
             }
         });
     }
 
-    (pc_rx, scene_rx)
+    (pc_rx, scene_rx, override_rx)
+}
+
+async fn maybe_sleep_until_override(scene_override: Option<&SceneOverride>) -> Option<()> {
+    if let Some(scene_override) = scene_override {
+        sleep(scene_override.remaining(Utc::now())).await;
+        Some(())
+    } else {
+        None
+    }
+}
+
+/// Get the scene to go back to, or the default scene if it doesn't exist.
+fn get_revert_scene(scene_map: &SceneMap, scene_name: &SceneName) -> Scene {
+    scene_map.get(scene_name).cloned().unwrap_or_default()
+}
+
+fn set_override(state: &mut LightState, scene_override: Option<SceneOverride>) {
+    state
+        .override_psr
+        .save(&scene_override)
+        .unwrap_or_else(|e| error!("Failed to save override: {}", e));
+    state.override_tx.try_send(scene_override.clone());
+    state.scene_override = scene_override;
+}
+
+async fn start_override(
+    state: &mut LightState,
+    scene: &Scene,
+    duration: Duration,
+    revert_to: Option<SceneName>,
+    transition: Option<Duration>,
+) {
+    // Overriding an override still goes back to where we started.
+    let revert_to = revert_to
+        .or_else(|| state.scene_override.as_ref().map(|o| o.revert_to.clone()))
+        .unwrap_or_else(|| state.scene_name.clone());
+    let scene_override = SceneOverride::new(scene.name.clone(), revert_to, Utc::now(), duration);
+    debug!("Starting override: {scene_override:?}");
+    set_override(state, Some(scene_override));
+    set_scene(state, scene, transition).await;
+}
+
+async fn end_override(state: &mut LightState, scene_map: &SceneMap) {
+    if let Some(scene_override) = state.scene_override.clone() {
+        debug!("Ending override: {scene_override:?}");
+        set_override(state, None);
+        let scene = get_revert_scene(scene_map, &scene_override.revert_to);
+        set_scene(state, &scene, None).await;
+    }
 }
 
 async fn process_command(state: &mut LightState, command: LightCommand, scene_map: &SceneMap) {
     match command {
-        LightCommand::TurnOn { scene } => {
+        LightCommand::TurnOn {
+            scene,
+            transition,
+            duration,
+        } => {
             if let Some(scene) = scene_map.get(&scene) {
-                set_scene(state, scene).await;
+                if let Some(duration) = duration {
+                    start_override(state, scene, duration, None, transition).await;
+                } else {
+                    set_override(state, None);
+                    set_scene(state, scene, transition).await;
+                }
             } else {
                 error!("Invalid scene: {}", scene);
             }
         }
-        LightCommand::TurnOff => {
+        LightCommand::Override {
+            scene,
+            duration,
+            revert_to,
+            transition,
+        } => {
+            if let Some(scene) = scene_map.get(&scene) {
+                start_override(state, scene, duration, revert_to, transition).await;
+            } else {
+                error!("Invalid scene: {}", scene);
+            }
+        }
+        LightCommand::TurnOff { transition } => {
             let scene_name = SceneName::new("off".to_string());
             if let Some(scene) = scene_map.get(&scene_name) {
-                set_scene(state, scene).await;
+                set_override(state, None);
+                set_scene(state, scene, transition).await;
             } else {
                 error!("Invalid scene: {}", "off");
             }
         }
 
-        LightCommand::Flash => {
-            let pc = state.last_value.clone().unwrap_or(PowerColor::Off);
-            state.pc_tx.try_send(state.flash_color.clone());
-            sleep(Duration::from_millis(500)).await;
-            state.pc_tx.try_send(pc.clone());
-            sleep(Duration::from_millis(500)).await;
-            state.pc_tx.try_send(state.flash_color.clone());
-            sleep(Duration::from_millis(500)).await;
-            state.pc_tx.try_send(pc);
+        LightCommand::Flash(pattern) => {
+            start_flash(state, pattern);
         }
     }
 }
 
-async fn set_scene(state: &mut LightState, scene: &Scene) {
+/// A flash that is in progress.
+#[derive(Debug)]
+struct FlashState {
+    /// The colors before the flash started.
+    base: PowerColor,
+    /// The colors to show while the flash is on.
+    on: PowerColor,
+    /// The color to flash, over the zones in the pattern.
+    color: PowerColor,
+    pattern: FlashPattern,
+    /// How many times we have switched between on and off.
+    step: u32,
+    next: Instant,
+}
+
+/// Get the colors of every zone, repeating the colors to fill the zones.
+fn zone_colors(pc: &PowerColor, number_of_zones: usize) -> Vec<HSBK> {
+    let black = HSBK {
+        hue: 0.0,
+        saturation: 0.0,
+        brightness: 0.0,
+        kelvin: 3500,
+    };
+    let mut colors = vec![black; number_of_zones];
+    copy_colors_to_pos(pc, &mut colors, 0, number_of_zones);
+    colors
+}
+
+/// Get the colors to show while the flash is on.
+fn flash_on_colors(
+    base: &PowerColor,
+    flash: &PowerColor,
+    zones: Option<&Range<usize>>,
+    number_of_zones: usize,
+) -> PowerColor {
+    let Some(zones) = zones.filter(|_| number_of_zones > 1) else {
+        return flash.clone();
+    };
+
+    let mut colors = zone_colors(base, number_of_zones);
+    let flash = zone_colors(flash, number_of_zones);
+    let end = zones.end.min(number_of_zones);
+    let start = zones.start.min(end);
+    colors[start..end].copy_from_slice(&flash[start..end]);
+    PowerColor::On(Colors::Sequence(colors))
+}
+
+fn start_flash(state: &mut LightState, pattern: FlashPattern) {
+    if pattern.count == 0 {
+        return;
+    }
+
+    // A new flash still goes back to the colors before the first flash.
+    let base = state
+        .last_value
+        .clone()
+        .or_else(|| state.flash.take().map(|flash| flash.base))
+        .unwrap_or(PowerColor::Off);
+    let flash_color = pattern.color.map_or_else(
+        || state.flash_color.clone(),
+        |color| PowerColor::On(Colors::Single(color)),
+    );
+    let on = flash_on_colors(
+        &base,
+        &flash_color,
+        pattern.zones.as_ref(),
+        state.number_of_zones,
+    );
+
+    debug!("Starting flash: {pattern:?}");
+    let fade = Duration::from_millis(pattern.fade_ms);
+    state.pc_tx.try_send(PowerColorTransition {
+        pc: on.clone(),
+        duration: fade,
+    });
+    state.flash = Some(FlashState {
+        base,
+        on,
+        color: flash_color,
+        next: Instant::now() + Duration::from_millis(pattern.on_ms),
+        pattern,
+        step: 0,
+    });
+}
+
+fn flash_step(state: &mut LightState) {
+    let Some(flash) = &mut state.flash else {
+        return;
+    };
+
+    flash.step += 1;
+    let fade = Duration::from_millis(flash.pattern.fade_ms);
+    // Show anything that changed during the flash.
+    let base = state
+        .last_value
+        .clone()
+        .unwrap_or_else(|| flash.base.clone());
+
+    if flash.step + 1 >= flash.pattern.count.saturating_mul(2) {
+        // Finished, show the scene again.
+        let duration = std::mem::take(&mut state.transition).max(fade);
+        debug!("Finished flash, restoring: {base:?}");
+        state
+            .pc_tx
+            .try_send(PowerColorTransition { pc: base, duration });
+        state.flash = None;
+    } else if flash.step % 2 == 0 {
+        // Zones outside the flash keep showing the scene.
+        flash.on = flash_on_colors(
+            &base,
+            &flash.color,
+            flash.pattern.zones.as_ref(),
+            state.number_of_zones,
+        );
+        state.pc_tx.try_send(PowerColorTransition {
+            pc: flash.on.clone(),
+            duration: fade,
+        });
+        flash.next = Instant::now() + Duration::from_millis(flash.pattern.on_ms);
+    } else {
+        state.pc_tx.try_send(PowerColorTransition {
+            pc: base,
+            duration: fade,
+        });
+        flash.next = Instant::now() + Duration::from_millis(flash.pattern.off_ms);
+    }
+}
+
+async fn maybe_sleep_until_flash(flash: Option<&FlashState>) -> Option<()> {
+    if let Some(flash) = flash {
+        sleep_until(flash.next).await;
+        Some(())
+    } else {
+        None
+    }
+}
+
+async fn set_scene(state: &mut LightState, scene: &Scene, transition: Option<Duration>) {
     // state.scene = scene;
     // state.entity = state.entities.get_scene_entity(scene);
     state
@@ -413,7 +704,13 @@ async fn set_scene(state: &mut LightState, scene: &Scene) {
         .save(&scene.name)
         .unwrap_or_else(|e| error!("Failed to save scene: {}", e));
     state.scene_tx.try_send(scene.name.clone());
-    state.pc_tx.try_send(PowerColor::Off);
+    state.scene_name = scene.name.clone();
+    state.transition = transition.unwrap_or(scene.transition);
+    if state.transition.is_zero() && state.flash.is_none() {
+        state
+            .pc_tx
+            .try_send(PowerColorTransition::immediate(PowerColor::Off));
+    }
     state.entity_s = scene.rx.subscribe().await;
     state.last_value = None;
 }
@@ -432,16 +729,18 @@ fn copy_colors_to_pos(add_colors: &PowerColor, colors: &mut [HSBK], offset: usiz
     }
 }
 
+const ONE_HOUR: TimeDelta = time_delta_constant!(1 hours);
+
 #[derive(Debug)]
 struct EntryTimeValue<T> {
-    duration: Duration,
+    duration: TimeDelta,
     value: T,
 }
 
 fn get_schedule_for_evt_list<T: Copy>(
     etv_list: &[EntryTimeValue<T>],
-    morning_start: chrono::NaiveTime,
-    evening_start: chrono::NaiveTime,
+    morning_start: TimeOfDay,
+    evening_start: TimeOfDay,
 ) -> Vec<scheduler::Entry<T>> {
     let mut scheduler_entries = Vec::new();
 
@@ -449,89 +748,156 @@ fn get_schedule_for_evt_list<T: Copy>(
     let all_but_last = etv_list.len().saturating_sub(1);
 
     for etv in etv_list.iter().take(all_but_last) {
-        morning_time -= etv.duration;
-        scheduler_entries.push(scheduler::Entry {
-            scheduled_time: morning_time,
-            value: etv.value,
-        });
+        morning_time = morning_time - etv.duration;
+        scheduler_entries.push(scheduler::Entry::new(morning_time, etv.value));
     }
 
     let mut evening_time = evening_start;
     for etv in etv_list.iter().skip(1) {
-        scheduler_entries.push(scheduler::Entry {
-            scheduled_time: evening_time,
-            value: etv.value,
-        });
-        evening_time += etv.duration;
+        scheduler_entries.push(scheduler::Entry::new(evening_time, etv.value));
+        evening_time = evening_time + etv.duration;
     }
 
     scheduler_entries
 }
 
-pub fn auto_brightness_level() -> stateful::Receiver<f32> {
+pub fn auto_brightness_level(
+    config: &AutoLightConfig,
+    location: Option<Location>,
+    classifier: Option<scheduler::Classifier>,
+    timezone: Tz,
+) -> stateful::Receiver<f32> {
+    if let Some(entries) = &config.brightness {
+        return scheduler::scheduler(
+            "auto-brightness-level",
+            entries.clone(),
+            location,
+            classifier,
+            timezone,
+        );
+    }
+
     let etv_list = [
         EntryTimeValue {
-            duration: Duration::from_hours(0),
+            duration: TimeDelta::zero(),
             value: 100.0,
         },
         EntryTimeValue {
-            duration: Duration::from_hours(1),
+            duration: ONE_HOUR,
             value: 50.0,
         },
         EntryTimeValue {
-            duration: Duration::from_hours(1),
+            duration: ONE_HOUR,
             value: 25.0,
         },
         EntryTimeValue {
-            duration: Duration::from_hours(1),
+            duration: ONE_HOUR,
             value: 15.0,
         },
         EntryTimeValue {
-            duration: Duration::from_hours(1),
+            duration: ONE_HOUR,
             value: 5.0,
         },
     ];
 
-    let schedule_entries = get_schedule_for_evt_list(
-        &etv_list,
-        naive_time_constant!(08:00:00),
-        naive_time_constant!(19:00:00),
-    );
-    scheduler::scheduler("auto-brightness-level", schedule_entries)
+    let schedule_entries = get_schedule_for_evt_list(&etv_list, config.morning, config.evening);
+    scheduler::scheduler(
+        "auto-brightness-level",
+        schedule_entries,
+        location,
+        None,
+        timezone,
+    )
 }
 
-pub fn auto_temperature_level() -> stateful::Receiver<u16> {
+pub fn auto_temperature_level(
+    config: &AutoLightConfig,
+    location: Option<Location>,
+    classifier: Option<scheduler::Classifier>,
+    timezone: Tz,
+) -> stateful::Receiver<u16> {
+    if let Some(entries) = &config.temperature {
+        return scheduler::scheduler(
+            "auto-temperature-level",
+            entries.clone(),
+            location,
+            classifier,
+            timezone,
+        );
+    }
+
     let offset = 250;
 
     let etv_list = [
         EntryTimeValue {
-            duration: Duration::from_hours(0),
+            duration: TimeDelta::zero(),
             value: 3500,
         },
         EntryTimeValue {
-            duration: Duration::from_hours(1),
+            duration: ONE_HOUR,
             value: 3500 - offset,
         },
         EntryTimeValue {
-            duration: Duration::from_hours(1),
+            duration: ONE_HOUR,
             value: 3500 - offset * 2,
         },
         EntryTimeValue {
-            duration: Duration::from_hours(1),
+            duration: ONE_HOUR,
             value: 3500 - offset * 3,
         },
         EntryTimeValue {
-            duration: Duration::from_hours(1),
+            duration: ONE_HOUR,
             value: 3500 - offset * 4,
         },
     ];
 
-    let schedule_entries = get_schedule_for_evt_list(
-        &etv_list,
-        naive_time_constant!(08:00:00),
-        naive_time_constant!(19:00:00),
-    );
-    scheduler::scheduler("auto-temperature-level", schedule_entries)
+    let schedule_entries = get_schedule_for_evt_list(&etv_list, config.morning, config.evening);
+    scheduler::scheduler(
+        "auto-temperature-level",
+        schedule_entries,
+        location,
+        None,
+        timezone,
+    )
+}
+
+const CIRCADIAN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Automatic brightness and color temperature levels.
+#[derive(Clone)]
+pub struct AutoLevels {
+    pub brightness: stateful::Receiver<f32>,
+    pub temperature: stateful::Receiver<u16>,
+}
+
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn circadian_values(config: &CircadianConfig, elevation: f64) -> (f32, u16) {
+    let brightness = config.brightness.level(elevation).clamp(0.0, 100.0);
+    let temperature = config.kelvin.level(elevation).clamp(1500.0, 9000.0).round() as u16;
+    (brightness, temperature)
+}
+
+/// Get light levels that follow the height of the sun, updated every minute.
+pub fn circadian_levels(config: CircadianConfig, location: Location, name: &str) -> AutoLevels {
+    let (brightness_tx, brightness) = stateful::create_pipe(format!("{name}-brightness"));
+    let (temperature_tx, temperature) = stateful::create_pipe(format!("{name}-temperature"));
+
+    spawn(async move {
+        while !brightness_tx.is_closed() || !temperature_tx.is_closed() {
+            let elevation = sun_elevation(Utc::now(), &location);
+            let (brightness, temperature) = circadian_values(&config, elevation);
+            debug!("Circadian sun elevation {elevation:.1}: {brightness:.1}% {temperature}K");
+            brightness_tx.try_send(brightness);
+            temperature_tx.try_send(temperature);
+            sleep(CIRCADIAN_INTERVAL).await;
+        }
+    });
+
+    AutoLevels {
+        brightness,
+        temperature,
+    }
 }
 
 enum AutoLightState {
@@ -612,3 +978,60 @@ pub fn auto_light_color(
 
     rx
 }
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use float_cmp::assert_approx_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_circadian_values() {
+        let config: CircadianConfig = serde_json::from_str("{}").unwrap();
+
+        let (brightness, temperature) = circadian_values(&config, -30.0);
+        assert_approx_eq!(f32, brightness, 5.0);
+        assert_eq!(temperature, 2200);
+
+        let (brightness, temperature) = circadian_values(&config, 60.0);
+        assert_approx_eq!(f32, brightness, 100.0);
+        assert_eq!(temperature, 5000);
+
+        let (dawn_brightness, dawn_temperature) = circadian_values(&config, -1.0);
+        assert!(dawn_brightness > 5.0 && dawn_brightness < 100.0);
+        assert!(dawn_temperature > 2200 && dawn_temperature < 5000);
+    }
+
+    #[test]
+    fn test_flash_on_colors() {
+        let hsbk = |hue: f32| HSBK {
+            hue,
+            saturation: 1.0,
+            brightness: 100.0,
+            kelvin: 3500,
+        };
+        let black = HSBK {
+            hue: 0.0,
+            saturation: 0.0,
+            brightness: 0.0,
+            kelvin: 3500,
+        };
+        let base = PowerColor::On(Colors::Sequence(vec![hsbk(10.0), hsbk(20.0)]));
+        let flash = PowerColor::On(Colors::Single(hsbk(240.0)));
+
+        let pc = flash_on_colors(&base, &flash, None, 4);
+        assert_eq!(pc, flash);
+
+        let pc = flash_on_colors(&base, &flash, Some(&(1..3)), 1);
+        assert_eq!(pc, flash);
+
+        let pc = flash_on_colors(&base, &flash, Some(&(1..3)), 4);
+        let expected = vec![hsbk(10.0), hsbk(240.0), hsbk(240.0), hsbk(20.0)];
+        assert_eq!(pc, PowerColor::On(Colors::Sequence(expected)));
+
+        let pc = flash_on_colors(&PowerColor::Off, &flash, Some(&(2..10)), 3);
+        let expected = vec![black, black, hsbk(240.0)];
+        assert_eq!(pc, PowerColor::On(Colors::Sequence(expected)));
+    }
+}
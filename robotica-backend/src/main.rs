@@ -10,6 +10,7 @@
 mod amber;
 mod car;
 mod config;
+mod effects;
 mod ha;
 mod hdmi_matrix;
 mod influxdb;
@@ -19,6 +20,7 @@ mod metrics;
 mod monitor_location;
 mod open_epaper_link;
 mod tesla;
+mod vacation;
 
 use std::collections::HashMap;
 use std::time::Duration;
@@ -26,34 +28,45 @@ use std::time::Duration;
 use amber::car::ChargeRequest;
 use amber::rules;
 use anyhow::Result;
-use chrono::Local;
+use chrono::TimeZone;
+use chrono_tz::Tz;
 use lights::{run_auto_light, run_split_light, Scene, SceneMap, SplitPowerColor};
 use robotica_common::mqtt::{Json, MqttMessage, Parsed, QoS, Retain};
 use robotica_common::owntracks;
 use robotica_common::robotica::audio::MessagePriority;
 use robotica_common::robotica::commands::Command;
 use robotica_common::robotica::entities::{AnyId, Id, IdWithRoom};
-use robotica_common::robotica::lights::{LightCommand, PowerColor, PowerState, SceneName, State};
+use robotica_common::robotica::lights::{
+    LightCommand, PowerColor, PowerColorTransition, PowerState, SceneName, State,
+};
 use robotica_common::robotica::message::Message;
 use robotica_common::robotica::tasks::{Payload, Task};
-use robotica_common::scheduler::Importance;
+use robotica_common::scheduler::{CreateHistoryEntry, Importance};
 use robotica_common::shelly;
 use robotica_common::zigbee2mqtt::{Door, DoorState};
+use robotica_tokio::database::schedule_history::add_history_entry;
 use robotica_tokio::devices::lifx::{DeviceConfig, DiscoverConfig};
 use robotica_tokio::devices::occupancy::{self, OccupiedState};
-use robotica_tokio::devices::presence_tracker::{is_any_presence_in_room, PresenceTrackerValue};
-use robotica_tokio::devices::{fake_switch, lifx, presence_tracker};
+use robotica_tokio::devices::presence_tracker::{
+    is_any_presence_at_home, is_any_presence_in_room, PresenceTrackerValue,
+};
+use robotica_tokio::devices::{fade, fake_switch, lifx, presence_tracker, zigbee2mqtt};
 use robotica_tokio::pipes::delays::DelayInputOptions;
 use robotica_tokio::pipes::{stateful, stateless, Subscriber};
 use robotica_tokio::scheduling::calendar::CalendarEntry;
-use robotica_tokio::scheduling::executor::executor;
+use robotica_tokio::scheduling::executor::{self, executor, ExecutorTx};
 use robotica_tokio::scheduling::sequencer::Sequence;
+use robotica_tokio::scheduling::{lint, runtime};
 use robotica_tokio::services::persistent_state::PersistentStateDatabase;
-use robotica_tokio::spawn;
+use robotica_tokio::services::tesla::api::ChargingStateEnum;
+use robotica_tokio::{host_timezone, is_debug_mode, spawn};
+use tokio::sync::mpsc;
 use tracing::{debug, error, info, instrument, span};
 
 use crate::amber::water_heater;
-use crate::lights::{auto_brightness_level, auto_light_color, auto_temperature_level};
+use crate::lights::{
+    auto_brightness_level, auto_light_color, auto_temperature_level, circadian_levels,
+};
 
 use robotica_tokio::services::http;
 use robotica_tokio::services::mqtt::{mqtt_channel, run_client, SendOptions, Subscriptions};
@@ -75,6 +88,10 @@ async fn main() -> Result<()> {
         panic!("Error loading config: {e}");
     });
 
+    if std::env::args().any(|arg| arg == "--check-schedule") {
+        std::process::exit(check_schedule(config));
+    }
+
     let _guard = logging::init_tracing_subscriber(&config.logging).unwrap_or_else(|e| {
         panic!("Error initializing tracing subscriber: {e}");
     });
@@ -111,6 +128,32 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Check the schedule config and print any problems, returning the exit code.
+fn check_schedule(config: config::Config) -> i32 {
+    let Some(mut executor_config) = config.executor else {
+        eprintln!("No executor configured");
+        return 1;
+    };
+
+    executor_config.location = executor_config.location.or(config.location);
+
+    let timezone = executor_config.get_timezone();
+    let today = chrono::Utc::now().with_timezone(&timezone).date_naive();
+    let issues = lint::lint(&executor_config, &timezone, today, lint::DEFAULT_DAYS);
+
+    for issue in &issues {
+        println!("{issue}");
+    }
+
+    if issues.is_empty() {
+        println!("Schedule config is OK");
+        0
+    } else {
+        println!("Found {} problem(s)", issues.len());
+        1
+    }
+}
+
 /// Global state for initialization.
 pub struct InitState {
     /// Subscriptions to MQTT topics.
@@ -125,9 +168,9 @@ pub struct InitState {
 }
 
 #[allow(clippy::unnecessary_wraps)]
-fn calendar_to_sequence(
+fn calendar_to_sequence<T: TimeZone>(
     event: CalendarEntry,
-    _timezone: Local,
+    _timezone: T,
     calendar_message_config: Option<&config::CalendarMessageConfig>,
 ) -> Option<Sequence> {
     let (start_time, end_time) = calendar_start_top_times(&event);
@@ -160,13 +203,22 @@ fn calendar_to_sequence(
         start_time,
         end_time,
         latest_time: end_time,
+        original_start_time: None,
         tasks,
         mark: None,
         if_cond: None,
+        run_if: None,
+        task_run_if: vec![],
+        task_expect: vec![],
+        outcomes: vec![],
         classifications: None,
         options: None,
         zero_time: true,
         repeat_number: 1,
+        timezone: None,
+        after: None,
+        requires: vec![],
+        reminders: None,
         status: None,
 
         // These fields are set by executor.
@@ -177,6 +229,32 @@ fn calendar_to_sequence(
     })
 }
 
+fn start_executor(
+    state: &mut InitState,
+    executor_config: executor::Config,
+    calendar_message_config: Option<config::CalendarMessageConfig>,
+    timezone: Tz,
+    inputs: runtime::Inputs,
+    message_sink: stateless::Sender<Message>,
+    history: mpsc::UnboundedSender<CreateHistoryEntry>,
+) -> ExecutorTx {
+    executor(
+        &mut state.subscriptions,
+        state.mqtt.clone(),
+        executor_config,
+        Box::new(move |event, timezone| {
+            calendar_to_sequence(event, timezone, calendar_message_config.as_ref())
+        }),
+        timezone,
+        inputs,
+        message_sink,
+        history,
+    )
+    .unwrap_or_else(|err| {
+        panic!("Failed to start executor: {err}");
+    })
+}
+
 const fn calendar_start_top_times(
     event: &CalendarEntry,
 ) -> (chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>) {
@@ -217,16 +295,34 @@ async fn setup_pipes(
         });
     }
 
+    let mut executor_inputs = runtime::Inputs::new();
+    for (tracker_id, tracker) in &presence_trackers {
+        let is_home = tracker.clone().map(|(_, value)| value.room.is_some());
+        executor_inputs.add(format!("{tracker_id}_home"), is_home);
+    }
+    executor_inputs.add(
+        "anyone_home",
+        is_any_presence_at_home(presence_trackers.clone()),
+    );
+
     // presence_trackers[0].clone().for_each(|(_, present)| {
     //     error!("Is Brian present? {present:#?}");
     // });
 
     // motion sensors, etc.
+    let mut sensors_for_room: HashMap<String, Vec<occupancy::WeightedSensor>> = HashMap::new();
     let occupancy_sensors: HashMap<IdWithRoom, stateful::Receiver<OccupiedState>> = config
         .occupancy_sensors
         .into_iter()
         .map(|sensor| {
             let rx = occupancy::subscribe(&sensor.config, &mut state.subscriptions);
+            sensors_for_room
+                .entry(sensor.id.room.clone())
+                .or_default()
+                .push(occupancy::WeightedSensor {
+                    rx: rx.clone(),
+                    weight: sensor.weight,
+                });
             (sensor.id, rx)
         })
         .collect();
@@ -245,6 +341,39 @@ async fn setup_pipes(
         });
     }
 
+    let occupancy_for_room: HashMap<String, stateful::Receiver<OccupiedState>> = sensors_for_room
+        .into_iter()
+        .map(|(room, sensors)| {
+            let fusion = config
+                .occupancy_rooms
+                .get(&room)
+                .cloned()
+                .unwrap_or_default();
+            let rx = occupancy::fuse_room(&room, sensors, fusion);
+            (room, rx)
+        })
+        .collect();
+
+    for (room, occupancy) in &occupancy_for_room {
+        let room_id = match Id::new(room) {
+            Ok(room_id) => room_id,
+            Err(err) => {
+                error!("Invalid room {room} for occupancy: {err}");
+                continue;
+            }
+        };
+        let mqtt = state.mqtt.clone();
+        occupancy.clone().for_each(move |(_, value)| {
+            debug!("Occupancy for room {room_id} value: {value:?}");
+            mqtt.try_serialize_send(
+                room_id.get_state_topic("occupancy"),
+                &Json(value),
+                Retain::Retain,
+                QoS::AtLeastOnce,
+            );
+        });
+    }
+
     // is_any_presence_in_room("brian", presence_trackers.clone()).for_each(|(_, present)| {
     //     error!("Is anyone present in brian? {present}");
     // });
@@ -280,12 +409,21 @@ async fn setup_pipes(
         let amber_account_id = Id::new("amber_account")
             .unwrap_or_else(|e| panic!("amber_account_id must be a valid Id: {e}"));
 
-        let (prices, usage) = amber::run(&amber_account_id, amber_config).unwrap_or_else(|e| {
-            panic!("Error running amber: {e}");
-        });
+        let usage_state = state
+            .persistent_state_database
+            .for_name(&amber_account_id, "usage_timer");
+        let (prices, usage) = amber::run(&amber_account_id, amber_config, usage_state)
+            .unwrap_or_else(|e| {
+                panic!("Error running amber: {e}");
+            });
         amber::logging::log_prices(prices.clone(), &config.influxdb);
         amber::logging::log_usage(usage, &config.influxdb);
 
+        // The current interval is found when conditions are checked, so it is never stale.
+        executor_inputs.add_timed("amber_price", prices.clone(), |prices, now| {
+            prices.current(now).map(|price| price.per_kwh).into()
+        });
+
         prices
             .clone()
             .map(|(_, prices)| prices.list.clone())
@@ -299,7 +437,14 @@ async fn setup_pipes(
             monitor_water_heater(&mut state, water_heater, &prices, message_sink.clone());
         }
 
-        monitor_cars(&config.cars, &mut state, &postgres, &prices, &message_sink);
+        monitor_cars(
+            &config.cars,
+            &mut state,
+            &postgres,
+            &prices,
+            &message_sink,
+            &mut executor_inputs,
+        );
     } else {
         info!("No amber configuration found; skipping water heater and car monitoring");
     }
@@ -312,12 +457,6 @@ async fn setup_pipes(
         monitor_owntracks(&mut state, owntracks_config, &postgres, &message_sink);
     }
 
-    if let Some(http_config) = config.http {
-        http::run(state.mqtt.clone(), http_config, postgres.clone())
-            .await
-            .unwrap_or_else(|e| panic!("Error running http server: {e}"));
-    }
-
     for hdmi_matrix_config in config.hdmi_matrices {
         hdmi_matrix::run(&mut state, &hdmi_matrix_config.id, &hdmi_matrix_config.addr);
     }
@@ -331,22 +470,6 @@ async fn setup_pipes(
         metric.monitor(&mut state.subscriptions, &config.influxdb);
     }
 
-    if let Some(executor_config) = config.executor {
-        let calendar_message_config = config.calendar_message;
-        executor(
-            &mut state.subscriptions,
-            state.mqtt.clone(),
-            executor_config,
-            Box::new(move |event, timezone| {
-                calendar_to_sequence(event, timezone, calendar_message_config.as_ref())
-            }),
-            Local,
-        )
-        .unwrap_or_else(|err| {
-            panic!("Failed to start executor: {err}");
-        });
-    }
-
     let night_mode_for_room: HashMap<String, stateful::Receiver<bool>> = config
         .night_mode
         .into_iter()
@@ -360,25 +483,135 @@ async fn setup_pipes(
         })
         .collect();
 
-    if let Some(lifx_config) = &config.lifx {
+    for (room, night_mode) in &night_mode_for_room {
+        executor_inputs.add(format!("night_mode_{room}"), night_mode.clone());
+    }
+
+    let vacation = config.vacation.map(|vacation_config| {
+        let rx = fake_switch(
+            &mut state,
+            &vacation_config.id.get_command_topic(""),
+            &vacation_config.id.get_state_topic("power"),
+        );
+        executor_inputs.add("vacation_mode", rx.clone());
+        (vacation_config, rx)
+    });
+
+    let date_classifier =
+        config.executor.as_ref().and_then(|executor_config| {
+            match executor_config.load_date_classifier() {
+                Ok(classifier) => Some(classifier),
+                Err(err) => {
+                    error!("Failed to load date classifier: {err}");
+                    None
+                }
+            }
+        });
+
+    let timezone = config
+        .executor
+        .as_ref()
+        .map_or_else(host_timezone, executor::Config::get_timezone);
+    info!("Schedule timezone: {timezone}");
+
+    let executor_tx = config.executor.map(|mut executor_config| {
+        executor_config.location = executor_config.location.or(config.location);
+        let (history_tx, history_rx) = mpsc::unbounded_channel();
+        record_schedule_history(history_rx, postgres.clone());
+        start_executor(
+            &mut state,
+            executor_config,
+            config.calendar_message,
+            timezone,
+            executor_inputs,
+            message_sink.clone(),
+            history_tx,
+        )
+    });
+
+    if let Some(http_config) = config.http {
+        http::run(
+            state.mqtt.clone(),
+            http_config,
+            postgres.clone(),
+            executor_tx,
+        )
+        .await
+        .unwrap_or_else(|e| panic!("Error running http server: {e}"));
+    }
+
+    let light_commands = if config.lights.is_empty() && config.strips.is_empty() {
+        info!("No lights configured; skipping light setup");
+        HashMap::new()
+    } else {
+        let levels = match (&config.auto_light.circadian, config.location) {
+            (Some(circadian), Some(location)) => {
+                circadian_levels(circadian.clone(), location, "auto-circadian")
+            }
+            (circadian, _) => {
+                if circadian.is_some() {
+                    error!("Circadian auto light needs a location; using fixed times");
+                }
+                lights::AutoLevels {
+                    brightness: auto_brightness_level(
+                        &config.auto_light,
+                        config.location,
+                        date_classifier.clone(),
+                        timezone,
+                    ),
+                    temperature: auto_temperature_level(
+                        &config.auto_light,
+                        config.location,
+                        date_classifier,
+                        timezone,
+                    ),
+                }
+            }
+        };
+
+        let levels_for_room = config
+            .auto_light
+            .circadian_rooms
+            .iter()
+            .filter_map(|(room, circadian)| {
+                let Some(location) = config.location else {
+                    error!("Circadian auto light for {room} needs a location");
+                    return None;
+                };
+                let name = format!("auto-circadian-{room}");
+                let levels = circadian_levels(circadian.clone(), location, &name);
+                Some((room.clone(), levels))
+            })
+            .collect();
+
         let shared = SharedAutoLight {
-            brightness: auto_brightness_level(),
-            temperature: auto_temperature_level(),
+            brightness: levels.brightness,
+            temperature: levels.temperature,
+            levels_for_room,
             night_mode_for_room,
             presence_trackers,
-            occupancy_sensors,
+            occupancy_for_room,
         };
 
         setup_lights(
             &mut state,
-            lifx_config,
+            config.lifx.as_ref(),
             &config.lights,
             &config.strips,
             &shared,
         )
-        .await;
-    } else {
-        info!("No lifx configuration found; skipping light setup");
+        .await
+    };
+
+    if let Some((vacation_config, enabled)) = vacation {
+        vacation::run(
+            enabled,
+            vacation_config,
+            light_commands,
+            state.mqtt.clone(),
+            config.location,
+            timezone,
+        );
     }
 
     run_client(state.subscriptions, mqtt_rx, config.mqtt).unwrap_or_else(|e| {
@@ -386,6 +619,20 @@ async fn setup_pipes(
     });
 }
 
+/// Keep a record of what the executor did, as it forgets old days.
+fn record_schedule_history(
+    mut rx: mpsc::UnboundedReceiver<CreateHistoryEntry>,
+    postgres: sqlx::PgPool,
+) {
+    spawn(async move {
+        while let Some(entry) = rx.recv().await {
+            if let Err(err) = add_history_entry(&postgres, &entry).await {
+                error!("Failed to record schedule history {entry:?}: {err}");
+            }
+        }
+    });
+}
+
 fn monitor_door(
     state: &mut InitState,
     config: config::DoorMonitorConfig,
@@ -419,9 +666,11 @@ fn monitor_door(
             if old.is_some() {
                 info!("{room_name} state: {door:?}");
                 let action = match door {
-                    DoorState::Open => LightCommand::TurnOff,
+                    DoorState::Open => LightCommand::TurnOff { transition: None },
                     DoorState::Closed => LightCommand::TurnOn {
                         scene: scene_name.clone(),
+                        transition: None,
+                        duration: None,
                     },
                 };
                 let command = Command::Light(action);
@@ -522,6 +771,7 @@ fn monitor_cars(
     postgres: &sqlx::Pool<sqlx::Postgres>,
     prices: &stateful::Receiver<std::sync::Arc<amber::Prices>>,
     message_sink: &stateless::Sender<Message>,
+    executor_inputs: &mut runtime::Inputs,
 ) {
     let id = Id::new("tesla_account")
         .unwrap_or_else(|e| panic!("must be a valid tesla account id: {e}"));
@@ -535,7 +785,10 @@ fn monitor_cars(
     });
 
     for (car, tesla) in teslas {
-        monitor_tesla(car, tesla, state, postgres, prices, &token, message_sink);
+        let charging_state =
+            monitor_tesla(car, tesla, state, postgres, prices, &token, message_sink);
+        let plugged_in = charging_state.map(|(_, state)| state.is_plugged_in());
+        executor_inputs.add(format!("{}_plugged_in", car.id), plugged_in);
     }
 }
 
@@ -548,7 +801,7 @@ fn monitor_tesla(
     prices: &stateful::Receiver<std::sync::Arc<amber::Prices>>,
     token: &stateful::Receiver<std::sync::Arc<robotica_tokio::services::tesla::api::Token>>,
     message_sink: &stateless::Sender<Message>,
-) {
+) -> stateful::Receiver<ChargingStateEnum> {
     let auto_charge = state
         .subscriptions
         .subscribe_into_stateless::<Json<Command>>(car.id.get_command_topic("auto_charge"));
@@ -641,6 +894,8 @@ fn monitor_tesla(
 
     tesla::monitor_doors::monitor(car, monitor_doors_receivers).send_to(message_sink);
     tesla::plug_in_reminder::plug_in_reminder(car, should_plugin_stream).send_to(message_sink);
+
+    receivers.charging_state
 }
 
 #[instrument(fields(id=%config.id), skip_all)]
@@ -693,45 +948,100 @@ fn fake_switch(
 
 async fn setup_lights(
     state: &mut InitState,
-    lifx: &config::LifxConfig,
+    lifx: Option<&config::LifxConfig>,
     lights: &[config::LightConfig],
     strips: &[config::StripConfig],
     shared: &SharedAutoLight,
-) {
-    let lifx_config = DiscoverConfig {
-        broadcast: lifx.broadcast.clone(),
-        poll_time: std::time::Duration::from_secs(10),
-        device_timeout: std::time::Duration::from_secs(45),
-        api_timeout: std::time::Duration::from_secs(1),
-        num_retries: 3,
+) -> HashMap<IdWithRoom, stateless::Sender<Json<Command>>> {
+    let discover = if let Some(lifx) = lifx {
+        let lifx_config = DiscoverConfig {
+            broadcast: lifx.broadcast.clone(),
+            poll_time: std::time::Duration::from_secs(10),
+            device_timeout: std::time::Duration::from_secs(45),
+            api_timeout: std::time::Duration::from_secs(1),
+            num_retries: 3,
+            state_poll_time: lifx.state_poll_time,
+            reconcile: lifx.reconcile,
+            static_devices: lights
+                .iter()
+                .map(|light| &light.device)
+                .chain(strips.iter().map(|strip| &strip.device))
+                .filter_map(config::LightDeviceConfig::static_lifx_device)
+                .collect(),
+        };
+        let discovery = lifx::discover(lifx_config)
+            .await
+            .unwrap_or_else(|e| panic!("Error discovering lifx devices: {e}"));
+        discovery.inventory.send_to_mqtt_json(
+            &state.mqtt,
+            "robotica/state/lifx/inventory",
+            &SendOptions::new(),
+        );
+        Some(discovery.devices)
+    } else {
+        info!("No lifx configuration found; lifx lights will be offline");
+        None
     };
-    let discover = lifx::discover(lifx_config)
-        .await
-        .unwrap_or_else(|e| panic!("Error discovering lifx devices: {e}"));
+    let discover = discover.as_ref();
 
     let shared_scenes = lights::get_default_scenes();
+    let mut light_commands = HashMap::new();
 
     for light_config in lights {
-        auto_light(state, &discover, &shared_scenes, shared, light_config);
+        let commands = auto_light(state, discover, &shared_scenes, shared, light_config);
+        light_commands.insert(light_config.id.clone(), commands);
     }
 
     for strip_config in strips {
-        strip_light(state, &discover, &shared_scenes, shared, strip_config);
+        strip_light(
+            state,
+            discover,
+            &shared_scenes,
+            shared,
+            strip_config,
+            &mut light_commands,
+        );
     }
+
+    light_commands
+}
+
+/// Get the commands for a light from MQTT, and a sender for commands from inside the backend.
+fn light_inputs(
+    init_state: &mut InitState,
+    id: &IdWithRoom,
+) -> (stateless::Sender<Json<Command>>, lights::Inputs) {
+    let (tx, rx) = stateless::create_pipe(format!("{id}_commands"));
+    init_state
+        .subscriptions
+        .subscribe_into_stateless::<Json<Command>>(id.get_command_topic(""))
+        .send_to(&tx);
+    (tx, lights::Inputs { commands: rx })
 }
 
 struct SharedAutoLight {
     brightness: stateful::Receiver<f32>,
     temperature: stateful::Receiver<u16>,
+    levels_for_room: HashMap<String, lights::AutoLevels>,
     night_mode_for_room: HashMap<String, stateful::Receiver<bool>>,
     presence_trackers: HashMap<Id, stateful::Receiver<PresenceTrackerValue>>,
-    occupancy_sensors: HashMap<IdWithRoom, stateful::Receiver<OccupiedState>>,
+    occupancy_for_room: HashMap<String, stateful::Receiver<OccupiedState>>,
 }
 
 impl SharedAutoLight {
     fn get_auto_scene(&self, room: &str, fixed_brightness: Option<f32>) -> Scene {
+        let levels = self.levels_for_room.get(room);
+        let temperature = levels.map_or_else(
+            || self.temperature.clone(),
+            |levels| levels.temperature.clone(),
+        );
         let brightness = fixed_brightness.map_or_else(
-            || self.brightness.clone(),
+            || {
+                levels.map_or_else(
+                    || self.brightness.clone(),
+                    |levels| levels.brightness.clone(),
+                )
+            },
             |level| {
                 stateful::static_entity(
                     level,
@@ -750,13 +1060,9 @@ impl SharedAutoLight {
 
         let presence = is_any_presence_in_room(room, self.presence_trackers.clone());
 
-        let default_id = IdWithRoom::new(room, "default")
-            .unwrap_or_else(|e| panic!("occupancy_id must be a valid IdWithRoom: {e}"));
         let occupied = self
-            .occupancy_sensors
-            // TODO: only the "default" sensor is consumed here for now; the
-            // OR-merge across all sensors in a room happens in a later refactor.
-            .get(&default_id)
+            .occupancy_for_room
+            .get(room)
             .cloned()
             .unwrap_or_else(|| {
                 stateful::static_entity(
@@ -765,13 +1071,7 @@ impl SharedAutoLight {
                 )
             });
 
-        let rx = auto_light_color(
-            brightness,
-            self.temperature.clone(),
-            night_mode,
-            presence,
-            occupied,
-        );
+        let rx = auto_light_color(brightness, temperature, night_mode, presence, occupied);
 
         Scene::new(rx, SceneName::new("auto"))
     }
@@ -779,16 +1079,12 @@ impl SharedAutoLight {
 
 fn auto_light(
     init_state: &mut InitState,
-    discover: &stateless::Receiver<lifx::Device>,
+    discover: Option<&stateless::Receiver<lifx::Device>>,
     shared_scenes: &SceneMap,
     shared: &SharedAutoLight,
     config: &config::LightConfig,
-) {
-    let inputs = lights::Inputs {
-        commands: init_state
-            .subscriptions
-            .subscribe_into_stateless::<Json<Command>>(config.id.get_command_topic("")),
-    };
+) -> stateless::Sender<Json<Command>> {
+    let (commands, inputs) = light_inputs(init_state, &config.id);
 
     let hash_map: HashMap<SceneName, Scene> = config
         .scenes
@@ -809,7 +1105,11 @@ fn auto_light(
         scene_map
     };
 
-    let lights::Outputs { pc, scene } = run_auto_light(
+    let lights::Outputs {
+        pc,
+        scene,
+        scene_override,
+    } = run_auto_light(
         inputs,
         &init_state.persistent_state_database,
         scene_map,
@@ -823,7 +1123,15 @@ fn auto_light(
         &SendOptions::new(),
     );
 
+    scene_override.send_to_mqtt_json(
+        &init_state.mqtt,
+        config.id.get_state_topic("override"),
+        &SendOptions::new(),
+    );
+
     send_to_device(&config.id, &config.device, pc, discover, init_state, false);
+
+    commands
 }
 
 fn split_light(
@@ -833,16 +1141,15 @@ fn split_light(
     priority: usize,
     strip_config: &config::StripConfig,
     split_config: &config::SplitLightConfig,
-) -> stateful::Receiver<SplitPowerColor> {
+) -> (
+    stateful::Receiver<SplitPowerColor>,
+    stateless::Sender<Json<Command>>,
+) {
     let id = &split_config.id;
     let scenes = &split_config.scenes;
     let flash_color = &split_config.flash_color;
 
-    let inputs = lights::Inputs {
-        commands: init_state
-            .subscriptions
-            .subscribe_into_stateless::<Json<Command>>(id.get_command_topic("")),
-    };
+    let (commands, inputs) = light_inputs(init_state, id);
 
     let hash_map: HashMap<SceneName, Scene> = scenes
         .iter()
@@ -862,13 +1169,18 @@ fn split_light(
         scene_map
     };
 
-    let lights::SplitOutputs { spc, scene } = run_split_light(
+    let lights::SplitOutputs {
+        spc,
+        scene,
+        scene_override,
+    } = run_split_light(
         inputs,
         &init_state.persistent_state_database,
         scene_map,
         flash_color.clone(),
         id,
         priority,
+        split_config.number,
     );
 
     scene.send_to_mqtt_string(
@@ -877,15 +1189,22 @@ fn split_light(
         &SendOptions::new(),
     );
 
-    spc
+    scene_override.send_to_mqtt_json(
+        &init_state.mqtt,
+        id.get_state_topic("override"),
+        &SendOptions::new(),
+    );
+
+    (spc, commands)
 }
 
 fn strip_light(
     init_state: &mut InitState,
-    discover: &stateless::Receiver<lifx::Device>,
+    discover: Option<&stateless::Receiver<lifx::Device>>,
     shared_scenes: &SceneMap,
     shared: &SharedAutoLight,
     config: &config::StripConfig,
+    light_commands: &mut HashMap<IdWithRoom, stateless::Sender<Json<Command>>>,
 ) {
     let span = span!(tracing::Level::INFO, "strip_light", id = %config.id);
     let _guard = span.enter();
@@ -893,8 +1212,10 @@ fn strip_light(
     let (combined_tx, combined_rx) = stateful::create_pipe("combined");
 
     for (priority, split) in config.splits.iter().enumerate() {
-        split_light(init_state, shared_scenes, shared, priority, config, split)
-            .send_to(&combined_tx);
+        let (spc, commands) =
+            split_light(init_state, shared_scenes, shared, priority, config, split);
+        spc.send_to(&combined_tx);
+        light_commands.insert(split.id.clone(), commands);
     }
 
     let splits: Vec<_> = config
@@ -920,22 +1241,34 @@ fn strip_light(
 fn send_to_device(
     id: &IdWithRoom,
     device: &config::LightDeviceConfig,
-    pc: stateful::Receiver<PowerColor>,
-    discover: &stateless::Receiver<lifx::Device>,
-    init_state: &InitState,
+    pc: stateful::Receiver<PowerColorTransition>,
+    discover: Option<&stateless::Receiver<lifx::Device>>,
+    init_state: &mut InitState,
     multiple_zones: bool,
 ) {
     let id_clone = id.clone();
     let output = match device {
-        config::LightDeviceConfig::Lifx { lifx_id } => lifx::device_entity(
+        config::LightDeviceConfig::Lifx { lifx_id, .. } => {
+            if let Some(discover) = discover {
+                let config = DeviceConfig::default()
+                    .set_multiple_zones(multiple_zones)
+                    .set_addr(device.static_lifx_device().map(|device| device.addr))
+                    .set_dry_run(is_debug_mode());
+                lifx::device_entity(pc, *lifx_id, discover, config)
+            } else {
+                error!(%id, "No lifx configuration found for {lifx_id}");
+                stateful::static_entity(State::Offline, format!("{id}_no_lifx"))
+            }
+        }
+        config::LightDeviceConfig::Zigbee2mqtt(config) => zigbee2mqtt::device_entity(
             pc,
-            *lifx_id,
-            discover,
-            DeviceConfig::default().set_multiple_zones(multiple_zones),
+            config.clone(),
+            init_state.mqtt.clone(),
+            &mut init_state.subscriptions,
         ),
         config::LightDeviceConfig::Debug { lifx_id } => {
             let lifx_id = *lifx_id;
-            pc.map(move |(_, pc)| {
+            fade::software_fade(pc, format!("{id}/faded")).map(move |(_, pc)| {
                 info!(%id_clone, "Debug {lifx_id}: {pc:?}");
                 State::Online(pc)
             })
@@ -0,0 +1,455 @@
+//! Make the house look occupied while we are away.
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
+use rand::Rng;
+use robotica_common::{
+    mqtt::{Json, QoS, Retain},
+    robotica::{
+        audio::{AudioCommand, MessagePriority, MusicCommand},
+        commands::Command,
+        entities::IdWithRoom,
+        lights::LightCommand,
+    },
+    solar::Location,
+};
+use robotica_macro::time_delta_constant;
+use robotica_tokio::{
+    pipes::{stateful, stateless, Subscriber, Subscription},
+    services::mqtt::MqttTx,
+    spawn,
+};
+use tokio::{select, time::sleep};
+use tracing::{debug, error, info};
+
+use crate::config::{VacationActivityConfig, VacationConfig, VacationTargetConfig};
+
+const ONE_DAY: TimeDelta = time_delta_constant!(1 days);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Start,
+    Stop,
+}
+
+/// Identifies one planned instance of an activity, the date and the index in the config.
+type ActivityId = (NaiveDate, usize);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Event {
+    activity: ActivityId,
+    datetime: DateTime<Utc>,
+    action: Action,
+    target: VacationTargetConfig,
+    title: String,
+}
+
+fn jitter(rng: &mut impl Rng, max: TimeDelta) -> TimeDelta {
+    let seconds = max.num_seconds().abs();
+    if seconds == 0 {
+        TimeDelta::zero()
+    } else {
+        TimeDelta::seconds(rng.random_range(-seconds..=seconds))
+    }
+}
+
+/// Choose which activities happen on the given date, and when.
+fn plan_day<T: TimeZone>(
+    activities: &[VacationActivityConfig],
+    date: NaiveDate,
+    timezone: &T,
+    location: Option<&Location>,
+    rng: &mut impl Rng,
+) -> Vec<Event> {
+    let mut events = Vec::with_capacity(activities.len() * 2);
+
+    for (index, activity) in activities.iter().enumerate() {
+        if !rng.random_bool(activity.probability.clamp(0.0, 1.0)) {
+            continue;
+        }
+
+        let start = match activity.start.to_utc(date, timezone, location) {
+            Ok(start) => start + jitter(rng, activity.jitter),
+            Err(err) => {
+                error!("Cannot plan {} on {date}: {err}", activity.title);
+                continue;
+            }
+        };
+        let stop = start + activity.duration + jitter(rng, activity.jitter);
+
+        events.push(Event {
+            activity: (date, index),
+            datetime: start,
+            action: Action::Start,
+            target: activity.target.clone(),
+            title: activity.title.clone(),
+        });
+        events.push(Event {
+            activity: (date, index),
+            datetime: stop.max(start),
+            action: Action::Stop,
+            target: activity.target.clone(),
+            title: activity.title.clone(),
+        });
+    }
+
+    events.sort_by_key(|event| event.datetime);
+    events
+}
+
+/// Drop activities that should already have started.
+///
+/// The whole activity is dropped, we must not stop something we never started.
+fn upcoming(events: Vec<Event>, now: DateTime<Utc>) -> impl Iterator<Item = Event> {
+    let missed: Vec<ActivityId> = events
+        .iter()
+        .filter(|event| event.action == Action::Start && event.datetime < now)
+        .map(|event| event.activity)
+        .collect();
+
+    events
+        .into_iter()
+        .filter(move |event| event.datetime >= now && !missed.contains(&event.activity))
+}
+
+fn target_command(target: &VacationTargetConfig, action: Action) -> (&IdWithRoom, Command) {
+    match (target, action) {
+        (VacationTargetConfig::Light { id, scene }, Action::Start) => (
+            id,
+            Command::Light(LightCommand::TurnOn {
+                scene: scene.clone(),
+                transition: None,
+                duration: None,
+            }),
+        ),
+        (VacationTargetConfig::Light { id, .. }, Action::Stop) => (
+            id,
+            Command::Light(LightCommand::TurnOff { transition: None }),
+        ),
+        (VacationTargetConfig::Music { id, play_list }, action) => {
+            // Never include a message, we don't want announcements while away.
+            let music = match action {
+                Action::Start => MusicCommand {
+                    play_list: Some(play_list.clone()),
+                    stop: None,
+                },
+                Action::Stop => MusicCommand {
+                    play_list: None,
+                    stop: Some(true),
+                },
+            };
+            let command = AudioCommand {
+                message: None,
+                priority: MessagePriority::default(),
+                sound: None,
+                music: Some(music),
+                volume: None,
+                pre_tasks: None,
+                post_tasks: None,
+            };
+            (id, Command::Audio(command))
+        }
+    }
+}
+
+struct State {
+    light_commands: HashMap<IdWithRoom, stateless::Sender<Json<Command>>>,
+    mqtt: MqttTx,
+    active: HashMap<ActivityId, VacationTargetConfig>,
+}
+
+impl State {
+    fn process(&mut self, event: &Event) {
+        match event.action {
+            Action::Start => {
+                self.active.insert(event.activity, event.target.clone());
+                self.send(&event.target, Action::Start);
+            }
+            Action::Stop => {
+                if self.active.remove(&event.activity).is_none() {
+                    debug!("Vacation: {} was not started", event.title);
+                } else if self.active.values().any(|target| target == &event.target) {
+                    debug!(
+                        "Vacation: {} is still used by another activity",
+                        event.title
+                    );
+                } else {
+                    self.send(&event.target, Action::Stop);
+                }
+            }
+        }
+    }
+
+    fn send(&self, target: &VacationTargetConfig, action: Action) {
+        let (id, command) = target_command(target, action);
+
+        match target {
+            VacationTargetConfig::Light { .. } => match self.light_commands.get(id) {
+                Some(tx) => tx.try_send(Json(command)),
+                None => error!("Vacation light {id} not found"),
+            },
+            VacationTargetConfig::Music { .. } => {
+                self.mqtt.try_serialize_send(
+                    id.get_command_topic(""),
+                    &Json(command),
+                    Retain::NoRetain,
+                    QoS::ExactlyOnce,
+                );
+            }
+        }
+    }
+
+    fn stop_all(&mut self) {
+        let mut stopped: Vec<VacationTargetConfig> = Vec::new();
+        for target in std::mem::take(&mut self.active).into_values() {
+            if !stopped.contains(&target) {
+                self.send(&target, Action::Stop);
+                stopped.push(target);
+            }
+        }
+    }
+}
+
+fn next_midnight<T: TimeZone>(now: DateTime<Utc>, timezone: &T) -> DateTime<Utc> {
+    let tomorrow = now.with_timezone(timezone).date_naive() + ONE_DAY;
+    tomorrow
+        .and_hms_opt(0, 0, 0)
+        .and_then(|midnight| midnight.and_local_timezone(timezone.clone()).earliest())
+        .map_or(now + ONE_DAY, |midnight| midnight.with_timezone(&Utc))
+}
+
+fn duration_until(datetime: DateTime<Utc>) -> std::time::Duration {
+    (datetime - Utc::now()).to_std().unwrap_or_default()
+}
+
+/// Run the vacation simulation while `enabled` is true.
+///
+/// Lights are driven through the same command pipes as MQTT commands, music is sent to
+/// the audio players. Days are planned in the schedule `timezone`.
+pub fn run(
+    enabled: stateful::Receiver<bool>,
+    config: VacationConfig,
+    light_commands: HashMap<IdWithRoom, stateless::Sender<Json<Command>>>,
+    mqtt: MqttTx,
+    location: Option<Location>,
+    timezone: Tz,
+) {
+    let mut state = State {
+        light_commands,
+        mqtt,
+        active: HashMap::new(),
+    };
+
+    spawn(async move {
+        let mut enabled_s = enabled.subscribe().await;
+        let mut is_enabled = false;
+        let mut events = Vec::new().into_iter().peekable();
+        let mut replan_time = Utc::now();
+
+        loop {
+            let now = Utc::now();
+
+            if is_enabled && now >= replan_time {
+                let today = now.with_timezone(&timezone).date_naive();
+                let planned = plan_day(
+                    &config.activities,
+                    today,
+                    &timezone,
+                    location.as_ref(),
+                    &mut rand::rng(),
+                );
+                info!("Vacation: planned {} events for {today}", planned.len());
+
+                // Keep events from yesterday, so activities can run past midnight.
+                let mut all: Vec<Event> = events.collect();
+                all.extend(upcoming(planned, now));
+                all.sort_by_key(|event| event.datetime);
+                events = all.into_iter().peekable();
+                replan_time = next_midnight(now, &timezone);
+            }
+
+            let next_time = match events.peek() {
+                Some(event) if is_enabled => event.datetime.min(replan_time),
+                _ if is_enabled => replan_time,
+                _ => now + ONE_DAY,
+            };
+
+            select! {
+                () = sleep(duration_until(next_time)) => {
+                    while let Some(event) = events.next_if(|event| event.datetime <= Utc::now()) {
+                        debug!("Vacation: {:?} {}", event.action, event.title);
+                        state.process(&event);
+                    }
+                }
+                Ok(enabled) = enabled_s.recv() => {
+                    info!("Vacation mode: {enabled}");
+                    if enabled && !is_enabled {
+                        replan_time = Utc::now();
+                    } else if !enabled {
+                        events = Vec::new().into_iter().peekable();
+                        state.stop_all();
+                    }
+                    is_enabled = enabled;
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use chrono::NaiveTime;
+    use rand::{rngs::StdRng, SeedableRng};
+    use robotica_common::{robotica::lights::SceneName, solar::TimeOfDay};
+
+    use super::*;
+
+    fn activity(start: &str, probability: f64) -> VacationActivityConfig {
+        VacationActivityConfig {
+            title: format!("Lounge at {start}"),
+            target: VacationTargetConfig::Light {
+                id: IdWithRoom::new("lounge", "light").unwrap(),
+                scene: SceneName::new("auto"),
+            },
+            start: TimeOfDay::from(start.parse::<NaiveTime>().unwrap()),
+            duration: TimeDelta::hours(2),
+            jitter: TimeDelta::minutes(15),
+            probability,
+        }
+    }
+
+    #[test]
+    fn test_plan_day() {
+        let activities = vec![activity("19:00:00", 1.0), activity("06:00:00", 0.0)];
+        let date = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let events = plan_day(&activities, date, &Utc, None, &mut rng);
+
+        assert_eq!(events.len(), 2);
+        let [start, stop] = &events[..] else {
+            unreachable!()
+        };
+        let planned = date.and_hms_opt(19, 0, 0).unwrap().and_utc();
+
+        assert_eq!(start.action, Action::Start);
+        assert_eq!(stop.action, Action::Stop);
+        assert!((start.datetime - planned).abs() <= TimeDelta::minutes(15));
+        let duration = stop.datetime - start.datetime;
+        assert!(duration >= TimeDelta::minutes(105));
+        assert!(duration <= TimeDelta::minutes(135));
+    }
+
+    fn event(index: usize, hour: u32, action: Action) -> Event {
+        let date = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        Event {
+            activity: (date, index),
+            datetime: date.and_hms_opt(hour, 0, 0).unwrap().and_utc(),
+            action,
+            target: VacationTargetConfig::Light {
+                id: IdWithRoom::new("lounge", "light").unwrap(),
+                scene: SceneName::new("auto"),
+            },
+            title: format!("Activity {index}"),
+        }
+    }
+
+    #[test]
+    fn test_next_midnight() {
+        // 7am on the 19th in Melbourne.
+        let now = Utc.with_ymd_and_hms(2026, 10, 18, 20, 0, 0).unwrap();
+        let midnight = next_midnight(now, &chrono_tz::Australia::Melbourne);
+        assert_eq!(
+            midnight,
+            Utc.with_ymd_and_hms(2026, 10, 19, 13, 0, 0).unwrap()
+        );
+
+        let midnight = next_midnight(now, &Utc);
+        assert_eq!(
+            midnight,
+            Utc.with_ymd_and_hms(2026, 10, 19, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_upcoming() {
+        let events = vec![
+            event(0, 6, Action::Start),
+            event(1, 7, Action::Start),
+            event(0, 8, Action::Stop),
+            event(1, 9, Action::Stop),
+            event(2, 10, Action::Start),
+            event(2, 11, Action::Stop),
+        ];
+        let now = event(0, 8, Action::Stop).datetime - TimeDelta::minutes(90);
+
+        // Activity 0 already started, so its stop is dropped too.
+        let events: Vec<Event> = upcoming(events, now).collect();
+        assert_eq!(
+            events,
+            vec![
+                event(1, 7, Action::Start),
+                event(1, 9, Action::Stop),
+                event(2, 10, Action::Start),
+                event(2, 11, Action::Stop),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_overlapping_activities() {
+        let id = IdWithRoom::new("lounge", "light").unwrap();
+        let (tx, rx) = stateless::create_pipe("lounge_light");
+        let mut rx_s = rx.subscribe().await;
+        let (mqtt, _mqtt_rx) = robotica_tokio::services::mqtt::mqtt_channel();
+        let mut state = State {
+            light_commands: HashMap::from([(id, tx)]),
+            mqtt,
+            active: HashMap::new(),
+        };
+
+        let is_turn_off = |command: &Json<Command>| {
+            matches!(command.0, Command::Light(LightCommand::TurnOff { .. }))
+        };
+
+        state.process(&event(0, 6, Action::Start));
+        state.process(&event(1, 7, Action::Start));
+        assert!(!is_turn_off(&rx_s.recv().await.unwrap()));
+        assert!(!is_turn_off(&rx_s.recv().await.unwrap()));
+
+        // The light is still used by activity 1, so the next command is the marker.
+        state.process(&event(0, 8, Action::Stop));
+        assert_eq!(state.active.len(), 1);
+        state.send(&event(2, 8, Action::Start).target, Action::Start);
+        assert!(!is_turn_off(&rx_s.recv().await.unwrap()));
+
+        // Stopping everything must still turn it off.
+        state.stop_all();
+        assert!(is_turn_off(&rx_s.recv().await.unwrap()));
+        assert!(state.active.is_empty());
+
+        // A stop for an activity that was never started does nothing.
+        state.process(&event(1, 9, Action::Stop));
+        state.send(&event(2, 9, Action::Start).target, Action::Start);
+        assert!(!is_turn_off(&rx_s.recv().await.unwrap()));
+    }
+
+    #[test]
+    fn test_music_never_announces() {
+        let target = VacationTargetConfig::Music {
+            id: IdWithRoom::new("lounge", "robotica").unwrap(),
+            play_list: "jazz".to_string(),
+        };
+
+        for action in [Action::Start, Action::Stop] {
+            let (_, command) = target_command(&target, action);
+            let Command::Audio(audio) = command else {
+                panic!("Expected audio command");
+            };
+            assert!(audio.message.is_none());
+        }
+    }
+}
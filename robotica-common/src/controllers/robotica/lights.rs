@@ -109,8 +109,10 @@ impl ControllerTrait for Controller {
         let action = match get_press_on_or_off(display_state, self.config.action) {
             TurnOnOff::TurnOn => LightCommand::TurnOn {
                 scene: self.config.scene.clone(),
+                transition: None,
+                duration: None,
             },
-            TurnOnOff::TurnOff => LightCommand::TurnOff,
+            TurnOnOff::TurnOff => LightCommand::TurnOff { transition: None },
         };
 
         let payload = Json(Command::Light(action));
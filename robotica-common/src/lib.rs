@@ -33,6 +33,8 @@ pub mod datetime;
 
 pub mod scheduler;
 
+pub mod solar;
+
 pub use chrono::NaiveTime;
 
 pub use chrono::TimeDelta;
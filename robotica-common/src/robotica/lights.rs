@@ -2,13 +2,17 @@
 
 use std::{
     fmt::{Display, Formatter},
+    iter::zip,
+    ops::Range,
     str::Utf8Error,
+    time::Duration,
 };
 
+use chrono::{DateTime, TimeDelta, Utc};
 use serde::{Deserialize, Serialize};
 use tap::Pipe;
 
-use crate::mqtt::MqttMessage;
+use crate::{datetime::duration, mqtt::MqttMessage};
 
 /// A LIFX device's power level.
 #[derive(Serialize, Deserialize)]
@@ -38,6 +42,37 @@ pub struct HSBK {
 
 impl Eq for HSBK {}
 
+impl HSBK {
+    /// Get the color a fraction of the way from this color to another color.
+    ///
+    /// The hue takes the shortest way around the color wheel. If either color has no
+    /// saturation, the hue of the other color is used.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    pub fn interpolate(self, other: Self, fraction: f32) -> Self {
+        let fraction = fraction.clamp(0.0, 1.0);
+        let lerp = |from: f32, to: f32| (to - from).mul_add(fraction, from);
+
+        let (from_hue, to_hue) = match (self.saturation > 0.0, other.saturation > 0.0) {
+            (true, true) | (false, false) => (self.hue, other.hue),
+            (true, false) => (self.hue, self.hue),
+            (false, true) => (other.hue, other.hue),
+        };
+        let mut delta = (to_hue - from_hue).rem_euclid(360.0);
+        if delta > 180.0 {
+            delta -= 360.0;
+        }
+
+        Self {
+            hue: delta.mul_add(fraction, from_hue).rem_euclid(360.0),
+            saturation: lerp(self.saturation, other.saturation),
+            brightness: lerp(self.brightness, other.brightness),
+            kelvin: lerp(f32::from(self.kelvin), f32::from(other.kelvin)).round() as u16,
+        }
+    }
+}
+
 /// One or more colors
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -61,6 +96,83 @@ pub enum PowerColor {
     On(Colors),
 }
 
+impl PowerColor {
+    /// Get the power color a fraction of the way from this power color to another.
+    ///
+    /// A light that is off is treated as showing the other colors at zero brightness. The
+    /// result is only off if both are off, or if the fraction reaches the end and the
+    /// other is off.
+    #[must_use]
+    pub fn interpolate(&self, other: &Self, fraction: f32) -> Self {
+        let (from, to) = match (self, other) {
+            (Self::Off, Self::Off) => return Self::Off,
+            (_, Self::Off) if fraction >= 1.0 => return Self::Off,
+            (Self::On(from), Self::On(to)) => (from.clone(), to.clone()),
+            (Self::On(from), Self::Off) => (from.clone(), from.with_brightness(0.0)),
+            (Self::Off, Self::On(to)) => (to.with_brightness(0.0), to.clone()),
+        };
+
+        let colors = match (from, to) {
+            (Colors::Single(from), Colors::Single(to)) => {
+                Colors::Single(from.interpolate(to, fraction))
+            }
+            (from, to) => {
+                let from = from.to_vec();
+                let to = to.to_vec();
+                let len = from.len().max(to.len());
+                let colors = zip(from.iter().cycle(), to.iter().cycle())
+                    .take(len)
+                    .map(|(from, to)| from.interpolate(*to, fraction))
+                    .collect();
+                Colors::Sequence(colors)
+            }
+        };
+
+        Self::On(colors)
+    }
+}
+
+impl Colors {
+    fn with_brightness(&self, brightness: f32) -> Self {
+        let set = |color: &HSBK| HSBK {
+            brightness,
+            ..*color
+        };
+        match self {
+            Self::Single(color) => Self::Single(set(color)),
+            Self::Sequence(colors) => Self::Sequence(colors.iter().map(set).collect()),
+        }
+    }
+
+    fn to_vec(&self) -> Vec<HSBK> {
+        match self {
+            Self::Single(color) => vec![*color],
+            Self::Sequence(colors) => colors.clone(),
+        }
+    }
+}
+
+/// A power color, and how long the light should take to change to it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PowerColorTransition {
+    /// The power color to change to.
+    pub pc: PowerColor,
+
+    /// How long to take to change, zero to change immediately.
+    pub duration: Duration,
+}
+
+impl PowerColorTransition {
+    /// Change to the power color immediately.
+    #[must_use]
+    pub const fn immediate(pc: PowerColor) -> Self {
+        Self {
+            pc,
+            duration: Duration::ZERO,
+        }
+    }
+}
+
 /// A V2 light power state
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -162,25 +274,337 @@ pub enum LightCommand {
     TurnOn {
         /// The scene to use
         scene: SceneName,
+
+        /// How long to take to fade to the scene, instead of the scene's default.
+        #[serde(with = "crate::datetime::with_option_duration")]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        transition: Option<Duration>,
+
+        /// How long to show the scene before going back to the previous scene.
+        #[serde(with = "crate::datetime::with_option_duration")]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        duration: Option<Duration>,
+    },
+
+    /// Show a scene for a while, then go back to another scene.
+    Override {
+        /// The scene to show.
+        scene: SceneName,
+
+        /// How long to show the scene.
+        #[serde(with = "crate::datetime::with_duration")]
+        duration: Duration,
+
+        /// The scene to go back to, instead of the scene before the override.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        revert_to: Option<SceneName>,
+
+        /// How long to take to fade to the scene, instead of the scene's default.
+        #[serde(with = "crate::datetime::with_option_duration")]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        transition: Option<Duration>,
     },
 
     /// Turn the switch off.
-    TurnOff,
+    TurnOff {
+        /// How long to take to fade out.
+        #[serde(with = "crate::datetime::with_option_duration")]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        transition: Option<Duration>,
+    },
 
     /// Flash the light.
-    Flash,
+    Flash(FlashPattern),
+}
+
+/// How to flash a light.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct FlashPattern {
+    /// The color to flash, instead of the light's flash color.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<HSBK>,
+
+    /// How many times to flash.
+    pub count: u32,
+
+    /// How long to show the flash color each time, in milliseconds.
+    pub on_ms: u64,
+
+    /// How long to show the previous colors between flashes, in milliseconds.
+    pub off_ms: u64,
+
+    /// How long to fade between the colors, in milliseconds.
+    pub fade_ms: u64,
+
+    /// Only flash these zones of a strip, instead of the whole light.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zones: Option<Range<usize>>,
+}
+
+impl Default for FlashPattern {
+    fn default() -> Self {
+        Self {
+            color: None,
+            count: 2,
+            on_ms: 500,
+            off_ms: 500,
+            fade_ms: 0,
+            zones: None,
+        }
+    }
 }
 
 impl Display for LightCommand {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            LightCommand::TurnOn { scene } => {
-                write!(f, "turn_on scene {scene}")
+            LightCommand::TurnOn {
+                scene,
+                transition,
+                duration,
+            } => {
+                write!(f, "turn_on scene {scene}")?;
+                write_transition(f, *transition)?;
+                if let Some(duration) = duration {
+                    write!(f, " for {}", duration::to_string(*duration))?;
+                }
+                Ok(())
             }
-            LightCommand::TurnOff => write!(f, "turn_off"),
-            LightCommand::Flash => {
-                write!(f, "flash")
+            LightCommand::Override {
+                scene,
+                duration,
+                revert_to,
+                transition,
+            } => {
+                write!(
+                    f,
+                    "override scene {scene} for {}",
+                    duration::to_string(*duration)
+                )?;
+                if let Some(revert_to) = revert_to {
+                    write!(f, " then {revert_to}")?;
+                }
+                write_transition(f, *transition)
             }
+            LightCommand::TurnOff { transition } => {
+                write!(f, "turn_off")?;
+                write_transition(f, *transition)
+            }
+            LightCommand::Flash(pattern) => {
+                write!(f, "flash")?;
+                if *pattern != FlashPattern::default() {
+                    write!(f, " {} times", pattern.count)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A scene that is only shown until a certain time.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SceneOverride {
+    /// The scene shown during the override.
+    pub scene: SceneName,
+
+    /// The scene to go back to when the override ends.
+    pub revert_to: SceneName,
+
+    /// When the override ends.
+    pub ends: DateTime<Utc>,
+}
+
+impl SceneOverride {
+    /// Create an override that starts now and lasts for a duration.
+    #[must_use]
+    pub fn new(
+        scene: SceneName,
+        revert_to: SceneName,
+        now: DateTime<Utc>,
+        duration: Duration,
+    ) -> Self {
+        let ends = TimeDelta::from_std(duration)
+            .ok()
+            .and_then(|duration| now.checked_add_signed(duration))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
+        Self {
+            scene,
+            revert_to,
+            ends,
+        }
+    }
+
+    /// How long until the override ends.
+    #[must_use]
+    pub fn remaining(&self, now: DateTime<Utc>) -> Duration {
+        (self.ends - now).to_std().unwrap_or(Duration::ZERO)
+    }
+}
+
+fn write_transition(f: &mut Formatter<'_>, transition: Option<Duration>) -> std::fmt::Result {
+    if let Some(transition) = transition {
+        write!(f, " over {}", duration::to_string(transition))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+
+    const fn hsbk(hue: f32, saturation: f32, brightness: f32, kelvin: u16) -> HSBK {
+        HSBK {
+            hue,
+            saturation,
+            brightness,
+            kelvin,
         }
     }
+
+    #[test]
+    fn test_hsbk_interpolate() {
+        let from = hsbk(350.0, 100.0, 100.0, 2500);
+        let to = hsbk(30.0, 50.0, 0.0, 3500);
+
+        assert_eq!(from.interpolate(to, 0.0), from);
+        assert_eq!(from.interpolate(to, 1.0), hsbk(30.0, 50.0, 0.0, 3500));
+        assert_eq!(from.interpolate(to, 0.5), hsbk(10.0, 75.0, 50.0, 3000));
+        assert_eq!(from.interpolate(to, 2.0), to);
+
+        let white = hsbk(200.0, 0.0, 100.0, 3500);
+        assert_eq!(white.interpolate(to, 0.5), hsbk(30.0, 25.0, 50.0, 3500));
+    }
+
+    #[test]
+    fn test_power_color_interpolate() {
+        let on = PowerColor::On(Colors::Single(hsbk(0.0, 0.0, 100.0, 3500)));
+
+        assert_eq!(
+            PowerColor::Off.interpolate(&on, 0.25),
+            PowerColor::On(Colors::Single(hsbk(0.0, 0.0, 25.0, 3500)))
+        );
+        assert_eq!(
+            on.interpolate(&PowerColor::Off, 0.5),
+            PowerColor::On(Colors::Single(hsbk(0.0, 0.0, 50.0, 3500)))
+        );
+        assert_eq!(on.interpolate(&PowerColor::Off, 1.0), PowerColor::Off);
+
+        let sequence = PowerColor::On(Colors::Sequence(vec![
+            hsbk(0.0, 0.0, 0.0, 3500),
+            hsbk(0.0, 0.0, 50.0, 3500),
+        ]));
+        assert_eq!(
+            on.interpolate(&sequence, 0.5),
+            PowerColor::On(Colors::Sequence(vec![
+                hsbk(0.0, 0.0, 50.0, 3500),
+                hsbk(0.0, 0.0, 75.0, 3500),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_light_command_transition() {
+        let command: LightCommand = serde_json::from_str(r#"{"action":"turn_off"}"#).unwrap();
+        assert_eq!(command, LightCommand::TurnOff { transition: None });
+
+        let command: LightCommand = serde_json::from_str(
+            r#"{"action":"turn_on","scene":"bedtime","transition":"00:10:00"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            command,
+            LightCommand::TurnOn {
+                scene: SceneName::new("bedtime"),
+                transition: Some(duration::minutes(10)),
+                duration: None,
+            }
+        );
+        assert_eq!(command.to_string(), "turn_on scene bedtime over 00:10:00");
+    }
+
+    #[test]
+    fn test_light_command_duration() {
+        let command: LightCommand =
+            serde_json::from_str(r#"{"action":"turn_on","scene":"party","duration":"01:00:00"}"#)
+                .unwrap();
+        assert_eq!(
+            command,
+            LightCommand::TurnOn {
+                scene: SceneName::new("party"),
+                transition: None,
+                duration: Some(duration::hours(1)),
+            }
+        );
+        assert_eq!(command.to_string(), "turn_on scene party for 01:00:00");
+
+        let command: LightCommand = serde_json::from_str(
+            r#"{"action":"override","scene":"party","duration":"00:30:00","revert_to":"auto"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            command,
+            LightCommand::Override {
+                scene: SceneName::new("party"),
+                duration: duration::minutes(30),
+                revert_to: Some(SceneName::new("auto")),
+                transition: None,
+            }
+        );
+        assert_eq!(
+            command.to_string(),
+            "override scene party for 00:30:00 then auto"
+        );
+    }
+
+    #[test]
+    fn test_light_command_flash() {
+        let command: LightCommand = serde_json::from_str(r#"{"action":"flash"}"#).unwrap();
+        assert_eq!(command, LightCommand::Flash(FlashPattern::default()));
+        assert_eq!(command.to_string(), "flash");
+        assert_eq!(
+            serde_json::to_string(&command).unwrap(),
+            r#"{"action":"flash","count":2,"on_ms":500,"off_ms":500,"fade_ms":0}"#
+        );
+
+        let command: LightCommand = serde_json::from_str(
+            r#"{"action":"flash","color":{"hue":0.0,"saturation":100.0,"brightness":100.0,"kelvin":3500},"count":3,"zones":{"start":2,"end":5}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            command,
+            LightCommand::Flash(FlashPattern {
+                color: Some(hsbk(0.0, 100.0, 100.0, 3500)),
+                count: 3,
+                zones: Some(2..5),
+                ..FlashPattern::default()
+            })
+        );
+        assert_eq!(command.to_string(), "flash 3 times");
+    }
+
+    #[test]
+    fn test_scene_override_remaining() {
+        let now = DateTime::parse_from_rfc3339("2024-01-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let scene_override = SceneOverride::new(
+            SceneName::new("party"),
+            SceneName::new("auto"),
+            now,
+            duration::minutes(30),
+        );
+        assert_eq!(
+            scene_override.ends,
+            DateTime::parse_from_rfc3339("2024-01-01T12:30:00Z").unwrap()
+        );
+        assert_eq!(
+            scene_override.remaining(now + TimeDelta::minutes(7)),
+            duration::minutes(23)
+        );
+        assert_eq!(
+            scene_override.remaining(now + TimeDelta::hours(1)),
+            Duration::ZERO
+        );
+    }
 }
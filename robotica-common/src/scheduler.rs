@@ -6,11 +6,14 @@ use std::{
     time::Duration,
 };
 
-use chrono::{NaiveDate, Utc};
+use chrono::{NaiveDate, TimeDelta, Utc};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::{datetime::DateTime, robotica::tasks::Task};
+use crate::{
+    datetime::{time_delta, DateTime},
+    robotica::tasks::Task,
+};
 
 /// The status of the Sequence.
 #[derive(Deserialize, Serialize, Copy, Clone, Debug, Eq, PartialEq)]
@@ -54,6 +57,14 @@ pub enum MarkStatus {
     /// The tasks are already done.
     #[serde(rename = "done")]
     Done,
+
+    /// The tasks are to be run at a different time.
+    #[serde(rename = "postponed")]
+    Postponed {
+        /// How much later the sequence should start, negative to start earlier.
+        #[serde(with = "crate::datetime::with_time_delta")]
+        offset: TimeDelta,
+    },
 }
 
 impl Display for MarkStatus {
@@ -61,6 +72,9 @@ impl Display for MarkStatus {
         match self {
             MarkStatus::Cancelled => write!(f, "Cancelled"),
             MarkStatus::Done => write!(f, "Done"),
+            MarkStatus::Postponed { offset } => {
+                write!(f, "Postponed by {}", time_delta::to_string(*offset))
+            }
         }
     }
 }
@@ -103,6 +117,40 @@ pub enum MarkError {
     Utf8Error(#[from] std::str::Utf8Error),
 }
 
+/// What happened to a task when its sequence was started.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum TaskOutcome {
+    /// The task was sent.
+    Sent,
+
+    /// The task was not sent because a runtime condition was not met.
+    Skipped {
+        /// Why the task was skipped.
+        reason: String,
+    },
+
+    /// The task was sent and the expected state change was seen.
+    Confirmed,
+
+    /// The task was sent but the expected state change was never seen.
+    Failed {
+        /// Why the task failed.
+        reason: String,
+    },
+}
+
+impl Display for TaskOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaskOutcome::Sent => write!(f, "Sent"),
+            TaskOutcome::Skipped { reason } => write!(f, "Skipped: {reason}"),
+            TaskOutcome::Confirmed => write!(f, "Confirmed"),
+            TaskOutcome::Failed { reason } => write!(f, "Failed: {reason}"),
+        }
+    }
+}
+
 /// The importance of a Sequence
 #[derive(
     Copy, Clone, Debug, Default, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord,
@@ -186,9 +234,17 @@ pub struct Sequence {
     /// The latest time this step can be started.
     pub latest_time: DateTime<Utc>,
 
+    /// The start time before it was postponed, if it was postponed.
+    #[serde(default)]
+    pub original_start_time: Option<DateTime<Utc>>,
+
     /// The number of the repeat.
     pub repeat_number: usize,
 
+    /// The IANA timezone the schedule was calculated in, if known.
+    #[serde(default)]
+    pub timezone: Option<String>,
+
     /// The tasks to execute.
     pub tasks: Vec<Task>,
 
@@ -197,6 +253,12 @@ pub struct Sequence {
 
     /// The mark for this task - for use by executor.
     pub mark: Option<Mark>,
+
+    /// The outcome of each task, in the same order as `tasks` - for use by executor.
+    ///
+    /// This is empty until the sequence has been started.
+    #[serde(default)]
+    pub outcomes: Vec<TaskOutcome>,
 }
 
 impl Sequence {
@@ -205,6 +267,18 @@ impl Sequence {
     pub fn is_done(&self) -> bool {
         self.status.is_some_and(|status| status.is_done())
     }
+
+    /// The start time before any postponement.
+    #[must_use]
+    pub fn original_start_time(&self) -> DateTime<Utc> {
+        self.original_start_time.unwrap_or(self.start_time)
+    }
+
+    /// The end time before any postponement.
+    #[must_use]
+    pub fn original_end_time(&self) -> DateTime<Utc> {
+        self.end_time - (self.start_time - self.original_start_time())
+    }
 }
 
 /// The complete set of tags for a particular day
@@ -228,3 +302,222 @@ impl Deref for Tags {
         &self.0
     }
 }
+
+/// A task that was sent as part of a sequence, for the schedule history.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct HistoryTask {
+    /// The index of the task in the sequence.
+    pub index: usize,
+
+    /// The title of the task.
+    pub title: String,
+
+    /// The topics the task was sent to.
+    pub topics: Vec<String>,
+
+    /// The payload sent, if any.
+    pub payload: Option<String>,
+}
+
+/// What happened in a schedule history entry.
+///
+/// Stored in the database as the [`HistoryOutcome::as_str`] name, the table only accepts
+/// these values.
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryOutcome {
+    /// The task was sent.
+    Sent,
+
+    /// The task was not sent because a runtime condition was not met.
+    Skipped,
+
+    /// The task was sent and the expected state change was seen.
+    Confirmed,
+
+    /// The task was sent but the expected state change was never seen.
+    Failed,
+
+    /// The task was not sent because the sequence was cancelled.
+    Cancelled,
+
+    /// The task was not sent because the sequence started too late.
+    Missed,
+
+    /// The sequence was marked as done.
+    MarkDone,
+
+    /// The sequence was marked as cancelled.
+    MarkCancelled,
+
+    /// The sequence was marked as postponed.
+    MarkPostponed,
+
+    /// A mark on the sequence was removed.
+    MarkRemoved,
+}
+
+impl HistoryOutcome {
+    /// Every outcome, in the same order as the database check.
+    pub const ALL: [Self; 10] = [
+        Self::Sent,
+        Self::Skipped,
+        Self::Confirmed,
+        Self::Failed,
+        Self::Cancelled,
+        Self::Missed,
+        Self::MarkDone,
+        Self::MarkCancelled,
+        Self::MarkPostponed,
+        Self::MarkRemoved,
+    ];
+
+    /// The name stored in the database.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Sent => "sent",
+            Self::Skipped => "skipped",
+            Self::Confirmed => "confirmed",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+            Self::Missed => "missed",
+            Self::MarkDone => "mark_done",
+            Self::MarkCancelled => "mark_cancelled",
+            Self::MarkPostponed => "mark_postponed",
+            Self::MarkRemoved => "mark_removed",
+        }
+    }
+}
+
+impl Display for HistoryOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Sent => write!(f, "Sent"),
+            Self::Skipped => write!(f, "Skipped"),
+            Self::Confirmed => write!(f, "Confirmed"),
+            Self::Failed => write!(f, "Failed"),
+            Self::Cancelled => write!(f, "Cancelled"),
+            Self::Missed => write!(f, "Missed"),
+            Self::MarkDone => write!(f, "Marked done"),
+            Self::MarkCancelled => write!(f, "Marked cancelled"),
+            Self::MarkPostponed => write!(f, "Marked postponed"),
+            Self::MarkRemoved => write!(f, "Mark removed"),
+        }
+    }
+}
+
+/// An error parsing a [`HistoryOutcome`].
+#[derive(Error, Debug)]
+#[error("Unknown history outcome {0}")]
+pub struct HistoryOutcomeError(String);
+
+impl std::str::FromStr for HistoryOutcome {
+    type Err = HistoryOutcomeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|outcome| outcome.as_str() == s)
+            .ok_or_else(|| HistoryOutcomeError(s.to_string()))
+    }
+}
+
+impl TaskOutcome {
+    /// Get the history outcome and any detail for this task outcome.
+    #[must_use]
+    pub fn to_history(&self) -> (HistoryOutcome, Option<String>) {
+        match self {
+            Self::Sent => (HistoryOutcome::Sent, None),
+            Self::Skipped { reason } => (HistoryOutcome::Skipped, Some(reason.clone())),
+            Self::Confirmed => (HistoryOutcome::Confirmed, None),
+            Self::Failed { reason } => (HistoryOutcome::Failed, Some(reason.clone())),
+        }
+    }
+}
+
+impl MarkStatus {
+    /// Get the history outcome and any detail for adding a mark with this status.
+    #[must_use]
+    pub fn to_history(&self) -> (HistoryOutcome, Option<String>) {
+        match self {
+            Self::Cancelled => (HistoryOutcome::MarkCancelled, None),
+            Self::Done => (HistoryOutcome::MarkDone, None),
+            Self::Postponed { offset } => (
+                HistoryOutcome::MarkPostponed,
+                Some(time_delta::to_string(*offset)),
+            ),
+        }
+    }
+}
+
+/// A new entry for the schedule history.
+///
+/// This is either a task that was executed or a mark that was changed.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct CreateHistoryEntry {
+    /// When this happened.
+    pub time: DateTime<Utc>,
+
+    /// The id of the sequence.
+    pub sequence_id: String,
+
+    /// The title of the sequence.
+    pub sequence_title: String,
+
+    /// The source schedule date of the sequence, if known.
+    pub schedule_date: Option<NaiveDate>,
+
+    /// The number of the repeat, if known.
+    pub repeat_number: Option<usize>,
+
+    /// The task, or `None` if this is a mark.
+    pub task: Option<HistoryTask>,
+
+    /// What happened.
+    pub outcome: HistoryOutcome,
+
+    /// More about what happened, for example why a task failed.
+    pub detail: Option<String>,
+}
+
+/// An entry in the schedule history.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct HistoryEntry {
+    /// The id of the entry.
+    pub id: i64,
+
+    /// When this happened.
+    pub time: DateTime<Utc>,
+
+    /// The id of the sequence.
+    pub sequence_id: String,
+
+    /// The title of the sequence.
+    pub sequence_title: String,
+
+    /// The source schedule date of the sequence, if known.
+    pub schedule_date: Option<NaiveDate>,
+
+    /// The number of the repeat, if known.
+    pub repeat_number: Option<usize>,
+
+    /// The task, or `None` if this is a mark.
+    pub task: Option<HistoryTask>,
+
+    /// What happened.
+    pub outcome: HistoryOutcome,
+
+    /// More about what happened, for example why a task failed.
+    pub detail: Option<String>,
+}
+
+/// A page of the schedule history, newest first.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct HistoryPage {
+    /// The entries in this page.
+    pub entries: Vec<HistoryEntry>,
+
+    /// Pass this as `before` to get the next page, `None` if this is the last page.
+    pub next: Option<i64>,
+}
@@ -0,0 +1,597 @@
+//! Solar position and times of solar events.
+//!
+//! Uses the NOAA solar calculator equations, which are accurate to within a minute or so
+//! for latitudes between +/- 72 degrees.
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use chrono::{NaiveDate, NaiveTime, TimeDelta, TimeZone, Timelike, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use thiserror::Error;
+
+use crate::datetime::{convert_date_time_to_utc_or_default, time_delta, DateTime};
+
+/// A location on the earth.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Location {
+    /// The latitude in degrees, positive for north.
+    pub latitude: f64,
+
+    /// The longitude in degrees, positive for east.
+    pub longitude: f64,
+}
+
+/// An event that happens once a day based on the position of the sun.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolarEvent {
+    /// The sun is at its highest point.
+    SolarNoon,
+
+    /// The top of the sun appears above the horizon.
+    Sunrise,
+
+    /// The top of the sun disappears below the horizon.
+    Sunset,
+
+    /// The sun is 6 degrees below the horizon in the morning.
+    CivilDawn,
+
+    /// The sun is 6 degrees below the horizon in the evening.
+    CivilDusk,
+
+    /// The sun is 12 degrees below the horizon in the morning.
+    NauticalDawn,
+
+    /// The sun is 12 degrees below the horizon in the evening.
+    NauticalDusk,
+
+    /// The sun is 18 degrees below the horizon in the morning.
+    AstronomicalDawn,
+
+    /// The sun is 18 degrees below the horizon in the evening.
+    AstronomicalDusk,
+}
+
+impl SolarEvent {
+    /// The zenith angle of the sun for this event, and if it is in the morning.
+    const fn zenith(self) -> Option<(f64, bool)> {
+        match self {
+            Self::SolarNoon => None,
+            Self::Sunrise => Some((90.833, true)),
+            Self::Sunset => Some((90.833, false)),
+            Self::CivilDawn => Some((96.0, true)),
+            Self::CivilDusk => Some((96.0, false)),
+            Self::NauticalDawn => Some((102.0, true)),
+            Self::NauticalDusk => Some((102.0, false)),
+            Self::AstronomicalDawn => Some((108.0, true)),
+            Self::AstronomicalDusk => Some((108.0, false)),
+        }
+    }
+
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::SolarNoon => "solar_noon",
+            Self::Sunrise => "sunrise",
+            Self::Sunset => "sunset",
+            Self::CivilDawn => "civil_dawn",
+            Self::CivilDusk => "civil_dusk",
+            Self::NauticalDawn => "nautical_dawn",
+            Self::NauticalDusk => "nautical_dusk",
+            Self::AstronomicalDawn => "astronomical_dawn",
+            Self::AstronomicalDusk => "astronomical_dusk",
+        }
+    }
+
+    const ALL: [Self; 9] = [
+        Self::SolarNoon,
+        Self::Sunrise,
+        Self::Sunset,
+        Self::CivilDawn,
+        Self::CivilDusk,
+        Self::NauticalDawn,
+        Self::NauticalDusk,
+        Self::AstronomicalDawn,
+        Self::AstronomicalDusk,
+    ];
+}
+
+impl Display for SolarEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// An error parsing a `SolarEvent`.
+#[derive(Error, Debug)]
+#[error("Unknown solar event {0}")]
+pub struct SolarEventParseError(String);
+
+impl FromStr for SolarEvent {
+    type Err = SolarEventParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|event| event.as_str() == s)
+            .ok_or_else(|| SolarEventParseError(s.to_string()))
+    }
+}
+
+/// Values that only depend on the time, not the location.
+struct SunPosition {
+    /// The declination of the sun in radians.
+    declination: f64,
+
+    /// The equation of time in minutes.
+    equation_of_time: f64,
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn julian_century(datetime: DateTime<Utc>) -> f64 {
+    // Julian day 2451545.0 is 2000-01-01 12:00 UTC.
+    let j2000 = Utc.with_ymd_and_hms(2000, 1, 1, 12, 0, 0).single();
+    let seconds = j2000.map_or(0, |j2000| (datetime - j2000).num_seconds());
+    seconds as f64 / 86400.0 / 36525.0
+}
+
+fn sun_position(datetime: DateTime<Utc>) -> SunPosition {
+    let t = julian_century(datetime);
+
+    let mean_longitude = t.mul_add(t.mul_add(0.000_303_2, 36_000.769_83), 280.466_46) % 360.0;
+    let mean_anomaly = t.mul_add(t.mul_add(-0.000_153_7, 35_999.050_29), 357.529_11);
+    let eccentricity = t.mul_add(-t.mul_add(0.000_000_126_7, 0.000_042_037), 0.016_708_634);
+
+    let anomaly = mean_anomaly.to_radians();
+    let equation_of_center = anomaly.sin().mul_add(
+        t.mul_add(-t.mul_add(0.000_014, 0.004_817), 1.914_602),
+        (2.0 * anomaly).sin().mul_add(
+            t.mul_add(-0.000_101, 0.019_993),
+            (3.0 * anomaly).sin() * 0.000_289,
+        ),
+    );
+
+    let true_longitude = mean_longitude + equation_of_center;
+    let omega = t.mul_add(-1934.136, 125.04).to_radians();
+    let apparent_longitude = 0.004_78f64.mul_add(-omega.sin(), true_longitude - 0.005_69);
+
+    let seconds = t.mul_add(-t.mul_add(t.mul_add(-0.001_813, 0.000_59), 46.815), 21.448);
+    let mean_obliquity = 23.0 + (26.0 + seconds / 60.0) / 60.0;
+    let obliquity = 0.002_56f64
+        .mul_add(omega.cos(), mean_obliquity)
+        .to_radians();
+
+    let declination = (obliquity.sin() * apparent_longitude.to_radians().sin()).asin();
+
+    let var_y = (obliquity / 2.0).tan().powi(2);
+    let longitude = mean_longitude.to_radians();
+    let terms = [
+        (var_y, (2.0 * longitude).sin()),
+        (-2.0 * eccentricity, anomaly.sin()),
+        (
+            4.0 * eccentricity * var_y * anomaly.sin(),
+            (2.0 * longitude).cos(),
+        ),
+        (-0.5 * var_y * var_y, (4.0 * longitude).sin()),
+        (-1.25 * eccentricity * eccentricity, (2.0 * anomaly).sin()),
+    ];
+    let equation_of_time = 4.0
+        * terms
+            .iter()
+            .fold(0.0f64, |acc, (a, b)| a.mul_add(*b, acc))
+            .to_degrees();
+
+    SunPosition {
+        declination,
+        equation_of_time,
+    }
+}
+
+/// Minutes after UTC midnight of the event, or None if the event does not happen.
+fn event_minutes(
+    date: NaiveDate,
+    event: SolarEvent,
+    location: &Location,
+    estimate: f64,
+) -> Option<f64> {
+    let datetime = minutes_to_datetime(date, estimate)?;
+    let position = sun_position(datetime);
+    let noon = 4.0f64.mul_add(-location.longitude, 720.0) - position.equation_of_time;
+
+    let Some((zenith, morning)) = event.zenith() else {
+        return Some(noon);
+    };
+
+    let latitude = location.latitude.to_radians();
+    let declination = position.declination;
+    let cos_hour_angle = (-latitude.tan()).mul_add(
+        declination.tan(),
+        zenith.to_radians().cos() / (latitude.cos() * declination.cos()),
+    );
+
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        // The sun never reaches this zenith on this day.
+        return None;
+    }
+
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+    if morning {
+        Some(4.0f64.mul_add(-hour_angle, noon))
+    } else {
+        Some(4.0f64.mul_add(hour_angle, noon))
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn minutes_to_datetime(date: NaiveDate, minutes: f64) -> Option<DateTime<Utc>> {
+    let midnight = Utc.from_utc_datetime(&date.and_time(NaiveTime::MIN));
+    let seconds = (minutes * 60.0).round() as i64;
+    TimeDelta::try_seconds(seconds).map(|delta| midnight + delta)
+}
+
+/// Get the time of a solar event on the given date.
+///
+/// The date is taken to be the local date at the location, so for example the sunrise
+/// returned will be the sunrise closest to the local morning of that day.
+///
+/// Returns `None` if the event does not occur on that day, for example there is no sunset
+/// during the polar summer.
+#[must_use]
+pub fn solar_event_time(
+    date: NaiveDate,
+    event: SolarEvent,
+    location: &Location,
+) -> Option<DateTime<Utc>> {
+    // Start with an estimate at local noon, then refine using the estimated time.
+    let estimate = 4.0f64.mul_add(-location.longitude, 720.0);
+    let estimate = event_minutes(date, event, location, estimate)?;
+    let minutes = event_minutes(date, event, location, estimate)?;
+    minutes_to_datetime(date, minutes)
+}
+
+/// Get the elevation of the sun above the horizon in degrees, negative if below it.
+///
+/// Atmospheric refraction is ignored, so this is slightly lower than the apparent elevation
+/// near the horizon.
+#[must_use]
+pub fn sun_elevation(datetime: DateTime<Utc>, location: &Location) -> f64 {
+    let position = sun_position(datetime);
+    let minutes = f64::from(datetime.num_seconds_from_midnight()) / 60.0;
+    let true_solar_time = 4.0f64.mul_add(location.longitude, minutes + position.equation_of_time);
+    let hour_angle = (true_solar_time / 4.0 - 180.0).to_radians();
+
+    let latitude = location.latitude.to_radians();
+    let declination = position.declination;
+    let cos_zenith = (latitude.cos() * declination.cos())
+        .mul_add(hour_angle.cos(), latitude.sin() * declination.sin());
+
+    90.0 - cos_zenith.clamp(-1.0, 1.0).acos().to_degrees()
+}
+
+/// A time of day, either fixed or relative to a solar event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeOfDay {
+    /// A fixed local time.
+    Fixed(NaiveTime),
+
+    /// A time relative to a solar event.
+    Solar {
+        /// The solar event.
+        event: SolarEvent,
+
+        /// The offset from the solar event.
+        offset: TimeDelta,
+    },
+}
+
+/// An error getting the time for a `TimeOfDay`.
+#[derive(Error, Debug)]
+pub enum TimeOfDayError {
+    /// A solar event was requested but no location is configured.
+    #[error("No location configured for {0}")]
+    NoLocation(SolarEvent),
+
+    /// The solar event does not happen on this day.
+    #[error("{0} does not occur on {1}")]
+    NoEvent(SolarEvent, NaiveDate),
+}
+
+impl TimeOfDay {
+    /// Get the UTC time for this time of day on the given local date.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if this is relative to a solar event and there is no location, or if
+    /// the solar event does not happen on this date.
+    pub fn to_utc<T: TimeZone>(
+        &self,
+        date: NaiveDate,
+        timezone: &T,
+        location: Option<&Location>,
+    ) -> Result<DateTime<Utc>, TimeOfDayError> {
+        match self {
+            Self::Fixed(time) => Ok(convert_date_time_to_utc_or_default(date, *time, timezone)),
+            Self::Solar { event, offset } => {
+                let location = location.ok_or(TimeOfDayError::NoLocation(*event))?;
+                let datetime = solar_event_time(date, *event, location)
+                    .ok_or(TimeOfDayError::NoEvent(*event, date))?;
+                Ok(datetime + *offset)
+            }
+        }
+    }
+}
+
+impl std::ops::Add<TimeDelta> for TimeOfDay {
+    type Output = Self;
+
+    fn add(self, rhs: TimeDelta) -> Self {
+        match self {
+            Self::Fixed(time) => Self::Fixed(time + rhs),
+            Self::Solar { event, offset } => Self::Solar {
+                event,
+                offset: offset + rhs,
+            },
+        }
+    }
+}
+
+impl std::ops::Sub<TimeDelta> for TimeOfDay {
+    type Output = Self;
+
+    fn sub(self, rhs: TimeDelta) -> Self {
+        self + -rhs
+    }
+}
+
+impl From<NaiveTime> for TimeOfDay {
+    fn from(time: NaiveTime) -> Self {
+        Self::Fixed(time)
+    }
+}
+
+impl Display for TimeOfDay {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fixed(time) => write!(f, "{time}"),
+            Self::Solar { event, offset } if offset.is_zero() => write!(f, "{event}"),
+            Self::Solar { event, offset } if *offset < TimeDelta::zero() => {
+                write!(f, "{event} - {}", time_delta::to_string(-*offset))
+            }
+            Self::Solar { event, offset } => {
+                write!(f, "{event} + {}", time_delta::to_string(*offset))
+            }
+        }
+    }
+}
+
+/// An error parsing a `TimeOfDay`.
+#[derive(Error, Debug)]
+pub enum TimeOfDayParseError {
+    /// The time could not be parsed.
+    #[error("Invalid time {0}")]
+    InvalidTime(String),
+
+    /// The solar event could not be parsed.
+    #[error("{0}")]
+    InvalidEvent(#[from] SolarEventParseError),
+
+    /// The offset could not be parsed.
+    #[error("Invalid offset {0}")]
+    InvalidOffset(String),
+}
+
+impl FromStr for TimeOfDay {
+    type Err = TimeOfDayParseError;
+
+    /// Parse a time of day.
+    ///
+    /// Accepts either a fixed time such as `08:30:00`, or a solar event with an optional
+    /// offset such as `sunset`, `sunset + 00:30` or `civil_dawn - 01:00:00`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if s.starts_with(|c: char| c.is_ascii_digit()) {
+            return s
+                .parse::<NaiveTime>()
+                .map(Self::Fixed)
+                .map_err(|_| TimeOfDayParseError::InvalidTime(s.to_string()));
+        }
+
+        let (event, offset) = match s.find(['+', '-']) {
+            Some(index) => {
+                let (event, offset) = s.split_at(index);
+                let (negative, offset) = offset.split_at(1);
+                let offset = time_delta::from_str(offset.trim())
+                    .map_err(|_| TimeOfDayParseError::InvalidOffset(offset.to_string()))?;
+                let offset = if negative == "-" { -offset } else { offset };
+                (event.trim(), offset)
+            }
+            None => (s, TimeDelta::zero()),
+        };
+
+        let event = event.parse()?;
+        Ok(Self::Solar { event, offset })
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeOfDay {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s: String = Deserialize::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for TimeOfDay {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use chrono::FixedOffset;
+
+    use super::*;
+
+    const MELBOURNE: Location = Location {
+        latitude: -37.8136,
+        longitude: 144.9631,
+    };
+
+    const TROMSO: Location = Location {
+        latitude: 69.6492,
+        longitude: 18.9553,
+    };
+
+    fn local(datetime: DateTime<Utc>) -> String {
+        let timezone = FixedOffset::east_opt(10 * 60 * 60).unwrap();
+        datetime
+            .with_timezone(&timezone)
+            .format("%H:%M")
+            .to_string()
+    }
+
+    fn assert_near(datetime: DateTime<Utc>, expected: &str) {
+        let timezone = FixedOffset::east_opt(10 * 60 * 60).unwrap();
+        let date = datetime.with_timezone(&timezone).date_naive();
+        let expected = date.and_time(expected.parse().unwrap());
+        let expected = timezone.from_local_datetime(&expected).unwrap();
+        let difference = (datetime - expected.with_timezone(&Utc))
+            .num_seconds()
+            .abs();
+        assert!(
+            difference <= 120,
+            "{} is not near {expected}",
+            local(datetime)
+        );
+    }
+
+    #[test]
+    fn test_melbourne_winter_solstice() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+        let sunrise = solar_event_time(date, SolarEvent::Sunrise, &MELBOURNE).unwrap();
+        let noon = solar_event_time(date, SolarEvent::SolarNoon, &MELBOURNE).unwrap();
+        let sunset = solar_event_time(date, SolarEvent::Sunset, &MELBOURNE).unwrap();
+        let dawn = solar_event_time(date, SolarEvent::CivilDawn, &MELBOURNE).unwrap();
+        let dusk = solar_event_time(date, SolarEvent::CivilDusk, &MELBOURNE).unwrap();
+
+        assert_near(sunrise, "07:35:00");
+        assert_near(noon, "12:21:00");
+        assert_near(sunset, "17:08:00");
+        assert_near(dawn, "07:06:00");
+        assert_near(dusk, "17:37:00");
+        assert!(dawn < sunrise && sunrise < noon && noon < sunset && sunset < dusk);
+    }
+
+    #[test]
+    fn test_melbourne_summer_solstice() {
+        // Times are given in AEST, not daylight savings time.
+        let date = NaiveDate::from_ymd_opt(2024, 12, 21).unwrap();
+        let sunrise = solar_event_time(date, SolarEvent::Sunrise, &MELBOURNE).unwrap();
+        let sunset = solar_event_time(date, SolarEvent::Sunset, &MELBOURNE).unwrap();
+
+        assert_near(sunrise, "04:55:00");
+        assert_near(sunset, "19:42:00");
+    }
+
+    #[test]
+    fn test_polar_summer() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+        assert!(solar_event_time(date, SolarEvent::Sunset, &TROMSO).is_none());
+        assert!(solar_event_time(date, SolarEvent::SolarNoon, &TROMSO).is_some());
+    }
+
+    #[test]
+    fn test_sun_elevation() {
+        let date = NaiveDate::from_ymd_opt(2024, 12, 21).unwrap();
+        let noon = solar_event_time(date, SolarEvent::SolarNoon, &MELBOURNE).unwrap();
+        let sunrise = solar_event_time(date, SolarEvent::Sunrise, &MELBOURNE).unwrap();
+        let dusk = solar_event_time(date, SolarEvent::CivilDusk, &MELBOURNE).unwrap();
+
+        // At the summer solstice the sun is 23.44 degrees south of the equator.
+        let elevation = sun_elevation(noon, &MELBOURNE);
+        assert!(
+            (elevation - 75.63).abs() < 0.5,
+            "noon elevation {elevation}"
+        );
+
+        // Sunrise is defined for the top of the sun after refraction.
+        let elevation = sun_elevation(sunrise, &MELBOURNE);
+        assert!(
+            (elevation + 0.833).abs() < 0.5,
+            "sunrise elevation {elevation}"
+        );
+
+        let elevation = sun_elevation(dusk, &MELBOURNE);
+        assert!((elevation + 6.0).abs() < 0.5, "dusk elevation {elevation}");
+
+        let midnight = noon + TimeDelta::hours(12);
+        assert!(sun_elevation(midnight, &MELBOURNE) < -25.0);
+    }
+
+    #[test]
+    fn test_parse_time_of_day() {
+        let time: TimeOfDay = "08:30:00".parse().unwrap();
+        assert_eq!(
+            time,
+            TimeOfDay::Fixed(NaiveTime::from_hms_opt(8, 30, 0).unwrap())
+        );
+
+        let time: TimeOfDay = "sunset".parse().unwrap();
+        assert_eq!(
+            time,
+            TimeOfDay::Solar {
+                event: SolarEvent::Sunset,
+                offset: TimeDelta::zero()
+            }
+        );
+
+        let time: TimeOfDay = "sunset + 00:30".parse().unwrap();
+        assert_eq!(
+            time,
+            TimeOfDay::Solar {
+                event: SolarEvent::Sunset,
+                offset: TimeDelta::minutes(30)
+            }
+        );
+        assert_eq!(time.to_string(), "sunset + 00:30:00");
+
+        let time: TimeOfDay = "nautical_dawn-01:00:00".parse().unwrap();
+        assert_eq!(
+            time,
+            TimeOfDay::Solar {
+                event: SolarEvent::NauticalDawn,
+                offset: TimeDelta::hours(-1)
+            }
+        );
+        assert_eq!(time.to_string(), "nautical_dawn - 01:00:00");
+
+        assert!("moonrise".parse::<TimeOfDay>().is_err());
+        assert!("sunset + soon".parse::<TimeOfDay>().is_err());
+        assert!("25:00:00".parse::<TimeOfDay>().is_err());
+    }
+
+    #[test]
+    fn test_time_of_day_to_utc() {
+        let timezone = FixedOffset::east_opt(10 * 60 * 60).unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+
+        let time: TimeOfDay = "sunset + 00:30".parse().unwrap();
+        let datetime = time.to_utc(date, &timezone, Some(&MELBOURNE)).unwrap();
+        assert_near(datetime, "17:38:00");
+
+        let time: TimeOfDay = "sunset".parse().unwrap();
+        let result = time.to_utc(date, &timezone, None);
+        assert!(matches!(result, Err(TimeOfDayError::NoLocation(_))));
+
+        let time: TimeOfDay = "08:00:00".parse().unwrap();
+        let datetime = time.to_utc(date, &timezone, None).unwrap();
+        assert_eq!(local(datetime), "08:00");
+    }
+}
@@ -1,9 +1,10 @@
 //! Structures and functions for `Zigbee2MQTT` devices.
 use std::fmt::Display;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
-use crate::mqtt::Json;
+use crate::mqtt::{Json, MqttMessage};
 
 /// The `Zigbee2MQTT` door.
 #[allow(dead_code)]
@@ -53,3 +54,99 @@ impl From<Json<Door>> for DoorState {
         door.0.into()
     }
 }
+
+/// The power state of a `Zigbee2MQTT` light.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum LightPower {
+    /// The light is on.
+    On,
+
+    /// The light is off.
+    Off,
+}
+
+/// The hue and saturation of a `Zigbee2MQTT` light.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct LightColor {
+    /// Hue, in degrees.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hue: Option<f32>,
+
+    /// Saturation, in percent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saturation: Option<f32>,
+}
+
+/// A `Zigbee2MQTT` light.
+///
+/// This is both the payload sent to the `set` topic and the state reported by the light.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Light {
+    /// Is the light on?
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<LightPower>,
+
+    /// The brightness, 0 to 254.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brightness: Option<u8>,
+
+    /// The color temperature, in mireds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_temp: Option<u16>,
+
+    /// The color.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<LightColor>,
+
+    /// Which of `color_temp` or `color` the light is currently showing.
+    #[serde(skip_serializing)]
+    pub color_mode: Option<String>,
+
+    /// How long the light should take to change, in seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transition: Option<f32>,
+}
+
+/// Is a `Zigbee2MQTT` device reachable?
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Availability {
+    /// The device is online.
+    Online,
+
+    /// The device is offline.
+    Offline,
+}
+
+/// An error parsing an availability message.
+#[derive(Error, Debug)]
+pub enum AvailabilityError {
+    /// The payload was not valid UTF-8.
+    #[error("Invalid UTF-8: {0}")]
+    InvalidUtf8(#[from] std::str::Utf8Error),
+
+    /// The payload was not a known state.
+    #[error("Invalid availability: {0}")]
+    InvalidValue(String),
+}
+
+#[derive(Deserialize)]
+struct AvailabilityJson {
+    state: String,
+}
+
+impl TryFrom<MqttMessage> for Availability {
+    type Error = AvailabilityError;
+
+    fn try_from(msg: MqttMessage) -> Result<Self, Self::Error> {
+        let payload = msg.payload_as_str()?;
+        // Accept both the legacy `online` and the newer `{"state":"online"}` payloads.
+        let state = serde_json::from_str::<AvailabilityJson>(payload)
+            .map_or_else(|_| payload.to_string(), |json| json.state);
+        match state.as_str() {
+            "online" => Ok(Self::Online),
+            "offline" => Ok(Self::Offline),
+            _ => Err(AvailabilityError::InvalidValue(state)),
+        }
+    }
+}
@@ -10,6 +10,7 @@ use yew::prelude::*;
 use yew_router::prelude::*;
 
 use crate::components::car::CarComponent;
+use crate::components::history_view::HistoryView;
 use crate::components::locations::zones::ZonesView;
 use crate::components::nav_bar::NavBar;
 use crate::components::occupancy_view::OccupancyViewComponent;
@@ -54,6 +55,7 @@ fn switch(selected_route: Route) -> Html {
         },
         Route::Prices => html! { <PricesComponent/> },
         Route::Schedule => html! { <ScheduleView/> },
+        Route::History => html! { <HistoryView/> },
         Route::Tags => html! { <TagsView/> },
         Route::Locations => return html! { <><NavBar/><ZonesView/></> },
         Route::Occupancy => html! { <OccupancyViewComponent id={"all".to_string()}/> },
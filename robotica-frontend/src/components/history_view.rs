@@ -0,0 +1,226 @@
+//! Component that shows the schedule history
+use chrono::{Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use gloo_net::http::{Request, Response};
+use gloo_net::Error;
+use robotica_common::{
+    datetime::datetime_to_string,
+    robotica::http_api::ApiResponse,
+    scheduler::{HistoryEntry, HistoryPage},
+};
+use tap::Pipe;
+use tracing::error;
+use yew::{platform::spawn_local, prelude::*};
+
+use crate::components::forms::text_input::TextInput;
+
+pub enum Msg {
+    SetSequenceId(String),
+    SetDate(String),
+    Search,
+    More,
+    Loaded(HistoryPage, bool),
+    LoadFailed(String),
+}
+
+#[derive(Default)]
+pub struct HistoryView {
+    sequence_id: String,
+    date: String,
+    entries: Vec<HistoryEntry>,
+    next: Option<i64>,
+    error: Option<String>,
+}
+
+async fn process_response(response: Result<Response, Error>) -> Result<HistoryPage, String> {
+    let response = response.map_err(|err| format!("http error: {err:?}"))?;
+    let api_response: Option<ApiResponse<HistoryPage>> = response
+        .json()
+        .await
+        .map_err(|err| error!("Error parsing server response: {err:?}"))
+        .ok();
+
+    match (response.ok(), api_response) {
+        (true, Some(ApiResponse::Success(response))) => Ok(response.data),
+        (true, None) => Err("Invalid response".to_string()),
+        (_, Some(ApiResponse::Error(err))) => err.message.pipe(Err),
+        (false, _) => response.status_text().pipe(Err),
+    }
+}
+
+/// Get the start and end of a local date in UTC.
+fn date_to_range(date: &str) -> Option<(String, String)> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    let start = Local
+        .from_local_datetime(&date.and_time(NaiveTime::MIN))
+        .earliest()?;
+    let end = Local
+        .from_local_datetime(&date.succ_opt()?.and_time(NaiveTime::MIN))
+        .earliest()?;
+    Some((
+        start.with_timezone(&Utc).to_rfc3339(),
+        end.with_timezone(&Utc).to_rfc3339(),
+    ))
+}
+
+impl HistoryView {
+    fn load(&self, ctx: &Context<Self>, before: Option<i64>) {
+        let mut query = Vec::new();
+        if !self.sequence_id.is_empty() {
+            query.push(("sequence_id", self.sequence_id.clone()));
+        }
+        if let Some((from, to)) = date_to_range(&self.date) {
+            query.push(("from", from));
+            query.push(("to", to));
+        }
+        if let Some(before) = before {
+            query.push(("before", before.to_string()));
+        }
+
+        let append = before.is_some();
+        let link = ctx.link().clone();
+        spawn_local(async move {
+            let result = Request::get("/api/history")
+                .query(query)
+                .send()
+                .await
+                .pipe(process_response)
+                .await;
+
+            let msg = match result {
+                Ok(page) => Msg::Loaded(page, append),
+                Err(err) => Msg::LoadFailed(err),
+            };
+            link.send_message(msg);
+        });
+    }
+}
+
+fn entry_to_html(entry: &HistoryEntry) -> Html {
+    let what = entry
+        .task
+        .as_ref()
+        .map_or_else(|| "Mark".to_string(), |task| task.title.clone());
+    let topics = entry
+        .task
+        .as_ref()
+        .map(|task| task.topics.join(", "))
+        .unwrap_or_default();
+    let payload = entry
+        .task
+        .as_ref()
+        .and_then(|task| task.payload.clone())
+        .unwrap_or_default();
+    let schedule_date = entry
+        .schedule_date
+        .map(|date| date.to_string())
+        .unwrap_or_default();
+    let outcome = entry.detail.as_ref().map_or_else(
+        || entry.outcome.to_string(),
+        |detail| format!("{}: {detail}", entry.outcome),
+    );
+
+    html! {
+        <tr>
+            <td>{datetime_to_string(entry.time)}</td>
+            <td>{schedule_date}</td>
+            <td>{&entry.sequence_title}</td>
+            <td>{what}</td>
+            <td>{topics}</td>
+            <td>{payload}</td>
+            <td>{outcome}</td>
+        </tr>
+    }
+}
+
+impl Component for HistoryView {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let view = Self::default();
+        view.load(ctx, None);
+        view
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::SetSequenceId(sequence_id) => {
+                self.sequence_id = sequence_id;
+                false
+            }
+            Msg::SetDate(date) => {
+                self.date = date;
+                false
+            }
+            Msg::Search => {
+                self.load(ctx, None);
+                false
+            }
+            Msg::More => {
+                self.load(ctx, self.next);
+                false
+            }
+            Msg::Loaded(page, append) => {
+                if append {
+                    self.entries.extend(page.entries);
+                } else {
+                    self.entries = page.entries;
+                }
+                self.next = page.next;
+                self.error = None;
+                true
+            }
+            Msg::LoadFailed(err) => {
+                self.error = Some(err);
+                true
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let on_sequence_id = ctx.link().callback(Msg::SetSequenceId);
+        let on_date = ctx.link().callback(|e: Event| {
+            let value = e
+                .target_unchecked_into::<web_sys::HtmlInputElement>()
+                .value();
+            Msg::SetDate(value)
+        });
+        let on_search = ctx.link().callback(|_| Msg::Search);
+        let on_more = ctx.link().callback(|_| Msg::More);
+
+        html! {
+            <>
+                <h1>{"Schedule History"}</h1>
+                <div>
+                    <TextInput id="sequence_id" label="Sequence Id" value={self.sequence_id.clone()} on_change={on_sequence_id} />
+                    <label for="date">{"Date"}</label>
+                    <input type="date" id="date" value={self.date.clone()} onchange={on_date} />
+                    <br/>
+                    <button type="button" class="btn btn-primary" onclick={on_search}>{"Search"}</button>
+                </div>
+                if let Some(error) = &self.error {
+                    <div class="alert alert-danger">{error}</div>
+                }
+                <table class="table">
+                    <thead>
+                        <tr>
+                            <th scope="col">{"Time"}</th>
+                            <th scope="col">{"Date"}</th>
+                            <th scope="col">{"Sequence"}</th>
+                            <th scope="col">{"Task"}</th>
+                            <th scope="col">{"Topics"}</th>
+                            <th scope="col">{"Payload"}</th>
+                            <th scope="col">{"Outcome"}</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        { for self.entries.iter().map(entry_to_html) }
+                    </tbody>
+                </table>
+                if self.next.is_some() {
+                    <button type="button" class="btn btn-secondary" onclick={on_more}>{"More"}</button>
+                }
+            </>
+        }
+    }
+}
@@ -4,6 +4,7 @@ pub mod app;
 pub mod button;
 pub mod car;
 pub mod forms;
+pub mod history_view;
 pub mod locations;
 pub mod nav_bar;
 pub mod occupancy_view;
@@ -171,6 +171,9 @@ pub fn nav_bar() -> Html {
                         <li class="nav-item" onclick={close_menu.clone()}>
                             { nav_link(Route::Schedule, "Schedule") }
                         </li>
+                        <li class="nav-item" onclick={close_menu.clone()}>
+                            { nav_link(Route::History, "History") }
+                        </li>
                         <li class="nav-item" onclick={close_menu.clone()}>
                             { nav_link(Route::Tags, "Tags") }
                         </li>
@@ -1,16 +1,18 @@
 //! Component that shows the schedule
 
-use chrono::Local;
+use chrono::{DateTime, FixedOffset, Local, TimeDelta, Utc};
+use chrono_tz::Tz;
+use gloo_net::http::{Request, Response};
 use itertools::Itertools;
 use serde_json::Value;
 use wasm_bindgen_futures::spawn_local;
 use yew::prelude::*;
 
 use robotica_common::{
-    datetime::{datetime_to_string, duration},
+    datetime::duration,
     mqtt::{Json, MqttMessage},
-    robotica::tasks::Task,
-    scheduler::{Mark, MarkStatus, Sequence},
+    robotica::{http_api::ApiResponse, tasks::Task},
+    scheduler::{Mark, MarkStatus, Sequence, Status, TaskOutcome},
 };
 
 use crate::services::websocket::WebsocketService;
@@ -78,6 +80,7 @@ pub fn schedule(props: &Props) -> Html {
     let classes = classes!("sequence_list", modal_open_class);
 
     let expanded_id = &*opened_id;
+    let all = &*sequence_list;
     html! {
         <div class={classes}>
         {
@@ -87,7 +90,7 @@ pub fn schedule(props: &Props) -> Html {
                     <>
                         <h2>{date_string}</h2>
                         {
-                            sequence_list_to_html(sequence_list, expanded_id, &on_click, &on_close)
+                            sequence_list_to_html(sequence_list, all, expanded_id, &on_click, &on_close)
                         }
                     </>
                 }
@@ -98,12 +101,31 @@ pub fn schedule(props: &Props) -> Html {
     }
 }
 
+/// Convert a time to the timezone of the schedule, or the browser timezone if not known.
+fn to_schedule_time(sequence: &Sequence, time: DateTime<Utc>) -> DateTime<FixedOffset> {
+    sequence
+        .timezone
+        .as_deref()
+        .and_then(|timezone| timezone.parse::<Tz>().ok())
+        .map_or_else(
+            || time.with_timezone(&Local).fixed_offset(),
+            |timezone| time.with_timezone(&timezone).fixed_offset(),
+        )
+}
+
+fn schedule_time_to_string(sequence: &Sequence, time: DateTime<Utc>) -> String {
+    to_schedule_time(sequence, time)
+        .format("%Y-%m-%d %H:%M:%S %z")
+        .to_string()
+}
+
 fn get_local_date_for_sequence(sequence: &Sequence) -> chrono::NaiveDate {
-    sequence.start_time.with_timezone(&Local).date_naive()
+    to_schedule_time(sequence, sequence.start_time).date_naive()
 }
 
 fn sequence_list_to_html<'a>(
     sequence_list: impl Iterator<Item = &'a Sequence>,
+    all: &[Sequence],
     opened_id: &OpenedId,
     on_click: &Callback<OpenedId>,
     on_close: &Callback<()>,
@@ -112,7 +134,7 @@ fn sequence_list_to_html<'a>(
         <div class="sequence_list">
         {
             sequence_list.map(|sequence| {
-                sequence_to_html(sequence, opened_id, on_click, on_close)
+                sequence_to_html(sequence, all, opened_id, on_click, on_close)
             }).collect::<Html>()
         }
         </div>
@@ -121,6 +143,7 @@ fn sequence_list_to_html<'a>(
 
 fn sequence_to_html(
     sequence: &Sequence,
+    all: &[Sequence],
     opened_id: &OpenedId,
     on_click: &Callback<OpenedId>,
     on_close: &Callback<()>,
@@ -131,10 +154,10 @@ fn sequence_to_html(
         robotica_common::scheduler::Importance::High => "importance_high",
     };
     let status_class = match sequence.status {
-        Some(robotica_common::scheduler::Status::Pending) | None => "pending",
-        Some(robotica_common::scheduler::Status::InProgress) => "in_progress",
-        Some(robotica_common::scheduler::Status::Completed) => "completed",
-        Some(robotica_common::scheduler::Status::Cancelled) => "cancelled",
+        Some(Status::Pending) | None => "pending",
+        Some(Status::InProgress) => "in_progress",
+        Some(Status::Completed) => "completed",
+        Some(Status::Cancelled) => "cancelled",
     };
     let mark_class = match sequence.mark {
         Some(Mark {
@@ -145,11 +168,15 @@ fn sequence_to_html(
             status: MarkStatus::Done,
             ..
         }) => Some("done"),
+        Some(Mark {
+            status: MarkStatus::Postponed { .. },
+            ..
+        }) => Some("postponed"),
         None => None,
     };
 
-    let start_local = sequence.start_time.with_timezone(&Local);
-    let end_local = sequence.end_time.with_timezone(&Local);
+    let start_local = to_schedule_time(sequence, sequence.start_time);
+    let end_local = to_schedule_time(sequence, sequence.end_time);
     let days = (end_local.date_naive() - start_local.date_naive()).num_days();
 
     let start_str = start_local.format("%H:%M:%S").to_string();
@@ -161,6 +188,12 @@ fn sequence_to_html(
         end_str
     };
 
+    let original_str = sequence.original_start_time.map(|original| {
+        to_schedule_time(sequence, original)
+            .format("%H:%M:%S")
+            .to_string()
+    });
+
     let date = sequence.schedule_date;
     let seq_id = &sequence.id;
     let repeat_number = sequence.repeat_number;
@@ -172,12 +205,19 @@ fn sequence_to_html(
     let classes = classes!("sequence", importance_class, status_class, mark_class);
     html! {
         <div class={classes} id={sequence.id.clone()}>
-            <div>{start_str}{" - "}{end_str}</div>
+            <div>
+                {start_str}{" - "}{end_str}
+                {
+                    if let Some(original_str) = original_str {
+                        html! { <div class="original_time">{original_str}</div> }
+                    } else { html! {} }
+                }
+            </div>
             <div>
                 <div class="title" onclick={move |_| on_click_clone.emit(OpenedId::Sequence(id_clone.clone()))}><span>{&sequence.title}</span></div>
                 {
                     if OpenedId::Sequence(id.clone()) == *opened_id {
-                        popover_sequence_content(sequence, on_close)
+                        popover_sequence_content(sequence, all, on_close)
                     } else { html! {} }
                 }
                 {
@@ -204,14 +244,23 @@ fn task_to_html(
     let id = format!("{date}-{seq_id}-{repeat_number}-{i}");
     let id_clone = id.clone();
     let on_click = on_click.clone();
+    let outcome = sequence.outcomes.get(i);
+    let outcome_class = match outcome {
+        Some(TaskOutcome::Sent) => Some("sent"),
+        Some(TaskOutcome::Skipped { .. }) => Some("skipped"),
+        Some(TaskOutcome::Confirmed) => Some("confirmed"),
+        Some(TaskOutcome::Failed { .. }) => Some("failed"),
+        None => None,
+    };
+    let classes = classes!("task", outcome_class);
 
     html! {
         html! {
             <>
-                <div class="task" onclick={move |_| on_click.emit(OpenedId::Task(id_clone.clone()))}><span>{&task.title}</span></div>
+                <div class={classes} onclick={move |_| on_click.emit(OpenedId::Task(id_clone.clone()))}><span>{&task.title}</span></div>
                 {
                     if OpenedId::Task(id) == *opened_id {
-                        popover_task_content(task, on_close)
+                        popover_task_content(task, outcome, on_close)
                     } else { html! {} }
                 }
             </>
@@ -259,7 +308,11 @@ fn json_to_html(json: &Value) -> Html {
     }
 }
 
-fn popover_sequence_content(sequence: &Sequence, on_close: &Callback<()>) -> Html {
+fn popover_sequence_content(
+    sequence: &Sequence,
+    all: &[Sequence],
+    on_close: &Callback<()>,
+) -> Html {
     let mark = match sequence.mark.clone() {
         Some(mark) => format!("{mark:?}"),
         None => "None".to_string(),
@@ -304,15 +357,23 @@ fn popover_sequence_content(sequence: &Sequence, on_close: &Callback<()>) -> Htm
                             } else { html! {} } }
                             <tr>
                                 <th scope="row">{"Required Time"}</th>
-                                <td>{datetime_to_string(sequence.start_time)}</td>
+                                <td>{schedule_time_to_string(sequence, sequence.start_time)}</td>
                             </tr>
+                            { if let Some(original) = sequence.original_start_time {
+                                html! {
+                                    <tr>
+                                        <th scope="row">{"Original Time"}</th>
+                                        <td>{schedule_time_to_string(sequence, original)}</td>
+                                    </tr>
+                                }
+                            } else { html! {} } }
                             <tr>
                                 <th scope="row">{"Required Duration"}</th>
                                 <td>{duration::to_string(sequence.duration)}</td>
                             </tr>
                             <tr>
                                 <th scope="row">{"Latest Time"}</th>
-                                <td>{datetime_to_string(sequence.latest_time)}</td>
+                                <td>{schedule_time_to_string(sequence, sequence.latest_time)}</td>
                             </tr>
                             <tr>
                                 <th scope="row">{"Schedule Date"}</th>
@@ -334,6 +395,7 @@ fn popover_sequence_content(sequence: &Sequence, on_close: &Callback<()>) -> Htm
                     </table>
                         </div>
                         <div class="modal-footer">
+                            { mark_buttons(sequence, all) }
                             <button type="button" class="btn btn-secondary" onclick={on_close}>{"Close"}</button>
                         </div>
                     </div>
@@ -344,7 +406,108 @@ fn popover_sequence_content(sequence: &Sequence, on_close: &Callback<()>) -> Htm
     }
 }
 
-fn popover_task_content(task: &Task, on_close: &Callback<()>) -> Html {
+const POSTPONE_TIME: TimeDelta = TimeDelta::minutes(30);
+
+/// Get a mark that covers all repeats of the sequence on the same day.
+fn mark_for_sequence(sequence: &Sequence, all: &[Sequence], status: MarkStatus) -> Mark {
+    let repeats = || {
+        all.iter()
+            .filter(|s| s.id == sequence.id && s.schedule_date == sequence.schedule_date)
+    };
+    let start_time = repeats()
+        .map(Sequence::original_start_time)
+        .min()
+        .unwrap_or_else(|| sequence.original_start_time());
+    let end_time = repeats()
+        .map(Sequence::original_end_time)
+        .max()
+        .unwrap_or_else(|| sequence.original_end_time());
+
+    Mark {
+        id: sequence.id.clone(),
+        status,
+        start_time,
+        end_time: end_time + TimeDelta::minutes(1),
+    }
+}
+
+fn mark_buttons(sequence: &Sequence, all: &[Sequence]) -> Html {
+    let upcoming = matches!(sequence.status, Some(Status::Pending) | None);
+
+    if sequence.mark.is_some() {
+        let id = sequence.id.clone();
+        let on_clear = move |_| clear_mark(id.clone());
+        html! {
+            <button type="button" class="btn btn-warning" onclick={on_clear}>{"Clear Mark"}</button>
+        }
+    } else if upcoming {
+        let done = mark_for_sequence(sequence, all, MarkStatus::Done);
+        let on_done = move |_| set_mark(done.clone());
+        let cancel = mark_for_sequence(sequence, all, MarkStatus::Cancelled);
+        let on_cancel = move |_| set_mark(cancel.clone());
+        let later = MarkStatus::Postponed {
+            offset: POSTPONE_TIME,
+        };
+        let later = mark_for_sequence(sequence, all, later);
+        let on_later = move |_| set_mark(later.clone());
+        let earlier = MarkStatus::Postponed {
+            offset: -POSTPONE_TIME,
+        };
+        let earlier = mark_for_sequence(sequence, all, earlier);
+        let on_earlier = move |_| set_mark(earlier.clone());
+        html! {
+            <>
+                <button type="button" class="btn btn-success" onclick={on_done}>{"Done"}</button>
+                <button type="button" class="btn btn-danger" onclick={on_cancel}>{"Cancel"}</button>
+                <button type="button" class="btn btn-primary" onclick={on_earlier}>{"30m Earlier"}</button>
+                <button type="button" class="btn btn-primary" onclick={on_later}>{"30m Later"}</button>
+            </>
+        }
+    } else {
+        html! {}
+    }
+}
+
+async fn process_response(response: Result<Response, gloo_net::Error>) -> Result<(), String> {
+    let response = response.map_err(|err| format!("http error: {err:?}"))?;
+    let api_response: Option<ApiResponse<serde_json::Value>> = response.json().await.ok();
+
+    match (response.ok(), api_response) {
+        (true, _) => Ok(()),
+        (false, Some(ApiResponse::Error(err))) => Err(err.message),
+        (false, _) => Err(response.status_text()),
+    }
+}
+
+fn set_mark(mark: Mark) {
+    spawn_local(async move {
+        let request = match Request::post("/api/marks").json(&mark) {
+            Ok(request) => request,
+            Err(err) => {
+                tracing::error!("Failed to encode mark {mark}: {err}");
+                return;
+            }
+        };
+        if let Err(err) = process_response(request.send().await).await {
+            tracing::error!("Failed to set mark {mark}: {err}");
+        }
+    });
+}
+
+fn clear_mark(id: String) {
+    spawn_local(async move {
+        let response = Request::delete(&format!("/api/marks/{id}")).send().await;
+        if let Err(err) = process_response(response).await {
+            tracing::error!("Failed to clear mark for {id}: {err}");
+        }
+    });
+}
+
+fn popover_task_content(
+    task: &Task,
+    outcome: Option<&TaskOutcome>,
+    on_close: &Callback<()>,
+) -> Html {
     use robotica_common::robotica::tasks::Payload;
 
     let payload = match &task.payload {
@@ -395,6 +558,14 @@ fn popover_task_content(task: &Task, on_close: &Callback<()>) -> Html {
                                 <th scope="row">{"Retain"}</th>
                                 <td>{format!("{:?}", task.retain)}</td>
                             </tr>
+                            { if let Some(outcome) = outcome {
+                                html! {
+                                    <tr>
+                                        <th scope="row">{"Outcome"}</th>
+                                        <td>{outcome.to_string()}</td>
+                                    </tr>
+                                }
+                            } else { html! {} } }
                         </tbody>
                     </table>
                         </div>
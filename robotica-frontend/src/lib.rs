@@ -40,6 +40,8 @@ enum Route {
     Prices,
     #[at("/schedule")]
     Schedule,
+    #[at("/history")]
+    History,
     #[at("/tags")]
     Tags,
     #[at("/locations")]
@@ -12,7 +12,7 @@ use robotica_common::{
         audio::{AudioCommand, Message, State},
         commands::Command,
         entities::{AnyId, IdWithRoom},
-        lights::LightCommand,
+        lights::{FlashPattern, LightCommand},
         switch::{DeviceAction, DevicePower},
         tasks::{Payload, SubTask, Task},
     },
@@ -180,7 +180,7 @@ async fn watch_audio(
                         vec![SubTask{
                             title: "Flash lights".to_string(),
                             target: "light".to_string(),
-                            payload: Payload::Command(Command::Light(LightCommand::Flash)),
+                            payload: Payload::Command(Command::Light(LightCommand::Flash(FlashPattern::default()))),
                             qos: QoS::ExactlyOnce,
                             retain: Retain::NoRetain,
                         }]
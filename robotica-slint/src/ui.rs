@@ -17,12 +17,13 @@ use crate::{
     RunningState,
 };
 use ::slint::{ComponentHandle, Model, ModelRc, RgbaColor, SharedString, VecModel, Weak};
-use chrono::{Local, Timelike};
+use chrono::{DateTime, Timelike, Utc};
+use chrono_tz::Tz;
 use serde::Deserialize;
 
 use robotica_common::{
     config::{ButtonConfig, ButtonRowConfig, Config as CommonConfig, ControllerConfig, Icon},
-    scheduler::{Importance, Status},
+    scheduler::{Importance, Status, TaskOutcome},
 };
 use robotica_common::{
     controllers::{ConfigTrait, ControllerTrait, DisplayState},
@@ -31,6 +32,7 @@ use robotica_common::{
     scheduler::{Sequence, Tags},
 };
 use robotica_tokio::{
+    host_timezone,
     pipes::{stateful, Subscriber, Subscription},
     services::mqtt::MqttTx,
 };
@@ -53,6 +55,9 @@ pub struct Config {
     backlight_on_time: u64,
     ui_config_name: String,
     programs: ProgramsConfig,
+    /// The IANA timezone for the clock and schedule, if not the timezone of the host.
+    #[serde(default)]
+    timezone: Option<Tz>,
 }
 
 #[derive()]
@@ -67,6 +72,7 @@ pub struct LoadedConfig {
     backlight_on_time: u64,
     ui_config_name: String,
     programs: LoadedProgramsConfig,
+    timezone: Tz,
 }
 
 pub struct Button {
@@ -89,6 +95,7 @@ impl TryFrom<Config> for LoadedConfig {
             backlight_on_time: config.backlight_on_time,
             ui_config_name: config.ui_config_name,
             programs,
+            timezone: config.timezone.unwrap_or_else(host_timezone),
         })
     }
 }
@@ -133,9 +140,11 @@ pub fn run_gui(
     ui.set_number_per_row(i32::from(config.number_per_row));
     ui.hide().unwrap();
 
+    let timezone = config.timezone;
     spawn_config_monitor(
         ui.as_weak(),
         config.ui_config_name.clone(),
+        timezone,
         state.mqtt.clone(),
         rx_room,
     );
@@ -143,7 +152,7 @@ pub fn run_gui(
     monitor_room_change(&ui, tx_room);
     monitor_screen_reset(&state, &ui);
     monitor_display(config, &ui, rx_screen_command);
-    monitor_time(&ui);
+    monitor_time(&ui, timezone);
 
     std::thread::sleep(std::time::Duration::from_secs(3));
 
@@ -158,6 +167,7 @@ pub fn run_gui(
 fn spawn_config_monitor(
     handle_weak: Weak<slint::AppWindow>,
     name: String,
+    timezone: Tz,
     mqtt: MqttTx,
     mut rx_room: mpsc::Receiver<String>,
 ) {
@@ -227,7 +237,14 @@ fn spawn_config_monitor(
                             None
                         };
 
-                        setup_config(&handle, &common_config, id.as_ref(), &mqtt, &cancellation);
+                        setup_config(
+                            &handle,
+                            &common_config,
+                            id.as_ref(),
+                            timezone,
+                            &mqtt,
+                            &cancellation,
+                        );
                     })
                     .unwrap();
             }
@@ -239,6 +256,7 @@ fn setup_config(
     ui: &slint::AppWindow,
     config: &Arc<CommonConfig>,
     room: Option<&String>,
+    timezone: Tz,
     mqtt: &MqttTx,
     cancellation: &CancellationToken,
 ) {
@@ -303,7 +321,13 @@ fn setup_config(
     monitor_buttons_state(buttons, mqtt, ui, cancellation);
 
     monitor_tags(config.clone(), mqtt.clone(), ui, cancellation.clone());
-    monitor_schedule(config.clone(), mqtt.clone(), ui, cancellation.clone());
+    monitor_schedule(
+        config.clone(),
+        timezone,
+        mqtt.clone(),
+        ui,
+        cancellation.clone(),
+    );
 }
 
 fn monitor_room_change(ui: &slint::AppWindow, tx_room: mpsc::Sender<String>) {
@@ -466,21 +490,50 @@ fn monitor_tags(
     });
 }
 
-fn get_local_date_for_sequence(sequence: &Sequence) -> chrono::NaiveDate {
-    sequence.start_time.with_timezone(&Local).date_naive()
+/// Convert a time to the timezone of the schedule, or the given timezone if not known.
+fn to_schedule_time(sequence: &Sequence, time: DateTime<Utc>, timezone: Tz) -> DateTime<Tz> {
+    let timezone = sequence
+        .timezone
+        .as_deref()
+        .and_then(|timezone| timezone.parse().ok())
+        .unwrap_or(timezone);
+    time.with_timezone(&timezone)
+}
+
+fn get_local_date_for_sequence(sequence: &Sequence, timezone: Tz) -> chrono::NaiveDate {
+    to_schedule_time(sequence, sequence.start_time, timezone).date_naive()
 }
 
 fn sequences_to_slint<'a>(
     sequences: impl Iterator<Item = &'a Sequence>,
+    timezone: Tz,
 ) -> Vec<slint::SequenceData> {
     sequences
         .map(|s| {
-            let tasks: Vec<SharedString> = s.tasks.iter().map(|t| t.title.clone().into()).collect();
+            let tasks: Vec<SharedString> = s
+                .tasks
+                .iter()
+                .enumerate()
+                .map(|(i, t)| match s.outcomes.get(i) {
+                    Some(TaskOutcome::Skipped { .. }) => format!("{} (skipped)", t.title).into(),
+                    Some(TaskOutcome::Failed { .. }) => format!("{} (failed)", t.title).into(),
+                    Some(TaskOutcome::Sent | TaskOutcome::Confirmed) | None => {
+                        t.title.clone().into()
+                    }
+                })
+                .collect();
             let b: VecModel<SharedString> = VecModel::from(tasks);
             let c: ModelRc<SharedString> = ModelRc::new(b);
 
-            let local = s.start_time.with_timezone(&Local);
+            let local = to_schedule_time(s, s.start_time, timezone);
             let time = local.format("%H:%M:%S").to_string();
+            let time = match s.original_start_time {
+                Some(original) => {
+                    let original = to_schedule_time(s, original, timezone).format("%H:%M:%S");
+                    format!("{time} (was {original})")
+                }
+                None => time,
+            };
             let status = match s.status {
                 Some(Status::Pending) | None => 0,
                 Some(Status::InProgress) => 1,
@@ -501,6 +554,7 @@ fn sequences_to_slint<'a>(
 
 fn monitor_schedule(
     config: Arc<CommonConfig>,
+    timezone: Tz,
     mqtt: MqttTx,
     ui: &slint::AppWindow,
     cancellation: CancellationToken,
@@ -528,11 +582,11 @@ fn monitor_schedule(
                                 let schedule = msg.as_ref();
                                 let schedule = schedule
                                     .iter()
-                                    .chunk_by(|s| get_local_date_for_sequence(s))
+                                    .chunk_by(|s| get_local_date_for_sequence(s, timezone))
                                     .into_iter()
                                     .map(|(date, sequences)| {
                                         let date = date.format("%A, %e %B, %Y").to_string();
-                                        let sequences: Vec<slint::SequenceData> = sequences_to_slint(sequences);
+                                        let sequences: Vec<slint::SequenceData> = sequences_to_slint(sequences, timezone);
                                         slint::ScheduleData {
                                             date: date.into(),
                                             sequences: ModelRc::new(VecModel::from(sequences)),
@@ -555,11 +609,11 @@ fn monitor_schedule(
     });
 }
 
-fn monitor_time(ui: &slint::AppWindow) {
+fn monitor_time(ui: &slint::AppWindow, timezone: Tz) {
     let handle_weak = ui.as_weak();
     tokio::spawn(async move {
         loop {
-            let time = Local::now();
+            let time = Utc::now().with_timezone(&timezone);
 
             #[allow(clippy::cast_possible_wrap)]
             let hour = time.hour() as i32;
@@ -1,3 +1,4 @@
 //! This module contains functions to access the database.
 pub mod postgis;
+pub mod schedule_history;
 pub mod zones;
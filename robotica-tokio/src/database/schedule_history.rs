@@ -0,0 +1,245 @@
+//! Access schedule history table in the database
+use chrono::{DateTime, NaiveDate, Utc};
+use robotica_common::scheduler::{CreateHistoryEntry, HistoryEntry, HistoryPage, HistoryTask};
+use tap::Pipe;
+
+/// The maximum number of entries returned in one page.
+pub const MAX_PAGE_SIZE: u32 = 500;
+
+/// Which history entries to list.
+#[derive(Debug, Default)]
+pub struct HistoryFilter {
+    /// Only entries for this sequence id.
+    pub sequence_id: Option<String>,
+
+    /// Only entries at or after this time.
+    pub from: Option<DateTime<Utc>>,
+
+    /// Only entries before this time.
+    pub to: Option<DateTime<Utc>>,
+
+    /// Only entries older than this entry id, for pagination.
+    pub before: Option<i64>,
+}
+
+/// Add an entry to the schedule history.
+///
+/// If the entry is for a task that has already been recorded, the outcome and detail are updated instead,
+/// unless the new outcome is `missed`, which never replaces what really happened.
+///
+/// # Arguments
+///
+/// * `postgres` - The `PostgreSQL` connection pool.
+/// * `entry` - The entry to add.
+///
+/// # Errors
+///
+/// This function can return an error if there is a problem with the database connection or query execution.
+pub async fn add_history_entry(
+    postgres: &sqlx::PgPool,
+    entry: &CreateHistoryEntry,
+) -> Result<i64, sqlx::Error> {
+    let task = entry.task.as_ref();
+    // Chrono types need `as _` as sqlx prefers the `time` crate when both are enabled.
+    sqlx::query!(
+        r#"INSERT INTO schedule_history (time, sequence_id, sequence_title, schedule_date, repeat_number, task_index, task_title, topics, payload, outcome, detail) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) ON CONFLICT (schedule_date, sequence_id, repeat_number, task_index) WHERE task_index IS NOT NULL DO UPDATE SET outcome = CASE WHEN EXCLUDED.outcome = 'missed' THEN schedule_history.outcome ELSE EXCLUDED.outcome END, detail = CASE WHEN EXCLUDED.outcome = 'missed' THEN schedule_history.detail ELSE EXCLUDED.detail END RETURNING id"#,
+        entry.time as _,
+        entry.sequence_id,
+        entry.sequence_title,
+        entry.schedule_date as _,
+        entry.repeat_number.and_then(|n| i32::try_from(n).ok()),
+        task.and_then(|t| i32::try_from(t.index).ok()),
+        task.map(|t| t.title.as_str()),
+        &task.map(|t| t.topics.clone()).unwrap_or_default(),
+        task.and_then(|t| t.payload.as_deref()),
+        entry.outcome.as_str(),
+        entry.detail,
+    )
+    .fetch_one(postgres)
+    .await?
+    .id
+    .pipe(Ok)
+}
+
+/// List schedule history entries, newest first.
+///
+/// # Arguments
+///
+/// * `postgres` - The `PostgreSQL` connection pool.
+/// * `filter` - Which entries to list.
+/// * `limit` - The maximum number of entries to return, capped at [`MAX_PAGE_SIZE`].
+///
+/// # Errors
+///
+/// This function can return an error if there is a problem with the database connection or query execution,
+/// or if a stored outcome is not known.
+pub async fn list_history(
+    postgres: &sqlx::PgPool,
+    filter: &HistoryFilter,
+    limit: u32,
+) -> Result<HistoryPage, sqlx::Error> {
+    let limit = limit.clamp(1, MAX_PAGE_SIZE);
+
+    // Get one extra row to find out if there is another page.
+    let mut entries = sqlx::query!(
+        r#"SELECT id, time as "time: DateTime<Utc>", sequence_id, sequence_title, schedule_date as "schedule_date: NaiveDate", repeat_number, task_index, task_title, topics, payload, outcome, detail FROM schedule_history WHERE ($1::text IS NULL OR sequence_id = $1) AND ($2::timestamptz IS NULL OR time >= $2) AND ($3::timestamptz IS NULL OR time < $3) AND ($4::bigint IS NULL OR id < $4) ORDER BY id DESC LIMIT $5"#,
+        filter.sequence_id,
+        filter.from as _,
+        filter.to as _,
+        filter.before,
+        i64::from(limit) + 1,
+    )
+    .fetch_all(postgres)
+    .await?
+    .into_iter()
+    .map(|row| {
+        let outcome = row
+            .outcome
+            .parse()
+            .map_err(|err| sqlx::Error::Decode(Box::new(err)))?;
+        let task = row
+            .task_index
+            .and_then(|index| usize::try_from(index).ok())
+            .map(|index| HistoryTask {
+                index,
+                title: row.task_title.unwrap_or_default(),
+                topics: row.topics,
+                payload: row.payload,
+            });
+        HistoryEntry {
+            id: row.id,
+            time: row.time,
+            sequence_id: row.sequence_id,
+            sequence_title: row.sequence_title,
+            schedule_date: row.schedule_date,
+            repeat_number: row.repeat_number.and_then(|n| usize::try_from(n).ok()),
+            task,
+            outcome,
+            detail: row.detail,
+        }
+        .pipe(Ok)
+    })
+    .collect::<Result<Vec<_>, sqlx::Error>>()?;
+
+    let next = if entries.len() > limit as usize {
+        entries.truncate(limit as usize);
+        entries.last().map(|entry| entry.id)
+    } else {
+        None
+    };
+
+    Ok(HistoryPage { entries, next })
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+    use chrono::TimeZone;
+    use robotica_common::scheduler::HistoryOutcome;
+    use sqlx::{Pool, Postgres};
+
+    use super::*;
+
+    fn task_entry(outcome: HistoryOutcome) -> CreateHistoryEntry {
+        CreateHistoryEntry {
+            time: Utc.with_ymd_and_hms(2026, 10, 13, 8, 0, 0).unwrap(),
+            sequence_id: "pool_pump".to_string(),
+            sequence_title: "Pool Pump".to_string(),
+            schedule_date: NaiveDate::from_ymd_opt(2026, 10, 13),
+            repeat_number: Some(1),
+            task: Some(HistoryTask {
+                index: 0,
+                title: "Turn on pool pump".to_string(),
+                topics: vec!["robotica/command/pool/pump".to_string()],
+                payload: Some("on".to_string()),
+            }),
+            outcome,
+            detail: None,
+        }
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_task_outcome_replaced(postgres: Pool<Postgres>) {
+        let first = add_history_entry(&postgres, &task_entry(HistoryOutcome::Sent))
+            .await
+            .unwrap();
+        let failed = CreateHistoryEntry {
+            detail: Some("timed out".to_string()),
+            ..task_entry(HistoryOutcome::Failed)
+        };
+        let second = add_history_entry(&postgres, &failed).await.unwrap();
+        assert_eq!(first, second);
+
+        let page = list_history(&postgres, &HistoryFilter::default(), 10)
+            .await
+            .unwrap();
+        assert_eq!(page.entries.len(), 1);
+        assert_eq!(page.entries[0].outcome, HistoryOutcome::Failed);
+        assert_eq!(page.entries[0].detail.as_deref(), Some("timed out"));
+        assert_eq!(page.next, None);
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_missed_does_not_replace_outcome(postgres: Pool<Postgres>) {
+        let confirmed = add_history_entry(&postgres, &task_entry(HistoryOutcome::Confirmed))
+            .await
+            .unwrap();
+
+        // After a restart the executor replays the day, and sees the task as missed.
+        let restarted = CreateHistoryEntry {
+            time: Utc.with_ymd_and_hms(2026, 10, 13, 12, 0, 0).unwrap(),
+            ..task_entry(HistoryOutcome::Missed)
+        };
+        let missed = add_history_entry(&postgres, &restarted).await.unwrap();
+        assert_eq!(confirmed, missed);
+
+        let page = list_history(&postgres, &HistoryFilter::default(), 10)
+            .await
+            .unwrap();
+        assert_eq!(page.entries.len(), 1);
+        assert_eq!(page.entries[0].outcome, HistoryOutcome::Confirmed);
+
+        // A task that was never recorded is still recorded as missed.
+        let other = CreateHistoryEntry {
+            repeat_number: Some(2),
+            ..restarted
+        };
+        add_history_entry(&postgres, &other).await.unwrap();
+        let page = list_history(&postgres, &HistoryFilter::default(), 10)
+            .await
+            .unwrap();
+        assert_eq!(page.entries.len(), 2);
+        assert_eq!(page.entries[0].outcome, HistoryOutcome::Missed);
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn test_list_history_pages(postgres: Pool<Postgres>) {
+        for outcome in [
+            HistoryOutcome::MarkDone,
+            HistoryOutcome::MarkCancelled,
+            HistoryOutcome::MarkRemoved,
+        ] {
+            let entry = CreateHistoryEntry {
+                task: None,
+                ..task_entry(outcome)
+            };
+            add_history_entry(&postgres, &entry).await.unwrap();
+        }
+
+        let filter = HistoryFilter {
+            sequence_id: Some("pool_pump".to_string()),
+            ..HistoryFilter::default()
+        };
+        let page = list_history(&postgres, &filter, 2).await.unwrap();
+        assert_eq!(page.entries.len(), 2);
+        assert!(page.next.is_some());
+
+        let filter = HistoryFilter {
+            before: page.next,
+            ..filter
+        };
+        let page = list_history(&postgres, &filter, 2).await.unwrap();
+        assert_eq!(page.entries.len(), 1);
+        assert_eq!(page.next, None);
+    }
+}
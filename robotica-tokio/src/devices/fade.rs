@@ -0,0 +1,169 @@
+//! Fade between colors in software, for lights that cannot fade by themselves
+//!
+//! LIFX and `Zigbee2MQTT` lights fade natively, this is for backends that don't, like the debug light.
+use std::time::Duration;
+
+use robotica_common::robotica::lights::{PowerColor, PowerColorTransition};
+use tokio::{
+    select,
+    time::{sleep_until, Instant},
+};
+
+use crate::{
+    pipes::{stateful, Subscriber, Subscription},
+    spawn,
+};
+
+/// The shortest time between two steps of a fade.
+const MIN_STEP: Duration = Duration::from_millis(100);
+
+/// The most steps a fade will take, however long it is.
+const MAX_STEPS: u32 = 100;
+
+#[derive(Debug)]
+struct Fade {
+    from: PowerColor,
+    to: PowerColor,
+    start: Instant,
+    duration: Duration,
+    step: u32,
+    steps: u32,
+}
+
+fn get_steps(duration: Duration) -> u32 {
+    let steps = duration.as_millis() / MIN_STEP.as_millis();
+    u32::try_from(steps)
+        .unwrap_or(MAX_STEPS)
+        .clamp(1, MAX_STEPS)
+}
+
+impl Fade {
+    fn new(from: PowerColor, transition: PowerColorTransition, start: Instant) -> Self {
+        Self {
+            from,
+            to: transition.pc,
+            start,
+            duration: transition.duration,
+            step: 0,
+            steps: get_steps(transition.duration),
+        }
+    }
+
+    fn next_instant(&self) -> Instant {
+        self.start + self.duration * (self.step + 1) / self.steps
+    }
+
+    /// Move to the next step, returning the color and if the fade is finished.
+    #[allow(clippy::cast_precision_loss)]
+    fn next_step(&mut self) -> (PowerColor, bool) {
+        self.step += 1;
+        let fraction = self.step as f32 / self.steps as f32;
+        let pc = self.from.interpolate(&self.to, fraction);
+        (pc, self.step >= self.steps)
+    }
+}
+
+async fn maybe_sleep_until(fade: Option<&Fade>) -> Option<()> {
+    if let Some(fade) = fade {
+        sleep_until(fade.next_instant()).await;
+        Some(())
+    } else {
+        None
+    }
+}
+
+/// Turn transitions into a series of colors over the duration of each transition.
+///
+/// A new transition interrupts any fade in progress, starting from the last color sent.
+#[must_use]
+pub fn software_fade(
+    rx: stateful::Receiver<PowerColorTransition>,
+    name: impl Into<String>,
+) -> stateful::Receiver<PowerColor> {
+    let (tx, faded_rx) = stateful::create_pipe(name);
+
+    spawn(async move {
+        let mut rx_s = rx.subscribe().await;
+        let mut current = PowerColor::Off;
+        let mut fade: Option<Fade> = None;
+
+        loop {
+            select! {
+                Ok(transition) = rx_s.recv() => {
+                    if transition.duration.is_zero() {
+                        fade = None;
+                        current = transition.pc;
+                        tx.try_send(current.clone());
+                    } else {
+                        fade = Some(Fade::new(current.clone(), transition, Instant::now()));
+                    }
+                }
+                Some(()) = maybe_sleep_until(fade.as_ref()) => {
+                    if let Some(f) = &mut fade {
+                        let (pc, finished) = f.next_step();
+                        current = pc;
+                        tx.try_send(current.clone());
+                        if finished {
+                            fade = None;
+                        }
+                    }
+                }
+                else => break,
+            }
+        }
+    });
+
+    faded_rx
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use robotica_common::{
+        datetime::duration,
+        robotica::lights::{Colors, HSBK},
+    };
+
+    use super::*;
+
+    fn on(brightness: f32) -> PowerColor {
+        PowerColor::On(Colors::Single(HSBK {
+            hue: 0.0,
+            saturation: 0.0,
+            brightness,
+            kelvin: 3500,
+        }))
+    }
+
+    #[test]
+    fn test_get_steps() {
+        assert_eq!(get_steps(Duration::from_millis(50)), 1);
+        assert_eq!(get_steps(Duration::from_millis(250)), 2);
+        assert_eq!(get_steps(duration::seconds(5)), 50);
+        assert_eq!(get_steps(duration::minutes(10)), MAX_STEPS);
+    }
+
+    #[test]
+    fn test_fade_steps() {
+        let start = Instant::now();
+        let transition = PowerColorTransition {
+            pc: PowerColor::Off,
+            duration: duration::minutes(10),
+        };
+        let mut fade = Fade::new(on(100.0), transition, start);
+
+        assert_eq!(fade.next_instant(), start + duration::seconds(6));
+        assert_eq!(fade.next_step(), (on(99.0), false));
+        assert_eq!(fade.next_instant(), start + duration::seconds(12));
+
+        for _ in 1..49 {
+            fade.next_step();
+        }
+        assert_eq!(fade.next_step(), (on(50.0), false));
+
+        for _ in 50..99 {
+            fade.next_step();
+        }
+        assert_eq!(fade.next_step(), (PowerColor::Off, true));
+    }
+}
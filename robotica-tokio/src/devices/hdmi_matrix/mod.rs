@@ -1,6 +1,8 @@
 //! Dodgy HDMI matrix of unknown origin.
 use std::fmt::Debug;
 
+use chrono::{TimeDelta, Utc};
+use robotica_macro::time_delta_constant;
 use thiserror::Error;
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
@@ -13,8 +15,11 @@ use tracing::{debug, info};
 use crate::{
     is_debug_mode,
     pipes::{stateful, stateless, Subscriber, Subscription},
+    sources::timer::{scheduled_timer, CatchUp, Schedule, ScheduledTimerConfig},
 };
 
+const POLL_PERIOD: TimeDelta = time_delta_constant!(30 seconds);
+
 /// A command to send to the HDMI matrix.
 #[derive(Clone, Debug)]
 pub enum Command {
@@ -56,33 +61,36 @@ where
 {
     let options = options.clone();
     let name = format!("{addr:?}");
-    let (tx, rx) = stateful::create_pipe(name);
+    let (tx, rx) = stateful::create_pipe(&name);
+
+    let ticks = scheduled_timer(
+        &format!("{name} (poll)"),
+        ScheduledTimerConfig {
+            schedule: Schedule::Aligned {
+                period: POLL_PERIOD,
+                offset: TimeDelta::zero(),
+            },
+            timezone: Utc,
+            catch_up: CatchUp::Latest,
+            state: None,
+        },
+    );
 
     let handle = spawn(async move {
         debug!("hdmi: Starting with addr {addr:?}");
         let mut rx_cmd_s = rx_cmd.subscribe().await;
-        let mut timer = tokio::time::interval(std::time::Duration::from_secs(30));
+        let mut ticks_s = ticks.subscribe().await;
         let addr = addr;
 
         let mut status: Status = [None; 4];
 
+        // Don't wait for the first tick.
+        update_status(&addr, &options, &tx, &mut status).await;
+
         loop {
             select! {
-                _ = timer.tick() => {
-                    if options.disable_polling  {
-                        debug!("hdmi: disabled polling {addr:?}");
-                    } else {
-                        match poll(&addr).await {
-                            Ok(new_status) => {
-                                status = new_status;
-                                tx.try_send(Ok(status));
-                            },
-                            Err(e) => {
-                                debug!("hdmi: error polling {addr:?}: {e}");
-                                tx.try_send(Err(Error::IoError(e.to_string())));
-                            }
-                        }
-                    }
+                Ok(_) = ticks_s.recv() => {
+                    update_status(&addr, &options, &tx, &mut status).await;
                 }
 
                 Ok(cmd) = rx_cmd_s.recv() => {
@@ -111,6 +119,31 @@ where
     (rx, handle)
 }
 
+async fn update_status<A>(
+    addr: &A,
+    options: &Options,
+    tx: &stateful::Sender<Result<Status, Error>>,
+    status: &mut Status,
+) where
+    A: ToSocketAddrs + Send + Sync + Debug,
+{
+    if options.disable_polling {
+        debug!("hdmi: disabled polling {addr:?}");
+        return;
+    }
+
+    match poll(addr).await {
+        Ok(new_status) => {
+            *status = new_status;
+            tx.try_send(Ok(*status));
+        }
+        Err(e) => {
+            debug!("hdmi: error polling {addr:?}: {e}");
+            tx.try_send(Err(Error::IoError(e.to_string())));
+        }
+    }
+}
+
 async fn connect<A>(addr: A) -> Result<TcpStream, std::io::Error>
 where
     A: ToSocketAddrs + Send + Sync + Debug,
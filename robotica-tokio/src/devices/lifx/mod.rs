@@ -0,0 +1,1433 @@
+//! Discover and control LIFX devices
+#[cfg(test)]
+mod simulator;
+
+use std::{collections::HashMap, net::SocketAddr, time::Duration};
+
+use chrono::{DateTime, Utc};
+use lifx_core::{BuildOptions, Message, RawMessage};
+use robotica_common::robotica::lights::{
+    Colors, PowerColor, PowerColorTransition, PowerLevel, State, HSBK,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{
+    net::UdpSocket,
+    select,
+    time::{interval, interval_at, sleep_until, Instant},
+};
+use tracing::{debug, error, info};
+
+use crate::{
+    pipes::stateful,
+    pipes::{stateless, Subscriber, Subscription},
+    spawn,
+};
+
+/// A LIFX ID
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct LifxId(u64);
+
+impl LifxId {
+    /// Create a new LIFX ID
+    #[must_use]
+    pub const fn new(id: u64) -> Self {
+        Self(id)
+    }
+}
+
+impl std::fmt::Display for LifxId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// Configuration for the LIFX discovery process
+pub struct DiscoverConfig {
+    /// The broadcast address to use for discovery.
+    pub broadcast: String,
+
+    /// The time between discovery requests.
+    pub poll_time: Duration,
+
+    /// The time before the light is considered unreachable.
+    pub device_timeout: Duration,
+
+    /// The time before each API call times out.
+    pub api_timeout: Duration,
+
+    /// The number of times to retry an API call.
+    pub num_retries: u8,
+
+    /// The time between checking the state of each light, if set.
+    pub state_poll_time: Option<Duration>,
+
+    /// What to do if a light was changed by something else.
+    pub reconcile: Reconcile,
+
+    /// Devices to probe directly, for networks where broadcasts don't reach them.
+    pub static_devices: Vec<StaticDevice>,
+}
+
+/// A LIFX device with a known address.
+#[derive(Debug, Copy, Clone)]
+pub struct StaticDevice {
+    /// The ID of the device.
+    pub id: LifxId,
+
+    /// The address of the device.
+    pub addr: SocketAddr,
+}
+
+/// What is known about a LIFX device on the network.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct InventoryEntry {
+    /// The ID of the device.
+    pub id: LifxId,
+
+    /// The address the device was last seen at.
+    pub addr: Option<SocketAddr>,
+
+    /// Was the device configured with a static address?
+    pub is_static: bool,
+
+    /// When the device last replied to us.
+    pub last_seen: Option<DateTime<Utc>>,
+
+    /// The version of the host firmware.
+    pub firmware: Option<String>,
+
+    /// The WiFi signal strength in dBm.
+    pub signal: Option<f32>,
+}
+
+impl InventoryEntry {
+    const fn new(id: LifxId, addr: Option<SocketAddr>, is_static: bool) -> Self {
+        Self {
+            id,
+            addr,
+            is_static,
+            last_seen: None,
+            firmware: None,
+            signal: None,
+        }
+    }
+}
+
+fn get_inventory(inventory: &HashMap<LifxId, InventoryEntry>) -> Vec<InventoryEntry> {
+    let mut inventory: Vec<_> = inventory.values().cloned().collect();
+    inventory.sort_by_key(|entry| entry.id.0);
+    inventory
+}
+
+/// Convert the signal LIFX reports in milliwatts to dBm.
+///
+/// Returns `None` if there is no signal to convert.
+fn signal_to_dbm(signal: f32) -> Option<f32> {
+    if signal > 0.0 {
+        Some((10.0 * signal.log10()).round())
+    } else {
+        None
+    }
+}
+
+/// How often to refresh details that rarely change, like WiFi signal and when devices were last seen.
+const INFO_REFRESH_TIME: Duration = Duration::from_secs(60);
+
+/// The results of discovering LIFX devices.
+pub struct Discovery {
+    /// Each device as it replies to discovery.
+    pub devices: stateless::Receiver<Device>,
+
+    /// Everything known about the devices seen so far.
+    pub inventory: stateful::Receiver<Vec<InventoryEntry>>,
+}
+
+/// What to do when a light was changed by something else, like the LIFX app.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Reconcile {
+    /// Change the light back to the desired state.
+    #[default]
+    Reassert,
+
+    /// Keep the change as a manual override, until the desired state changes.
+    Adopt,
+}
+
+/// An error discovering LIFX devices
+#[derive(Debug, Error)]
+pub enum DiscoverError {
+    /// A UDP socket error occurred.
+    #[error("udp error: {0}")]
+    Udp(#[from] tokio::io::Error),
+}
+
+/// Discover LIFX devices on the network
+///
+/// Static devices are probed directly as well as by the broadcast.
+///
+/// # Errors
+///
+/// Returns an error if the UDP socket cannot be created.
+pub async fn discover(config: DiscoverConfig) -> Result<Discovery, DiscoverError> {
+    let (tx, rx) = stateless::create_pipe("lifx");
+    let (tx_inventory, rx_inventory) = stateful::create_pipe("lifx_inventory");
+
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.set_broadcast(true)?;
+
+    spawn(async move {
+        let mut interval = interval(config.poll_time);
+        let mut refresh = interval_at(Instant::now() + INFO_REFRESH_TIME, INFO_REFRESH_TIME);
+        let mut wifi_checked: HashMap<LifxId, Instant> = HashMap::new();
+        let mut buf = [0; 1024];
+        let mut inventory: HashMap<LifxId, InventoryEntry> = config
+            .static_devices
+            .iter()
+            .map(|device| {
+                let entry = InventoryEntry::new(device.id, Some(device.addr), true);
+                (device.id, entry)
+            })
+            .collect();
+        tx_inventory.try_send(get_inventory(&inventory));
+
+        loop {
+            select! {
+                _ = interval.tick() => {
+                    debug!("Sending GetService");
+                    let msg = Message::GetService;
+                    send_broadcast(&socket, msg, &config.broadcast).await.unwrap_or_else(|e| {
+                        error!("Error sending GetService: {e:?}");
+                    });
+
+                    for device in &config.static_devices {
+                        debug!("Sending GetService to {} at {}", device.id, device.addr);
+                        let msg = Message::GetService;
+                        send_request(&socket, device.id, device.addr, msg).await.unwrap_or_else(|e| {
+                            error!("Error sending GetService to {}: {e:?}", device.id);
+                        });
+                    }
+                }
+
+                _ = refresh.tick() => {
+                    tx_inventory.try_send(get_inventory(&inventory));
+                }
+
+                Ok((len, addr)) = socket.recv_from(&mut buf) => {
+                    debug!("Discover received {len} bytes from {addr}");
+                    let msg = RawMessage::unpack(&buf[..len])
+                        .and_then(|raw| {
+                            Message::from_raw(&raw).map(|msg| (LifxId(raw.frame_addr.target), msg))
+                        });
+                    match msg {
+                        Ok((target, msg)) => {
+                            let entry = inventory
+                                .entry(target)
+                                .or_insert_with(|| InventoryEntry::new(target, None, false));
+                            let before = entry.clone();
+                            entry.addr = Some(addr);
+                            entry.last_seen = Some(Utc::now());
+
+                            match msg {
+                                Message::StateService { .. } => {
+                                    if entry.firmware.is_none() {
+                                        let msg = Message::GetHostFirmware;
+                                        send_request(&socket, target, addr, msg).await.unwrap_or_else(|e| {
+                                            error!("Error sending GetHostFirmware to {target}: {e:?}");
+                                        });
+                                    }
+                                    let wifi_due = wifi_checked
+                                        .get(&target)
+                                        .is_none_or(|checked| checked.elapsed() >= INFO_REFRESH_TIME);
+                                    if wifi_due {
+                                        wifi_checked.insert(target, Instant::now());
+                                        let msg = Message::GetWifiInfo;
+                                        send_request(&socket, target, addr, msg).await.unwrap_or_else(|e| {
+                                            error!("Error sending GetWifiInfo to {target}: {e:?}");
+                                        });
+                                    }
+
+                                    let device_timeout = config.device_timeout;
+                                    let api_timeout = config.api_timeout;
+                                    let num_retries = config.num_retries;
+                                    let state_poll_time = config.state_poll_time;
+                                    let reconcile = config.reconcile;
+                                    let device = Device {
+                                        target,
+                                        addr,
+                                        device_timeout,
+                                        api_timeout,
+                                        num_retries,
+                                        state_poll_time,
+                                        reconcile,
+                                    };
+                                    tx.try_send(device);
+                                }
+                                Message::StateHostFirmware { version_major, version_minor, .. } => {
+                                    entry.firmware = Some(format!("{version_major}.{version_minor}"));
+                                }
+                                Message::StateWifiInfo { signal, .. } => {
+                                    entry.signal = signal_to_dbm(signal);
+                                }
+                                msg => {
+                                    debug!("Discover ignoring message from {target}: {msg:?}");
+                                }
+                            }
+
+                            // Every reply changes when the device was last seen, so only
+                            // publish that on its own when refreshing.
+                            let changed = before.last_seen.is_none()
+                                || *entry != InventoryEntry { last_seen: entry.last_seen, ..before };
+                            if changed {
+                                tx_inventory.try_send(get_inventory(&inventory));
+                            }
+                        }
+
+                        Err(e) => {
+                            error!("Error unpacking message: {e:?}");
+                        }
+                    }
+                }
+            }
+        }
+    });
+
+    Ok(Discovery {
+        devices: rx,
+        inventory: rx_inventory,
+    })
+}
+
+fn hsbk_to_lifx(hsbk: HSBK) -> lifx_core::HSBK {
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    lifx_core::HSBK {
+        hue: (hsbk.hue * 65535.0 / 360.0) as u16,
+        saturation: (hsbk.saturation * 65535.0 / 100.0) as u16,
+        brightness: (hsbk.brightness * 65535.0 / 100.0) as u16,
+        kelvin: hsbk.kelvin,
+    }
+}
+
+fn lifx_to_hsbk(hsbk: lifx_core::HSBK) -> HSBK {
+    HSBK {
+        hue: f32::from(hsbk.hue) * 360.0 / 65535.0,
+        saturation: f32::from(hsbk.saturation) * 100.0 / 65535.0,
+        brightness: f32::from(hsbk.brightness) * 100.0 / 65535.0,
+        kelvin: hsbk.kelvin,
+    }
+}
+
+/// Is the reported color close enough to the desired color?
+///
+/// The light rounds the values we send, so they never come back exactly the same.
+fn hsbk_matches(desired: &HSBK, reported: &HSBK) -> bool {
+    let hue_diff = (desired.hue - reported.hue).rem_euclid(360.0);
+    let hue_diff = hue_diff.min(360.0 - hue_diff);
+    let no_hue = desired.saturation < 1.0 && reported.saturation < 1.0;
+
+    (no_hue || hue_diff <= 2.0)
+        && (desired.saturation - reported.saturation).abs() <= 1.0
+        && (desired.brightness - reported.brightness).abs() <= 1.0
+        && desired.kelvin.abs_diff(reported.kelvin) <= 50
+}
+
+/// Is the reported state of the light close enough to the desired state?
+fn power_color_matches(desired: &PowerColor, reported: &PowerColor) -> bool {
+    match (desired, reported) {
+        (PowerColor::Off, PowerColor::Off) => true,
+        (PowerColor::On(desired), PowerColor::On(reported)) => {
+            let desired = match desired {
+                Colors::Single(color) => std::slice::from_ref(color),
+                Colors::Sequence(colors) => colors.as_slice(),
+            };
+            let reported = match reported {
+                Colors::Single(color) => std::slice::from_ref(color),
+                Colors::Sequence(colors) => colors.as_slice(),
+            };
+            match (desired, reported) {
+                ([], _) | (_, []) => true,
+                // A single color is sent to every zone.
+                ([desired], reported) => reported.iter().all(|r| hsbk_matches(desired, r)),
+                // Zones past the end of either list were not set by us.
+                (desired, reported) => desired
+                    .iter()
+                    .zip(reported)
+                    .all(|(d, r)| hsbk_matches(d, r)),
+            }
+        }
+        _ => false,
+    }
+}
+
+/// A LIFX device
+#[allow(clippy::struct_field_names)]
+#[derive(Clone, Debug)]
+pub struct Device {
+    target: LifxId,
+    addr: SocketAddr,
+    device_timeout: Duration,
+    api_timeout: Duration,
+    num_retries: u8,
+    state_poll_time: Option<Duration>,
+    reconcile: Reconcile,
+}
+
+#[derive(Debug)]
+enum DeviceState {
+    Online(Device, Instant, u8),
+    Offline,
+}
+
+/// An error talking to a LIFX device
+#[derive(Debug, Error)]
+pub enum LifxError {
+    /// The device is offline.
+    #[error("timed out")]
+    Timeout,
+
+    /// A LIFX error occurred.
+    #[error("lifx error: {0}")]
+    Lifx(#[from] lifx_core::Error),
+
+    /// A UDP socket error occurred.
+    #[error("udp error: {0}")]
+    Udp(#[from] tokio::io::Error),
+
+    /// A bad response was received.
+    #[error("bad response")]
+    BadResponse,
+
+    /// The device is offline.
+    #[error("Device is offline")]
+    DeviceOffline,
+}
+
+impl DeviceState {
+    fn set_online(&mut self, device: Device) {
+        let seq = match self {
+            DeviceState::Online(_, _, seq) => *seq,
+            DeviceState::Offline => 0,
+        };
+        let timeout_time = device.device_timeout;
+        *self = DeviceState::Online(device, Instant::now() + timeout_time, seq);
+    }
+
+    fn renew_online(&mut self) {
+        if let DeviceState::Online(device, expires, _) = self {
+            *expires = Instant::now() + device.device_timeout;
+        }
+    }
+
+    const fn set_offline(&mut self) {
+        *self = DeviceState::Offline;
+    }
+
+    fn next_poll(&self) -> Option<Instant> {
+        match self {
+            DeviceState::Online(device, _, _) => device
+                .state_poll_time
+                .map(|poll_time| Instant::now() + poll_time),
+            DeviceState::Offline => None,
+        }
+    }
+
+    fn reconcile(&self) -> Reconcile {
+        match self {
+            DeviceState::Online(device, _, _) => device.reconcile,
+            DeviceState::Offline => Reconcile::default(),
+        }
+    }
+
+    async fn get_power_color(&mut self, config: &DeviceConfig) -> Result<PowerColor, LifxError> {
+        if let DeviceState::Online(device, _, seq) = self {
+            let socket = UdpSocket::bind("0.0.0.0:0").await?;
+
+            let msg = send_and_wait_response(&socket, device, seq, Message::LightGet).await?;
+            let Message::LightState { color, power, .. } = msg else {
+                return Err(LifxError::BadResponse);
+            };
+
+            if power == 0 {
+                return Ok(PowerColor::Off);
+            }
+
+            if !config.multiple_zones {
+                return Ok(PowerColor::On(Colors::Single(lifx_to_hsbk(color))));
+            }
+
+            let msg = send_and_wait_response(&socket, device, seq, Message::GetExtendedColorZones)
+                .await?;
+            let Message::StateExtendedColorZones {
+                colors,
+                colors_count,
+                ..
+            } = msg
+            else {
+                return Err(LifxError::BadResponse);
+            };
+
+            let colors = colors
+                .iter()
+                .take(usize::from(colors_count))
+                .map(|color| lifx_to_hsbk(*color))
+                .collect();
+            Ok(PowerColor::On(Colors::Sequence(colors)))
+        } else {
+            Err(LifxError::DeviceOffline)
+        }
+    }
+
+    /// Set the light, then read back what it is actually showing.
+    async fn set_and_get_power_color(
+        &mut self,
+        power_color: &PowerColor,
+        duration: u32,
+        config: &DeviceConfig,
+    ) -> Result<PowerColor, LifxError> {
+        self.set_power_color(power_color, duration, config).await?;
+        if config.dry_run {
+            // Nothing was sent, so there is nothing to read back.
+            return Ok(power_color.clone());
+        }
+        self.get_power_color(config).await
+    }
+
+    async fn set_power_color(
+        &mut self,
+        power_color: &PowerColor,
+        duration: u32,
+        config: &DeviceConfig,
+    ) -> Result<(), LifxError> {
+        if config.dry_run {
+            debug!("Not setting power color in dry run mode");
+            return Ok(());
+        }
+
+        if let DeviceState::Online(device, _, seq) = self {
+            let socket = UdpSocket::bind("0.0.0.0:0").await?;
+            // let power = get_power(&socket, device, seq).await?;
+            // if power == PowerLevel::Standby && power_color.power == PowerLevel::Enabled {
+            let off_color = HSBK {
+                hue: 0.0,
+                saturation: 0.0,
+                brightness: 0.0,
+                kelvin: 0,
+            };
+            //     send_set_color(&socket, device, seq, off_color).await?;
+            // }
+
+            match power_color {
+                PowerColor::Off => {
+                    send_set_power(&socket, device, seq, PowerLevel::Off, duration).await?;
+                    send_set_color(&socket, device, seq, off_color, duration).await?;
+                }
+                PowerColor::On(color) => {
+                    send_set_power(&socket, device, seq, PowerLevel::On, 0).await?;
+                    send_set_colors(color, socket, device, seq, duration, config).await?;
+                }
+            }
+            Ok(())
+        } else {
+            Err(LifxError::DeviceOffline)
+        }
+    }
+}
+
+async fn send_set_colors(
+    color: &Colors,
+    socket: UdpSocket,
+    device: &Device,
+    seq: &mut u8,
+    duration: u32,
+    config: &DeviceConfig,
+) -> Result<(), LifxError> {
+    match (config.multiple_zones, color) {
+        (true, Colors::Sequence(colors)) => {
+            send_set_extended_color_zones(&socket, device, seq, colors, duration).await?;
+        }
+        (false, Colors::Sequence(colors)) => {
+            let first_color = colors.first().unwrap_or_else(|| {
+                error!("No colors in sequence");
+                &HSBK {
+                    hue: 0.0,
+                    saturation: 0.0,
+                    brightness: 0.0,
+                    kelvin: 0,
+                }
+            });
+            send_set_color(&socket, device, seq, *first_color, duration).await?;
+        }
+        (_, Colors::Single(color)) => {
+            send_set_color(&socket, device, seq, *color, duration).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn send_set_power(
+    socket: &UdpSocket,
+    device: &Device,
+    sequence: &mut u8,
+    power: PowerLevel,
+    duration: u32,
+) -> Result<(), LifxError> {
+    // Unlike `SetPower`, `LightSetPower` takes a transition duration.
+    let level = match power {
+        PowerLevel::On => u16::MAX,
+        PowerLevel::Off => 0,
+    };
+    let msg = Message::LightSetPower { level, duration };
+    send_and_wait_ack(socket, device, sequence, msg).await?;
+    Ok(())
+}
+
+async fn send_set_color(
+    socket: &UdpSocket,
+    device: &Device,
+    sequence: &mut u8,
+    color: HSBK,
+    duration: u32,
+) -> Result<(), LifxError> {
+    let msg = Message::LightSetColor {
+        reserved: 0,
+        color: hsbk_to_lifx(color),
+        duration,
+    };
+    send_and_wait_ack(socket, device, sequence, msg).await?;
+    Ok(())
+}
+
+async fn send_set_extended_color_zones(
+    socket: &UdpSocket,
+    device: &Device,
+    sequence: &mut u8,
+    colors: &[HSBK],
+    duration: u32,
+) -> Result<(), LifxError> {
+    let len = if colors.len() > 82 { 82 } else { colors.len() };
+
+    let c = lifx_core::HSBK {
+        hue: 0,
+        saturation: 0,
+        brightness: 0,
+        kelvin: 0,
+    };
+    let mut x_colors: Box<[lifx_core::HSBK; 82]> = Box::new([c; 82]);
+
+    for (src, dst) in colors.iter().take(len).zip(x_colors.iter_mut()) {
+        *dst = hsbk_to_lifx(*src);
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    let msg = Message::SetExtendedColorZones {
+        colors: x_colors,
+        duration,
+        apply: lifx_core::ApplicationRequest::Apply,
+        zone_index: 0,
+        colors_count: len as u8,
+    };
+    send_and_wait_ack(socket, device, sequence, msg).await?;
+    Ok(())
+}
+
+async fn send_broadcast(
+    socket: &UdpSocket,
+    msg: Message,
+    broadcast: &str,
+) -> Result<(), LifxError> {
+    let source: u32 = 0x1234_5678;
+    let opts = BuildOptions {
+        source,
+        target: None,
+        ack_required: false,
+        res_required: false,
+        sequence: 0,
+    };
+    let raw = RawMessage::build(&opts, msg)?;
+    let raw = raw.pack()?;
+    socket.send_to(&raw, &broadcast).await?;
+    Ok(())
+}
+
+async fn send_request(
+    socket: &UdpSocket,
+    target: LifxId,
+    addr: SocketAddr,
+    msg: Message,
+) -> Result<(), LifxError> {
+    let source: u32 = 0x1234_5678;
+    let opts = BuildOptions {
+        source,
+        target: Some(target.0),
+        ack_required: false,
+        res_required: true,
+        sequence: 0,
+    };
+    let raw = RawMessage::build(&opts, msg)?;
+    let raw = raw.pack()?;
+    socket.send_to(&raw, &addr).await?;
+    Ok(())
+}
+
+#[allow(dead_code)]
+async fn send_only(
+    socket: &UdpSocket,
+    device: &Device,
+    sequence: &mut u8,
+    msg: Message,
+) -> Result<(), LifxError> {
+    let source: u32 = 0x1234_5678;
+    let opts = BuildOptions {
+        source,
+        target: Some(device.target.0),
+        ack_required: false,
+        res_required: false,
+        sequence: *sequence,
+    };
+    let raw = RawMessage::build(&opts, msg)?;
+    let raw = raw.pack()?;
+    socket.send_to(&raw, &device.addr).await?;
+    *sequence = sequence.wrapping_add(1);
+    Ok(())
+}
+
+async fn send_and_wait_response(
+    socket: &UdpSocket,
+    device: &Device,
+    sequence: &mut u8,
+    msg: Message,
+) -> Result<Message, LifxError> {
+    let mut retries = device.num_retries;
+
+    loop {
+        let source: u32 = 0x1234_5678;
+        let this_sequence = *sequence;
+        *sequence = sequence.wrapping_add(1);
+
+        let opts = BuildOptions {
+            source,
+            target: Some(device.target.0),
+            ack_required: false,
+            res_required: true,
+            sequence: this_sequence,
+        };
+        let raw = RawMessage::build(&opts, msg.clone())?;
+        let raw = raw.pack()?;
+        socket.send_to(&raw, &device.addr).await?;
+        if let Ok(msg) = wait_for_response(socket, this_sequence, device.api_timeout).await {
+            break Ok(msg);
+        }
+
+        retries = retries.saturating_sub(1);
+        if retries == 0 {
+            break Err(LifxError::Timeout);
+        }
+    }
+}
+
+async fn send_and_wait_ack(
+    socket: &UdpSocket,
+    device: &Device,
+    sequence: &mut u8,
+    msg: Message,
+) -> Result<(), LifxError> {
+    let mut retries = device.num_retries;
+
+    loop {
+        let source: u32 = 0x1234_5678;
+        let this_sequence = *sequence;
+        *sequence = sequence.wrapping_add(1);
+
+        let opts = BuildOptions {
+            source,
+            target: Some(device.target.0),
+            ack_required: true,
+            res_required: false,
+            sequence: this_sequence,
+        };
+        let raw = RawMessage::build(&opts, msg.clone())?;
+        let raw = raw.pack()?;
+        socket.send_to(&raw, &device.addr).await?;
+        if (wait_for_response(socket, this_sequence, device.api_timeout).await).is_ok() {
+            return Ok(());
+        }
+
+        retries = retries.saturating_sub(1);
+        if retries == 0 {
+            break Err(LifxError::Timeout);
+        }
+    }
+}
+
+async fn wait_for_response(
+    socket: &UdpSocket,
+    sequence: u8,
+    timeout: Duration,
+) -> Result<Message, LifxError> {
+    let mut buf = [0; 1024];
+    loop {
+        let timeout = Instant::now() + timeout;
+        select! {
+            () = sleep_until(timeout) => {
+                return Err(LifxError::Timeout);
+            }
+            Ok((len, _)) = socket.recv_from(&mut buf) => {
+                let raw = RawMessage::unpack(&buf[..len])?;
+                if raw.frame_addr.sequence == sequence {
+                    return Ok(Message::from_raw(&raw)?);
+                }
+            }
+        }
+    }
+}
+
+/// Configuration for a device.
+#[derive(Default)]
+pub struct DeviceConfig {
+    /// Does this device have multiple zones?
+    pub multiple_zones: bool,
+
+    /// The address of the device, instead of the address it was discovered at.
+    pub addr: Option<SocketAddr>,
+
+    /// Pretend to change the device without sending anything to it.
+    pub dry_run: bool,
+}
+
+impl DeviceConfig {
+    /// Set whether this device has multiple zones.
+    #[must_use]
+    pub const fn set_multiple_zones(mut self, multiple_zones: bool) -> DeviceConfig {
+        self.multiple_zones = multiple_zones;
+        self
+    }
+
+    /// Set the static address of this device.
+    #[must_use]
+    pub const fn set_addr(mut self, addr: Option<SocketAddr>) -> DeviceConfig {
+        self.addr = addr;
+        self
+    }
+
+    /// Set whether to only pretend to change the device.
+    #[must_use]
+    pub const fn set_dry_run(mut self, dry_run: bool) -> DeviceConfig {
+        self.dry_run = dry_run;
+        self
+    }
+}
+
+/// Run the device.
+#[must_use]
+pub fn device_entity(
+    rx_pc: stateful::Receiver<PowerColorTransition>,
+    id: LifxId,
+    discover: &stateless::Receiver<Device>,
+    config: DeviceConfig,
+) -> stateful::Receiver<State> {
+    let (tx_state, rx_state) = stateful::create_pipe("lifx_state");
+    let discover = discover.clone().filter(move |d| d.target == id);
+
+    spawn(async move {
+        let mut discover_s = discover.subscribe().await;
+        let mut rx_s = rx_pc.subscribe().await;
+        let mut state = DeviceState::Offline;
+        let mut power_color = PowerColor::Off;
+        let mut fade_end = Instant::now();
+        let mut next_poll: Option<Instant> = None;
+        tx_state.try_send(State::Offline);
+
+        loop {
+            select! {
+                Ok(mut d) = discover_s.recv() => {
+                    if let Some(addr) = config.addr {
+                        d.addr = addr;
+                    }
+                    let was_online = matches!(state, DeviceState::Online(..));
+                    state.set_online(d);
+                    if was_online {
+                        // Rediscovery is frequent, so only check the light if a poll is due anyway.
+                        let now = Instant::now();
+                        let poll_due = next_poll.is_some_and(|next_poll| now >= next_poll);
+                        if poll_due && now >= fade_end {
+                            next_poll = state.next_poll();
+                            check_power_color(id, &mut state, &mut power_color, &config, &tx_state).await;
+                        }
+                    } else {
+                        let duration = remaining_millis(fade_end);
+                        match state.set_and_get_power_color(&power_color, duration, &config).await {
+                            Ok(reported) => {
+                                state.renew_online();
+                                debug!("{id} discovered and initializing: {power_color:?}");
+                                tx_state.try_send(State::Online(reported));
+                            }
+                            Err(err) => {
+                                state.set_offline();
+                                info!("{id} failed initialize: {err:?}");
+                                tx_state.try_send(State::Offline);
+                            }
+                        }
+                    }
+                    // Rediscovery is frequent, so keep any pending poll.
+                    next_poll = next_poll.or_else(|| state.next_poll());
+                }
+                Ok(PowerColorTransition { pc, duration }) = rx_s.recv() => {
+                    power_color = pc;
+                    fade_end = Instant::now() + duration;
+                    let duration = remaining_millis(fade_end);
+                    match state.set_and_get_power_color(&power_color, duration, &config).await {
+                        Ok(reported) => {
+                            state.renew_online();
+                            debug!("{id} set power color: {power_color:?}");
+                            tx_state.try_send(State::Online(reported));
+                            if fade_end > Instant::now() {
+                                // Report the final state once the fade has finished.
+                                next_poll = Some(fade_end);
+                            }
+                        }
+                        Err(err) => {
+                            state.set_offline();
+                            info!("{id} failed to set power color: {err:?}");
+                            tx_state.try_send(State::Offline);
+                        }
+                    }
+                }
+                Some(()) = maybe_sleep_until(&state) => {
+                    // Don't cut short a fade that is still in progress.
+                    let duration = remaining_millis(fade_end);
+                    match state.set_and_get_power_color(&power_color, duration, &config).await {
+                        Ok(reported) => {
+                            state.renew_online();
+                            debug!("{id} timeout check: {power_color:?}");
+                            tx_state.try_send(State::Online(reported));
+                        }
+                        Err(err) => {
+                            state.set_offline();
+                            info!("{id} failed to timeout check: {err:?}");
+                            tx_state.try_send(State::Offline);
+                        }
+                    }
+                }
+                Some(()) = maybe_sleep_until_poll(next_poll) => {
+                    let online = matches!(state, DeviceState::Online(..));
+                    if !online || config.dry_run {
+                        next_poll = state.next_poll();
+                    } else if Instant::now() < fade_end {
+                        // The light won't match until the fade is finished.
+                        next_poll = Some(fade_end);
+                    } else {
+                        next_poll = state.next_poll();
+                        check_power_color(id, &mut state, &mut power_color, &config, &tx_state).await;
+                    }
+                }
+            }
+        }
+    });
+
+    rx_state
+}
+
+/// Compare the light with the desired state, and reconcile any difference.
+async fn check_power_color(
+    id: LifxId,
+    state: &mut DeviceState,
+    power_color: &mut PowerColor,
+    config: &DeviceConfig,
+    tx_state: &stateful::Sender<State>,
+) {
+    if config.dry_run {
+        tx_state.try_send(State::Online(power_color.clone()));
+        return;
+    }
+
+    match state.get_power_color(config).await {
+        Ok(reported) if power_color_matches(power_color, &reported) => {
+            state.renew_online();
+            tx_state.try_send(State::Online(reported));
+        }
+        Ok(reported) => {
+            state.renew_online();
+            match state.reconcile() {
+                Reconcile::Reassert => {
+                    info!("{id} changed to {reported:?}, restoring {power_color:?}");
+                    tx_state.try_send(State::Online(reported));
+                    match state.set_and_get_power_color(power_color, 0, config).await {
+                        Ok(reported) => {
+                            tx_state.try_send(State::Online(reported));
+                        }
+                        Err(err) => {
+                            state.set_offline();
+                            info!("{id} failed to restore power color: {err:?}");
+                            tx_state.try_send(State::Offline);
+                        }
+                    }
+                }
+                Reconcile::Adopt => {
+                    info!("{id} changed to {reported:?}, keeping as override");
+                    *power_color = reported.clone();
+                    tx_state.try_send(State::Online(reported));
+                }
+            }
+        }
+        Err(err) => {
+            state.set_offline();
+            info!("{id} failed to poll state: {err:?}");
+            tx_state.try_send(State::Offline);
+        }
+    }
+}
+
+/// Get the time left until a fade ends, in the milliseconds LIFX expects.
+fn remaining_millis(fade_end: Instant) -> u32 {
+    let remaining = fade_end.saturating_duration_since(Instant::now());
+    u32::try_from(remaining.as_millis()).unwrap_or(u32::MAX)
+}
+
+async fn maybe_sleep_until(state: &DeviceState) -> Option<()> {
+    if let DeviceState::Online(_, timeout, _) = state {
+        sleep_until(*timeout).await;
+        Some(())
+    } else {
+        None
+    }
+}
+
+async fn maybe_sleep_until_poll(next_poll: Option<Instant>) -> Option<()> {
+    if let Some(next_poll) = next_poll {
+        sleep_until(next_poll).await;
+        Some(())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use robotica_common::datetime::duration;
+    use tokio::time::timeout;
+
+    use super::simulator::Simulator;
+    use super::*;
+
+    #[test]
+    fn test_serialize_lifx_id() {
+        let id = LifxId::new(0x1234_5678_9abc_def0);
+        let serialized = serde_json::to_string(&id).unwrap();
+        assert_eq!(serialized, "1311768467463790320");
+    }
+
+    #[test]
+    fn test_deserialize_lifx_id() {
+        let serialized = "1311768467463790320";
+        let id: LifxId = serde_json::from_str(serialized).unwrap();
+        assert_eq!(id, LifxId::new(0x1234_5678_9abc_def0));
+    }
+
+    #[test]
+    fn test_signal_to_dbm() {
+        assert!(signal_to_dbm(1.0).unwrap().abs() < f32::EPSILON);
+        assert!((signal_to_dbm(0.000_01).unwrap() + 50.0).abs() < f32::EPSILON);
+        assert!((signal_to_dbm(0.000_000_2).unwrap() + 67.0).abs() < f32::EPSILON);
+        assert_eq!(signal_to_dbm(0.0), None);
+        assert_eq!(signal_to_dbm(-1.0), None);
+    }
+
+    #[test]
+    fn test_get_inventory() {
+        let addr: SocketAddr = "10.0.0.5:56700".parse().unwrap();
+        let inventory = HashMap::from([
+            (LifxId(2), InventoryEntry::new(LifxId(2), None, false)),
+            (LifxId(1), InventoryEntry::new(LifxId(1), Some(addr), true)),
+        ]);
+        let inventory = get_inventory(&inventory);
+        assert_eq!(inventory.len(), 2);
+        assert_eq!(inventory[0].id, LifxId(1));
+        assert_eq!(inventory[0].addr, Some(addr));
+        assert!(inventory[0].is_static);
+        assert_eq!(inventory[1].id, LifxId(2));
+        assert_eq!(inventory[1].last_seen, None);
+    }
+
+    fn sim_device(sim: &Simulator) -> Device {
+        Device {
+            target: sim.id(),
+            addr: sim.addr(),
+            device_timeout: duration::seconds(45),
+            api_timeout: Duration::from_millis(50),
+            num_retries: 3,
+            state_poll_time: None,
+            reconcile: Reconcile::Reassert,
+        }
+    }
+
+    /// Keep announcing a device, like discovery does.
+    fn fake_discover(device: Device) -> stateless::Receiver<Device> {
+        let (tx, rx) = stateless::create_pipe("fake_discover");
+        spawn(async move {
+            let mut interval = interval(duration::seconds(1));
+            loop {
+                interval.tick().await;
+                tx.try_send(device.clone());
+            }
+        });
+        rx
+    }
+
+    async fn wait_for<T: Clone + PartialEq + Send + 'static>(
+        rx: &stateful::Receiver<T>,
+        check: impl Fn(&T) -> bool,
+    ) -> T {
+        let mut rx_s = rx.subscribe().await;
+        let wait = async {
+            loop {
+                let value = rx_s.recv().await.unwrap();
+                if check(&value) {
+                    return value;
+                }
+            }
+        };
+        timeout(duration::seconds(5), wait).await.unwrap()
+    }
+
+    fn count(messages: &[Message], check: impl Fn(&Message) -> bool) -> usize {
+        messages.iter().filter(|msg| check(msg)).count()
+    }
+
+    /// Wait until the device has answered more than `n` messages that pass the check.
+    async fn wait_for_more(sim: &Simulator, n: usize, check: impl Fn(&Message) -> bool) {
+        let wait = sim.wait_for_received(|messages| count(messages, &check) > n);
+        timeout(duration::seconds(5), wait).await.unwrap();
+    }
+
+    async fn wait_for_online(rx: &stateful::Receiver<State>, desired: &PowerColor) {
+        wait_for(rx, |state| match state {
+            State::Online(pc) => power_color_matches(desired, pc),
+            State::Offline => false,
+        })
+        .await;
+    }
+
+    fn start_device(
+        sim: &Simulator,
+        device: Device,
+        config: DeviceConfig,
+        pc: &PowerColor,
+    ) -> (
+        stateful::Sender<PowerColorTransition>,
+        stateful::Receiver<State>,
+    ) {
+        let (tx_pc, rx_pc) = stateful::create_pipe("lifx_pc");
+        tx_pc.try_send(PowerColorTransition::immediate(pc.clone()));
+        let rx_state = device_entity(rx_pc, sim.id(), &fake_discover(device), config);
+        (tx_pc, rx_state)
+    }
+
+    fn red() -> PowerColor {
+        PowerColor::On(Colors::Single(color(0.0, 100.0, 50.0)))
+    }
+
+    fn blue() -> PowerColor {
+        PowerColor::On(Colors::Single(color(240.0, 100.0, 50.0)))
+    }
+
+    #[tokio::test]
+    async fn test_discover_broadcast() {
+        let sim = Simulator::start(LifxId::new(0x1234), 1).await;
+        let config = DiscoverConfig {
+            broadcast: sim.addr().to_string(),
+            poll_time: Duration::from_millis(100),
+            device_timeout: duration::seconds(45),
+            api_timeout: Duration::from_millis(50),
+            num_retries: 3,
+            state_poll_time: None,
+            reconcile: Reconcile::Reassert,
+            static_devices: vec![],
+        };
+        let discovery = discover(config).await.unwrap();
+
+        let mut devices = discovery.devices.subscribe().await;
+        let device = timeout(duration::seconds(5), devices.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(device.target, sim.id());
+        assert_eq!(device.addr, sim.addr());
+    }
+
+    #[tokio::test]
+    async fn test_discover_static() {
+        let sim = Simulator::start(LifxId::new(0x5678), 1).await;
+        let config = DiscoverConfig {
+            broadcast: "127.0.0.1:9".to_string(),
+            poll_time: Duration::from_millis(100),
+            device_timeout: duration::seconds(45),
+            api_timeout: Duration::from_millis(50),
+            num_retries: 3,
+            state_poll_time: None,
+            reconcile: Reconcile::Reassert,
+            static_devices: vec![StaticDevice {
+                id: sim.id(),
+                addr: sim.addr(),
+            }],
+        };
+        let discovery = discover(config).await.unwrap();
+
+        let inventory = wait_for(&discovery.inventory, |inventory| {
+            inventory.iter().any(|entry| entry.last_seen.is_some())
+        })
+        .await;
+        assert_eq!(inventory.len(), 1);
+        assert_eq!(inventory[0].id, sim.id());
+        assert_eq!(inventory[0].addr, Some(sim.addr()));
+        assert!(inventory[0].is_static);
+    }
+
+    #[tokio::test]
+    async fn test_discover_inventory_not_republished() {
+        let sim = Simulator::start(LifxId::new(0x9abc), 1).await;
+        let config = DiscoverConfig {
+            broadcast: sim.addr().to_string(),
+            poll_time: Duration::from_millis(100),
+            device_timeout: duration::seconds(45),
+            api_timeout: Duration::from_millis(50),
+            num_retries: 3,
+            state_poll_time: None,
+            reconcile: Reconcile::Reassert,
+            static_devices: vec![],
+        };
+        let discovery = discover(config).await.unwrap();
+        wait_for(&discovery.inventory, |inventory| {
+            inventory.iter().any(|entry| entry.last_seen.is_some())
+        })
+        .await;
+        let mut inventory_s = discovery.inventory.subscribe().await;
+        inventory_s.recv().await.unwrap();
+
+        // After the first reply, the rest only change when the device was last seen.
+        let is_discover = |msg: &Message| matches!(msg, Message::GetService);
+        let rounds = sim.count_received(is_discover);
+        wait_for_more(&sim, rounds + 5, is_discover).await;
+        assert!(matches!(inventory_s.try_recv(), Ok(None)));
+        assert_eq!(
+            sim.count_received(|msg| matches!(msg, Message::GetWifiInfo)),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_device_entity_set_power_color() {
+        let sim = Simulator::start(LifxId::new(1), 1).await;
+        let (tx_pc, rx_state) =
+            start_device(&sim, sim_device(&sim), DeviceConfig::default(), &red());
+
+        wait_for_online(&rx_state, &red()).await;
+        assert!(power_color_matches(&red(), &sim.power_color()));
+
+        tx_pc.try_send(PowerColorTransition::immediate(PowerColor::Off));
+        wait_for_online(&rx_state, &PowerColor::Off).await;
+        assert_eq!(sim.power_color(), PowerColor::Off);
+    }
+
+    #[tokio::test]
+    async fn test_device_entity_retries() {
+        let sim = Simulator::start(LifxId::new(2), 1).await;
+
+        // Fewer lost packets than retries still works.
+        sim.drop_packets(2);
+        let (tx_pc, rx_state) =
+            start_device(&sim, sim_device(&sim), DeviceConfig::default(), &red());
+        wait_for_online(&rx_state, &red()).await;
+        assert!(power_color_matches(&red(), &sim.power_color()));
+
+        // Running out of retries makes the light offline until it is discovered again.
+        sim.drop_packets(3);
+        tx_pc.try_send(PowerColorTransition::immediate(blue()));
+        wait_for(&rx_state, |state| *state == State::Offline).await;
+        wait_for_online(&rx_state, &blue()).await;
+        assert!(power_color_matches(&blue(), &sim.power_color()));
+    }
+
+    #[tokio::test]
+    async fn test_device_entity_offline() {
+        let sim = Simulator::start(LifxId::new(3), 1).await;
+        let (tx_pc, rx_state) =
+            start_device(&sim, sim_device(&sim), DeviceConfig::default(), &red());
+        wait_for_online(&rx_state, &red()).await;
+
+        sim.set_offline(true);
+        tx_pc.try_send(PowerColorTransition::immediate(blue()));
+        wait_for(&rx_state, |state| *state == State::Offline).await;
+        assert!(power_color_matches(&red(), &sim.power_color()));
+
+        sim.set_offline(false);
+        wait_for_online(&rx_state, &blue()).await;
+        assert!(power_color_matches(&blue(), &sim.power_color()));
+    }
+
+    #[tokio::test]
+    async fn test_device_entity_multiple_zones() {
+        let sim = Simulator::start(LifxId::new(4), 4).await;
+        let red = color(0.0, 100.0, 50.0);
+        let blue = color(240.0, 100.0, 50.0);
+        let pc = PowerColor::On(Colors::Sequence(vec![red, blue, red, blue]));
+        let config = DeviceConfig::default().set_multiple_zones(true);
+        let (tx_pc, rx_state) = start_device(&sim, sim_device(&sim), config, &pc);
+
+        wait_for_online(&rx_state, &pc).await;
+        assert!(power_color_matches(&pc, &sim.power_color()));
+        assert!(sim
+            .received()
+            .iter()
+            .any(|msg| matches!(msg, Message::SetExtendedColorZones { .. })));
+
+        // A single color is sent to every zone.
+        tx_pc.try_send(PowerColorTransition::immediate(PowerColor::On(
+            Colors::Single(blue),
+        )));
+        wait_for_online(&rx_state, &PowerColor::On(Colors::Single(blue))).await;
+        let PowerColor::On(Colors::Sequence(zones)) = sim.power_color() else {
+            panic!("expected zones");
+        };
+        assert_eq!(zones.len(), 4);
+        assert!(zones.iter().all(|zone| hsbk_matches(&blue, zone)));
+    }
+
+    #[tokio::test]
+    async fn test_device_entity_reassert() {
+        let sim = Simulator::start(LifxId::new(5), 1).await;
+        let device = Device {
+            state_poll_time: Some(Duration::from_millis(100)),
+            ..sim_device(&sim)
+        };
+        let (_tx_pc, rx_state) = start_device(&sim, device, DeviceConfig::default(), &red());
+        wait_for_online(&rx_state, &red()).await;
+
+        // The next poll notices the change and restores red.
+        let is_set = |msg: &Message| matches!(msg, Message::LightSetColor { .. });
+        let sets = sim.count_received(is_set);
+        sim.set_power_color(&blue());
+        wait_for_more(&sim, sets, is_set).await;
+        assert!(power_color_matches(&red(), &sim.power_color()));
+        wait_for_online(&rx_state, &red()).await;
+    }
+
+    #[tokio::test]
+    async fn test_device_entity_adopt() {
+        let sim = Simulator::start(LifxId::new(6), 1).await;
+        let device = Device {
+            state_poll_time: Some(Duration::from_millis(100)),
+            reconcile: Reconcile::Adopt,
+            ..sim_device(&sim)
+        };
+        let (tx_pc, rx_state) = start_device(&sim, device, DeviceConfig::default(), &red());
+        wait_for_online(&rx_state, &red()).await;
+
+        sim.set_power_color(&blue());
+        wait_for_online(&rx_state, &blue()).await;
+
+        // Later polls keep the override rather than restoring red.
+        let is_get = |msg: &Message| matches!(msg, Message::LightGet);
+        let polls = sim.count_received(is_get);
+        wait_for_more(&sim, polls + 3, is_get).await;
+        assert!(power_color_matches(&blue(), &sim.power_color()));
+
+        // Until the desired state changes.
+        tx_pc.try_send(PowerColorTransition::immediate(PowerColor::Off));
+        wait_for_online(&rx_state, &PowerColor::Off).await;
+        assert_eq!(sim.power_color(), PowerColor::Off);
+    }
+
+    #[tokio::test]
+    async fn test_device_entity_reports_device_state() {
+        let sim = Simulator::start(LifxId::new(7), 1).await;
+        let red = color(0.0, 100.0, 50.0);
+        let blue = color(240.0, 100.0, 50.0);
+        let pc = PowerColor::On(Colors::Sequence(vec![red, blue]));
+        let (_tx_pc, rx_state) = start_device(&sim, sim_device(&sim), DeviceConfig::default(), &pc);
+
+        // A single zone light only shows the first color, and that is what gets reported.
+        let state = wait_for(&rx_state, |state| matches!(state, State::Online(_))).await;
+        assert_eq!(state, State::Online(sim.power_color()));
+        assert!(power_color_matches(
+            &PowerColor::On(Colors::Single(red)),
+            &sim.power_color()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_device_entity_rediscovery_without_polling() {
+        let sim = Simulator::start(LifxId::new(8), 1).await;
+        let (_tx_pc, rx_state) =
+            start_device(&sim, sim_device(&sim), DeviceConfig::default(), &red());
+        wait_for_online(&rx_state, &red()).await;
+
+        // Without polling, rediscovery keeps the light online but doesn't check it.
+        let is_get = |msg: &Message| matches!(msg, Message::LightGet);
+        let gets = sim.count_received(is_get);
+        sim.set_power_color(&blue());
+        let checked = sim.wait_for_received(|messages| count(messages, is_get) > gets);
+        assert!(timeout(duration::seconds(3), checked).await.is_err());
+        assert!(power_color_matches(&blue(), &sim.power_color()));
+        assert!(matches!(rx_state.get().await, Some(State::Online(_))));
+    }
+
+    fn color(hue: f32, saturation: f32, brightness: f32) -> HSBK {
+        HSBK {
+            hue,
+            saturation,
+            brightness,
+            kelvin: 3500,
+        }
+    }
+
+    #[test]
+    fn test_lifx_hsbk_round_trip() {
+        let desired = color(123.4, 56.7, 89.0);
+        let reported = lifx_to_hsbk(hsbk_to_lifx(desired));
+        assert!(hsbk_matches(&desired, &reported));
+        assert_eq!(reported.kelvin, 3500);
+    }
+
+    #[test]
+    fn test_power_color_matches() {
+        let red = color(0.0, 100.0, 50.0);
+        let almost_red = color(359.5, 99.8, 50.4);
+        let blue = color(240.0, 100.0, 50.0);
+        let white = color(0.0, 0.0, 50.0);
+        let other_white = color(180.0, 0.0, 50.0);
+
+        let single = |c| PowerColor::On(Colors::Single(c));
+        let sequence = |c: Vec<HSBK>| PowerColor::On(Colors::Sequence(c));
+
+        assert!(power_color_matches(&PowerColor::Off, &PowerColor::Off));
+        assert!(!power_color_matches(&PowerColor::Off, &single(red)));
+        assert!(!power_color_matches(&single(red), &PowerColor::Off));
+        assert!(power_color_matches(&single(red), &single(almost_red)));
+        assert!(!power_color_matches(&single(red), &single(blue)));
+        assert!(power_color_matches(&single(white), &single(other_white)));
+
+        assert!(power_color_matches(
+            &single(red),
+            &sequence(vec![red, almost_red])
+        ));
+        assert!(!power_color_matches(
+            &single(red),
+            &sequence(vec![red, blue])
+        ));
+        assert!(power_color_matches(
+            &sequence(vec![red, blue]),
+            &sequence(vec![almost_red, blue, white])
+        ));
+        assert!(!power_color_matches(
+            &sequence(vec![red, blue]),
+            &sequence(vec![blue, red])
+        ));
+        assert!(power_color_matches(
+            &sequence(vec![red, blue]),
+            &single(red)
+        ));
+    }
+}
@@ -0,0 +1,294 @@
+//! A simulated LIFX device that speaks the LAN protocol, for tests
+#![allow(clippy::unwrap_used)]
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use lifx_core::{BuildOptions, LifxString, Message, RawMessage, Service};
+use robotica_common::robotica::lights::{Colors, PowerColor};
+use tokio::{net::UdpSocket, sync::watch, task::JoinHandle};
+use tracing::{debug, error};
+
+use super::{lifx_to_hsbk, LifxId};
+
+const BLACK: lifx_core::HSBK = lifx_core::HSBK {
+    hue: 0,
+    saturation: 0,
+    brightness: 0,
+    kelvin: 3500,
+};
+
+#[derive(Debug)]
+struct SimulatedState {
+    power: u16,
+    zones: Vec<lifx_core::HSBK>,
+    drop_packets: usize,
+    offline: bool,
+    received: Vec<Message>,
+}
+
+impl SimulatedState {
+    /// Update the state for a message, returning the reply if one was asked for.
+    fn handle(&mut self, msg: &Message) -> Option<Message> {
+        match msg {
+            Message::GetService => Some(Message::StateService {
+                service: Service::UDP,
+                port: 56700,
+            }),
+            Message::GetPower => Some(Message::StatePower { level: self.power }),
+            Message::LightGet => Some(self.light_state()),
+            Message::LightSetPower { level, .. } => {
+                self.power = *level;
+                Some(Message::StatePower { level: self.power })
+            }
+            Message::LightSetColor { color, .. } => {
+                self.zones.fill(*color);
+                Some(self.light_state())
+            }
+            Message::GetExtendedColorZones => Some(self.extended_color_zones()),
+            Message::SetExtendedColorZones {
+                colors,
+                colors_count,
+                zone_index,
+                ..
+            } => {
+                let start = usize::from(*zone_index);
+                let count = usize::from(*colors_count);
+                for (dst, src) in self
+                    .zones
+                    .iter_mut()
+                    .skip(start)
+                    .zip(colors.iter().take(count))
+                {
+                    *dst = *src;
+                }
+                Some(self.extended_color_zones())
+            }
+            _ => None,
+        }
+    }
+
+    fn light_state(&self) -> Message {
+        Message::LightState {
+            color: self.zones.first().copied().unwrap_or(BLACK),
+            reserved: 0,
+            power: self.power,
+            label: LifxString::new(c"Simulator"),
+            reserved2: 0,
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn extended_color_zones(&self) -> Message {
+        let mut colors = Box::new([BLACK; 82]);
+        for (dst, src) in colors.iter_mut().zip(&self.zones) {
+            *dst = *src;
+        }
+        Message::StateExtendedColorZones {
+            zones_count: self.zones.len() as u16,
+            zone_index: 0,
+            colors_count: self.zones.len().min(82) as u8,
+            colors,
+        }
+    }
+}
+
+/// A simulated LIFX device listening on localhost.
+pub struct Simulator {
+    id: LifxId,
+    addr: SocketAddr,
+    state: Arc<Mutex<SimulatedState>>,
+    received: watch::Receiver<usize>,
+    task: JoinHandle<()>,
+}
+
+impl Simulator {
+    /// Start a simulated device with the given number of zones.
+    pub async fn start(id: LifxId, zones: usize) -> Self {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let state = Arc::new(Mutex::new(SimulatedState {
+            power: 0,
+            zones: vec![BLACK; zones.max(1)],
+            drop_packets: 0,
+            offline: false,
+            received: Vec::new(),
+        }));
+
+        let (tx_received, received) = watch::channel(0);
+        let task = tokio::spawn(run(socket, id, state.clone(), tx_received));
+
+        Self {
+            id,
+            addr,
+            state,
+            received,
+            task,
+        }
+    }
+
+    /// The ID of the device.
+    pub const fn id(&self) -> LifxId {
+        self.id
+    }
+
+    /// The address the device is listening on.
+    pub const fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Ignore the next `count` packets, as if they were lost.
+    pub fn drop_packets(&self, count: usize) {
+        self.state.lock().unwrap().drop_packets = count;
+    }
+
+    /// Stop or start answering packets, as if the device lost power.
+    pub fn set_offline(&self, offline: bool) {
+        self.state.lock().unwrap().offline = offline;
+    }
+
+    /// Change the device, as if something else had changed it.
+    pub fn set_power_color(&self, power_color: &PowerColor) {
+        let mut state = self.state.lock().unwrap();
+        match power_color {
+            PowerColor::Off => state.power = 0,
+            PowerColor::On(Colors::Single(color)) => {
+                state.power = u16::MAX;
+                state.zones.fill(super::hsbk_to_lifx(*color));
+            }
+            PowerColor::On(Colors::Sequence(colors)) => {
+                state.power = u16::MAX;
+                for (dst, src) in state.zones.iter_mut().zip(colors) {
+                    *dst = super::hsbk_to_lifx(*src);
+                }
+            }
+        }
+    }
+
+    /// The current state of the device.
+    pub fn power_color(&self) -> PowerColor {
+        let state = self.state.lock().unwrap();
+        if state.power == 0 {
+            PowerColor::Off
+        } else if state.zones.len() == 1 {
+            PowerColor::On(Colors::Single(lifx_to_hsbk(state.zones[0])))
+        } else {
+            let colors = state.zones.iter().map(|c| lifx_to_hsbk(*c)).collect();
+            PowerColor::On(Colors::Sequence(colors))
+        }
+    }
+
+    /// The messages the device has answered so far.
+    pub fn received(&self) -> Vec<Message> {
+        self.state.lock().unwrap().received.clone()
+    }
+
+    /// Wait until the messages the device has answered pass the check.
+    pub async fn wait_for_received(&self, check: impl Fn(&[Message]) -> bool) {
+        let mut received = self.received.clone();
+        received
+            .wait_for(|_| check(&self.state.lock().unwrap().received))
+            .await
+            .unwrap();
+    }
+
+    /// Count the messages the device has answered that pass the check.
+    pub fn count_received(&self, check: impl Fn(&Message) -> bool) -> usize {
+        self.state
+            .lock()
+            .unwrap()
+            .received
+            .iter()
+            .filter(|msg| check(msg))
+            .count()
+    }
+}
+
+impl Drop for Simulator {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn run(
+    socket: UdpSocket,
+    id: LifxId,
+    state: Arc<Mutex<SimulatedState>>,
+    tx_received: watch::Sender<usize>,
+) {
+    let mut buf = [0; 1024];
+
+    loop {
+        let Ok((len, addr)) = socket.recv_from(&mut buf).await else {
+            continue;
+        };
+
+        let raw = match RawMessage::unpack(&buf[..len]) {
+            Ok(raw) => raw,
+            Err(err) => {
+                error!("Simulator could not unpack message: {err:?}");
+                continue;
+            }
+        };
+
+        if raw.frame_addr.target != 0 && raw.frame_addr.target != id.0 {
+            continue;
+        }
+
+        let msg = match Message::from_raw(&raw) {
+            Ok(msg) => msg,
+            Err(err) => {
+                error!("Simulator could not decode message: {err:?}");
+                continue;
+            }
+        };
+
+        let replies = {
+            let mut state = state.lock().unwrap();
+            if state.offline {
+                debug!("Simulator offline, ignoring {msg:?}");
+                continue;
+            }
+            if state.drop_packets > 0 {
+                debug!("Simulator dropping {msg:?}");
+                state.drop_packets -= 1;
+                continue;
+            }
+
+            let reply = state.handle(&msg);
+            state.received.push(msg.clone());
+
+            let sequence = raw.frame_addr.sequence;
+            let mut replies = Vec::new();
+            if raw.frame_addr.ack_required {
+                replies.push(Message::Acknowledgement { seq: sequence });
+            }
+            // Discovery is always answered, whatever flags were set.
+            let res_required = raw.frame_addr.res_required || matches!(msg, Message::GetService);
+            if let (true, Some(reply)) = (res_required, reply) {
+                replies.push(reply);
+            }
+            replies
+        };
+        // Outside the lock, as waiting for messages checks them with the watch locked.
+        tx_received.send_modify(|count| *count += 1);
+
+        for reply in replies {
+            let opts = BuildOptions {
+                source: raw.frame.source,
+                target: Some(id.0),
+                ack_required: false,
+                res_required: false,
+                sequence: raw.frame_addr.sequence,
+            };
+            let packet = RawMessage::build(&opts, reply).and_then(|raw| raw.pack());
+            match packet {
+                Ok(packet) => {
+                    socket.send_to(&packet, addr).await.unwrap();
+                }
+                Err(err) => error!("Simulator could not build reply: {err:?}"),
+            }
+        }
+    }
+}
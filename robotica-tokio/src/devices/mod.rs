@@ -1,6 +1,8 @@
 //! External devices
+pub mod fade;
 pub mod fake_switch;
 pub mod hdmi_matrix;
 pub mod lifx;
 pub mod occupancy;
 pub mod presence_tracker;
+pub mod zigbee2mqtt;
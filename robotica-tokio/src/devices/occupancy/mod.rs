@@ -1,10 +1,17 @@
 //! Module for handling occupancy or PIR sensors
 pub use robotica_common::robotica::occupancy::OccupiedState;
 
+use std::time::Duration;
+
 use robotica_common::mqtt::Json;
 use serde::Deserialize;
+use tokio::{
+    select,
+    time::{sleep_until, Instant},
+};
+use tracing::{debug, error};
 
-use crate::{pipes::stateful, services::mqtt::Subscriptions};
+use crate::{pipes::stateful, services::mqtt::Subscriptions, spawn};
 
 /// The type of sensor
 #[derive(Deserialize, Debug, Clone)]
@@ -92,3 +99,172 @@ pub fn subscribe(
         }
     }
 }
+
+/// How the occupancy sensors in a room are combined
+#[derive(Deserialize, Debug, Clone)]
+pub struct FusionConfig {
+    /// How long to stay occupied after the sensors say the room is vacant
+    #[serde(with = "robotica_common::datetime::with_duration", default)]
+    pub vacancy_hold: Duration,
+
+    /// The total weight of occupied sensors needed for the room to be occupied
+    #[serde(default = "default_threshold")]
+    pub threshold: f32,
+}
+
+const fn default_threshold() -> f32 {
+    1.0
+}
+
+impl Default for FusionConfig {
+    fn default() -> Self {
+        Self {
+            vacancy_hold: Duration::ZERO,
+            threshold: default_threshold(),
+        }
+    }
+}
+
+/// An occupancy sensor and how much it counts towards the room being occupied
+#[derive(Debug, Clone)]
+pub struct WeightedSensor {
+    /// The occupancy of the sensor
+    pub rx: stateful::Receiver<OccupiedState>,
+    /// How much the sensor counts towards the threshold
+    pub weight: f32,
+}
+
+/// Get the occupancy of a room from the latest sensor states.
+///
+/// Sensors that have not reported yet count as vacant.
+fn fused_state(states: &[Option<OccupiedState>], weights: &[f32], threshold: f32) -> OccupiedState {
+    let total: f32 = states
+        .iter()
+        .zip(weights)
+        .filter(|(state, _)| matches!(state, Some(OccupiedState::Occupied)))
+        .map(|(_, weight)| weight)
+        .sum();
+
+    if total >= threshold {
+        OccupiedState::Occupied
+    } else {
+        OccupiedState::Vacant
+    }
+}
+
+async fn maybe_sleep_until(instant: Option<Instant>) -> Option<()> {
+    if let Some(instant) = instant {
+        sleep_until(instant).await;
+        Some(())
+    } else {
+        None
+    }
+}
+
+/// Combine all the occupancy sensors in a room into one occupancy state.
+///
+/// With the default config the room is occupied if any sensor is occupied. The room only becomes
+/// vacant once the sensors have said vacant for the `vacancy_hold` time.
+#[must_use]
+pub fn fuse_room(
+    room: &str,
+    sensors: Vec<WeightedSensor>,
+    config: FusionConfig,
+) -> stateful::Receiver<OccupiedState> {
+    if sensors.is_empty() {
+        return stateful::static_pipe(OccupiedState::Vacant, format!("OccupancyForRoom_{room}"));
+    }
+
+    let (tx, rx) = stateful::create_pipe(format!("OccupancyForRoom_{room}"));
+    let room = room.to_string();
+
+    spawn(async move {
+        let weights: Vec<f32> = sensors.iter().map(|sensor| sensor.weight).collect();
+        let receivers = sensors.into_iter().map(|sensor| sensor.rx).collect();
+        let combined = stateful::combine_latest(format!("combined_occupancy_{room}"), receivers);
+        let mut combined_sub = combined.subscribe().await;
+
+        let mut states = vec![None; weights.len()];
+        let mut current: Option<OccupiedState> = None;
+        let mut vacant_at: Option<Instant> = None;
+
+        loop {
+            select! {
+                Ok((i, state)) = combined_sub.recv() => {
+                    if let Some(slot) = states.get_mut(i) {
+                        *slot = Some(state);
+                    } else {
+                        error!(
+                            "fuse_room: received out-of-bounds index {i} (states.len = {})",
+                            states.len()
+                        );
+                        continue;
+                    }
+
+                    let fused = fused_state(&states, &weights, config.threshold);
+                    let hold = fused == OccupiedState::Vacant
+                        && current == Some(OccupiedState::Occupied)
+                        && !config.vacancy_hold.is_zero();
+
+                    if hold {
+                        if vacant_at.is_none() {
+                            debug!("Room {room} vacant, holding for {:?}", config.vacancy_hold);
+                            vacant_at = Some(Instant::now() + config.vacancy_hold);
+                        }
+                    } else {
+                        vacant_at = None;
+                        current = Some(fused);
+                        tx.try_send(fused);
+                    }
+                }
+                Some(()) = maybe_sleep_until(vacant_at) => {
+                    vacant_at = None;
+                    current = Some(OccupiedState::Vacant);
+                    tx.try_send(OccupiedState::Vacant);
+                }
+                else => break,
+            }
+        }
+    });
+
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use robotica_common::datetime::duration;
+
+    use super::*;
+
+    #[test]
+    fn test_fused_state() {
+        use OccupiedState::{Occupied, Vacant};
+
+        let weights = [1.0, 1.0, 0.5];
+        assert_eq!(fused_state(&[None, None, None], &weights, 1.0), Vacant);
+        assert_eq!(
+            fused_state(&[Some(Vacant), Some(Occupied), None], &weights, 1.0),
+            Occupied
+        );
+        assert_eq!(
+            fused_state(&[Some(Vacant), Some(Vacant), Some(Occupied)], &weights, 1.0),
+            Vacant
+        );
+        assert_eq!(
+            fused_state(&[Some(Occupied), None, Some(Occupied)], &weights, 1.5),
+            Occupied
+        );
+        assert_eq!(
+            fused_state(&[Some(Occupied), None, None], &weights, 1.5),
+            Vacant
+        );
+    }
+
+    #[test]
+    fn test_fusion_config_deserialize() {
+        let config: FusionConfig = serde_json::from_str(r#"{"vacancy_hold": "00:05:00"}"#).unwrap();
+        assert_eq!(config.vacancy_hold, duration::minutes(5));
+        assert!((config.threshold - 1.0).abs() < f32::EPSILON);
+    }
+}
@@ -159,13 +159,34 @@ fn calculate_away_instant(away_timeout: chrono::TimeDelta, updated: DateTime<Utc
 pub fn is_any_presence_in_room<S: 'static + ::std::hash::BuildHasher + Send>(
     room: &str,
     presences: HashMap<Id, stateful::Receiver<PresenceTrackerValue>, S>,
+) -> stateful::Receiver<bool> {
+    let name = format!("IsAnyPresenceInRoom_{room}");
+    let room = room.to_string();
+    is_any_presence(name, presences, move |value| {
+        value.room.as_ref() == Some(&room)
+    })
+}
+
+/// Is anybody home, in any room?
+#[must_use]
+pub fn is_any_presence_at_home<S: 'static + ::std::hash::BuildHasher + Send>(
+    presences: HashMap<Id, stateful::Receiver<PresenceTrackerValue>, S>,
+) -> stateful::Receiver<bool> {
+    is_any_presence("IsAnyPresenceAtHome".to_string(), presences, |value| {
+        value.room.is_some()
+    })
+}
+
+fn is_any_presence<S: 'static + ::std::hash::BuildHasher + Send>(
+    name: String,
+    presences: HashMap<Id, stateful::Receiver<PresenceTrackerValue>, S>,
+    is_present: impl Fn(&PresenceTrackerValue) -> bool + Send + 'static,
 ) -> stateful::Receiver<bool> {
     if presences.is_empty() {
-        return stateful::static_pipe(false, format!("IsAnyPresenceInRoom_{room}"));
+        return stateful::static_pipe(false, name);
     }
 
-    let (tx, rx) = stateful::create_pipe(format!("IsAnyPresenceInRoom_{room}"));
-    let room = room.to_string();
+    let (tx, rx) = stateful::create_pipe(name);
 
     spawn(async move {
         let mut results = vec![false; presences.len()];
@@ -175,10 +196,10 @@ pub fn is_any_presence_in_room<S: 'static + ::std::hash::BuildHasher + Send>(
 
         while let Ok((i, msg)) = combined_sub.recv().await {
             if let Some(slot) = results.get_mut(i) {
-                *slot = msg.room.as_ref() == Some(&room);
+                *slot = is_present(&msg);
             } else {
                 tracing::error!(
-                    "is_any_presence_in_room: received out-of-bounds index {i} (results.len = {})",
+                    "is_any_presence: received out-of-bounds index {i} (results.len = {})",
                     results.len()
                 );
             }
@@ -0,0 +1,369 @@
+//! Control lights connected via `Zigbee2MQTT`, such as Hue and IKEA bulbs
+
+use robotica_common::mqtt::{Json, QoS, Retain};
+use robotica_common::robotica::lights::{Colors, PowerColor, PowerColorTransition, State, HSBK};
+use robotica_common::zigbee2mqtt::{Availability, Light, LightColor, LightPower};
+use serde::Deserialize;
+use tokio::select;
+use tracing::{debug, error};
+
+use crate::{
+    is_debug_mode,
+    pipes::{stateful, Subscriber, Subscription},
+    services::mqtt::{MqttTx, Subscriptions},
+    spawn,
+};
+
+const MAX_BRIGHTNESS: f32 = 254.0;
+const DEFAULT_KELVIN: u16 = 3500;
+
+/// What a `Zigbee2MQTT` light can display.
+#[derive(Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LightCapability {
+    /// Full color and color temperature.
+    Color,
+
+    /// White with adjustable color temperature only.
+    ColorTemperature,
+
+    /// Brightness only.
+    Dimmer,
+}
+
+/// Configuration for a `Zigbee2MQTT` light.
+#[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct LightConfig {
+    /// The topic of the light, for example `zigbee2mqtt/Brian/Light`.
+    pub topic: String,
+
+    /// What the light can display.
+    pub capability: LightCapability,
+
+    /// The warmest color temperature the light supports.
+    #[serde(default = "default_min_kelvin")]
+    pub min_kelvin: u16,
+
+    /// The coolest color temperature the light supports.
+    #[serde(default = "default_max_kelvin")]
+    pub max_kelvin: u16,
+}
+
+const fn default_min_kelvin() -> u16 {
+    2200
+}
+
+const fn default_max_kelvin() -> u16 {
+    6500
+}
+
+fn first_color(colors: &Colors) -> Option<HSBK> {
+    match colors {
+        Colors::Single(color) => Some(*color),
+        Colors::Sequence(colors) => colors.first().copied(),
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn to_brightness(percent: f32) -> u8 {
+    (percent.clamp(0.0, 100.0) * MAX_BRIGHTNESS / 100.0).round() as u8
+}
+
+fn to_percent(brightness: u8) -> f32 {
+    f32::from(brightness) * 100.0 / MAX_BRIGHTNESS
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn kelvin_to_mireds(kelvin: u16) -> u16 {
+    (1_000_000 / u32::from(kelvin.max(1))) as u16
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn mireds_to_kelvin(mireds: u16) -> u16 {
+    (1_000_000 / u32::from(mireds.max(1))).min(u32::from(u16::MAX)) as u16
+}
+
+/// Approximate a saturated color with a white, for lights without color.
+///
+/// Reds, oranges and yellows become warmer and blues become cooler.
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn color_to_kelvin(color: HSBK, config: &LightConfig) -> u16 {
+    let kelvin = color.kelvin.clamp(config.min_kelvin, config.max_kelvin);
+    let hue = color.hue.rem_euclid(360.0);
+    let target = if !(90.0..300.0).contains(&hue) {
+        config.min_kelvin
+    } else if (150.0..=270.0).contains(&hue) {
+        config.max_kelvin
+    } else {
+        kelvin
+    };
+    let fraction = color.saturation.clamp(0.0, 100.0) / 100.0;
+    let kelvin = f32::from(kelvin);
+    (kelvin + (f32::from(target) - kelvin) * fraction).round() as u16
+}
+
+/// Get the payload to send to the light's `set` topic.
+#[must_use]
+pub fn power_color_to_light(power_color: &PowerColor, config: &LightConfig) -> Light {
+    let off = Light {
+        state: Some(LightPower::Off),
+        ..Light::default()
+    };
+
+    let color = match power_color {
+        PowerColor::Off => return off,
+        PowerColor::On(colors) => match first_color(colors) {
+            Some(color) if color.brightness > 0.0 => color,
+            _ => return off,
+        },
+    };
+
+    let mut light = Light {
+        state: Some(LightPower::On),
+        brightness: Some(to_brightness(color.brightness)),
+        ..Light::default()
+    };
+
+    match config.capability {
+        LightCapability::Color if color.saturation > 0.0 => {
+            light.color = Some(LightColor {
+                hue: Some(color.hue.rem_euclid(360.0)),
+                saturation: Some(color.saturation.clamp(0.0, 100.0)),
+            });
+        }
+        LightCapability::Color => {
+            let kelvin = color.kelvin.clamp(config.min_kelvin, config.max_kelvin);
+            light.color_temp = Some(kelvin_to_mireds(kelvin));
+        }
+        LightCapability::ColorTemperature => {
+            light.color_temp = Some(kelvin_to_mireds(color_to_kelvin(color, config)));
+        }
+        LightCapability::Dimmer => {}
+    }
+
+    light
+}
+
+/// Get the colors the light reports it is showing.
+#[must_use]
+pub fn light_to_power_color(light: &Light) -> PowerColor {
+    if light.state != Some(LightPower::On) {
+        return PowerColor::Off;
+    }
+
+    let (hue, saturation) = match (&light.color, light.color_mode.as_deref()) {
+        (Some(color), Some("hs" | "xy")) => (
+            color.hue.unwrap_or_default(),
+            color.saturation.unwrap_or_default(),
+        ),
+        _ => (0.0, 0.0),
+    };
+
+    PowerColor::On(Colors::Single(HSBK {
+        hue,
+        saturation,
+        brightness: light.brightness.map_or(100.0, to_percent),
+        kelvin: light.color_temp.map_or(DEFAULT_KELVIN, mireds_to_kelvin),
+    }))
+}
+
+/// Get the payload to send to the light's `set` topic, letting the light do the fade itself.
+#[must_use]
+pub fn transition_to_light(transition: &PowerColorTransition, config: &LightConfig) -> Light {
+    let mut light = power_color_to_light(&transition.pc, config);
+    if !transition.duration.is_zero() {
+        light.transition = Some(transition.duration.as_secs_f32());
+    }
+    light
+}
+
+fn send_transition(mqtt: &MqttTx, transition: &PowerColorTransition, config: &LightConfig) {
+    if is_debug_mode() {
+        debug!("Not setting power color in debug mode");
+        return;
+    }
+
+    let light = transition_to_light(transition, config);
+    mqtt.try_serialize_send(
+        format!("{}/set", config.topic),
+        &Json(light),
+        Retain::NoRetain,
+        QoS::AtLeastOnce,
+    );
+}
+
+/// Run the device.
+#[must_use]
+pub fn device_entity(
+    rx_pc: stateful::Receiver<PowerColorTransition>,
+    config: LightConfig,
+    mqtt: MqttTx,
+    subscriptions: &mut Subscriptions,
+) -> stateful::Receiver<State> {
+    let (tx_state, rx_state) = stateful::create_pipe("zigbee2mqtt_state");
+    let light_rx = subscriptions.subscribe_into_stateless::<Json<Light>>(&config.topic);
+    let availability_rx = subscriptions
+        .subscribe_into_stateful::<Availability>(format!("{}/availability", config.topic));
+
+    spawn(async move {
+        let mut light_s = light_rx.subscribe().await;
+        let mut availability_s = availability_rx.subscribe().await;
+        let mut rx_s = rx_pc.subscribe().await;
+        let mut availability = Availability::Online;
+        tx_state.try_send(State::Offline);
+
+        loop {
+            select! {
+                Ok(transition) = rx_s.recv() => {
+                    debug!("{} set power color: {transition:?}", config.topic);
+                    send_transition(&mqtt, &transition, &config);
+                }
+                Ok(Json(light)) = light_s.recv() => {
+                    if availability == Availability::Online {
+                        tx_state.try_send(State::Online(light_to_power_color(&light)));
+                    }
+                }
+                Ok(new_availability) = availability_s.recv() => {
+                    availability = new_availability;
+                    match availability {
+                        Availability::Online => {
+                            // The light may have lost its state while it was unreachable.
+                            // Any fade has been missed, so go straight to the final color.
+                            if let Some(transition) = rx_pc.get().await {
+                                let transition = PowerColorTransition::immediate(transition.pc);
+                                send_transition(&mqtt, &transition, &config);
+                            }
+                        }
+                        Availability::Offline => {
+                            error!("{} is offline", config.topic);
+                            tx_state.try_send(State::Offline);
+                        }
+                    }
+                }
+            }
+        }
+    });
+
+    rx_state
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use std::time::Duration;
+
+    use super::*;
+
+    fn config(capability: LightCapability) -> LightConfig {
+        LightConfig {
+            topic: "zigbee2mqtt/Brian/Light".to_string(),
+            capability,
+            min_kelvin: 2200,
+            max_kelvin: 6500,
+        }
+    }
+
+    fn on(hue: f32, saturation: f32, brightness: f32, kelvin: u16) -> PowerColor {
+        PowerColor::On(Colors::Single(HSBK {
+            hue,
+            saturation,
+            brightness,
+            kelvin,
+        }))
+    }
+
+    #[test]
+    fn test_power_color_to_light() {
+        let color = config(LightCapability::Color);
+        let temperature = config(LightCapability::ColorTemperature);
+        let dimmer = config(LightCapability::Dimmer);
+
+        let light = power_color_to_light(&PowerColor::Off, &color);
+        assert_eq!(light.state, Some(LightPower::Off));
+        assert_eq!(light.brightness, None);
+
+        let light = power_color_to_light(&on(0.0, 0.0, 0.0, 3500), &color);
+        assert_eq!(light.state, Some(LightPower::Off));
+
+        let white = on(0.0, 0.0, 50.0, 4000);
+        let light = power_color_to_light(&white, &color);
+        assert_eq!(light.state, Some(LightPower::On));
+        assert_eq!(light.brightness, Some(127));
+        assert_eq!(light.color_temp, Some(250));
+        assert_eq!(light.color, None);
+
+        let red = on(0.0, 100.0, 100.0, 4000);
+        let light = power_color_to_light(&red, &color);
+        assert_eq!(light.brightness, Some(254));
+        assert_eq!(light.color_temp, None);
+        assert_eq!(
+            light.color,
+            Some(LightColor {
+                hue: Some(0.0),
+                saturation: Some(100.0)
+            })
+        );
+
+        let light = power_color_to_light(&red, &temperature);
+        assert_eq!(light.color_temp, Some(kelvin_to_mireds(2200)));
+        assert_eq!(light.color, None);
+
+        let blue = on(240.0, 50.0, 100.0, 4000);
+        let light = power_color_to_light(&blue, &temperature);
+        assert_eq!(light.color_temp, Some(kelvin_to_mireds(5250)));
+
+        let light = power_color_to_light(&red, &dimmer);
+        assert_eq!(light.brightness, Some(254));
+        assert_eq!(light.color_temp, None);
+        assert_eq!(light.color, None);
+    }
+
+    #[test]
+    fn test_light_to_power_color() {
+        let light: Light = serde_json::from_str(
+            r#"{"state":"ON","brightness":254,"color_temp":250,"color_mode":"color_temp","color":{"hue":30,"saturation":80,"x":0.4,"y":0.4},"linkquality":120}"#,
+        )
+        .unwrap();
+        assert_eq!(light_to_power_color(&light), on(0.0, 0.0, 100.0, 4000));
+
+        let light: Light = serde_json::from_str(
+            r#"{"state":"ON","brightness":127,"color_mode":"hs","color":{"hue":120,"saturation":100}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            light_to_power_color(&light),
+            on(120.0, 100.0, 50.0, DEFAULT_KELVIN)
+        );
+
+        let light: Light = serde_json::from_str(r#"{"state":"OFF","brightness":254}"#).unwrap();
+        assert_eq!(light_to_power_color(&light), PowerColor::Off);
+    }
+
+    #[test]
+    fn test_serialize_light() {
+        let light =
+            power_color_to_light(&on(0.0, 0.0, 100.0, 4000), &config(LightCapability::Color));
+        let json = serde_json::to_string(&light).unwrap();
+        assert_eq!(json, r#"{"state":"ON","brightness":254,"color_temp":250}"#);
+    }
+
+    #[test]
+    fn test_serialize_transition() {
+        let config = config(LightCapability::Color);
+        let transition = PowerColorTransition {
+            pc: on(0.0, 0.0, 100.0, 4000),
+            duration: Duration::from_millis(2500),
+        };
+        let json = serde_json::to_string(&transition_to_light(&transition, &config)).unwrap();
+        assert_eq!(
+            json,
+            r#"{"state":"ON","brightness":254,"color_temp":250,"transition":2.5}"#
+        );
+
+        let transition = PowerColorTransition::immediate(PowerColor::Off);
+        let json = serde_json::to_string(&transition_to_light(&transition, &config)).unwrap();
+        assert_eq!(json, r#"{"state":"OFF"}"#);
+    }
+}
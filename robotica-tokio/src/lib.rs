@@ -21,7 +21,7 @@ pub mod scheduling;
 
 use std::{env, future::Future};
 use tokio::task::JoinHandle;
-use tracing::{debug, error};
+use tracing::{debug, error, warn};
 
 /// Spawn a task and automatically monitor its execution.
 pub fn spawn<T>(future: T) -> JoinHandle<()>
@@ -75,3 +75,18 @@ pub fn is_debug_mode() -> bool {
 
     false
 }
+
+/// Get the IANA timezone of the host, or UTC if it cannot be found.
+#[must_use]
+pub fn host_timezone() -> chrono_tz::Tz {
+    iana_time_zone::get_timezone()
+        .map_err(|err| err.to_string())
+        .and_then(|name| {
+            name.parse()
+                .map_err(|err: chrono_tz::ParseError| err.to_string())
+        })
+        .unwrap_or_else(|err| {
+            warn!("Cannot get host timezone, using UTC: {err}");
+            chrono_tz::UTC
+        })
+}
@@ -22,6 +22,7 @@ pub(in crate::pipes) use receiver::ReceiveMessage;
 use sender::SendMessage;
 use tokio::sync::broadcast;
 use tokio::sync::mpsc;
+use tokio::sync::watch;
 
 /// Create a stateful entity that sends every message and adds state messages.
 /// Only the last value is stored and replayed to new subscribers.
@@ -36,11 +37,15 @@ where
 
     drop(out_rx);
 
+    let (subscribed_tx, subscribed_rx) = watch::channel(());
+
     let name = name.into();
 
     let sender: Sender<T> = Sender {
         tx: send_tx,
         name: name.clone(),
+        out_tx: out_tx.clone(),
+        subscribed: subscribed_rx,
     };
     let receiver: Receiver<T> = Receiver {
         tx: receive_tx,
@@ -85,6 +90,8 @@ where
                             let replay = current_data.clone().into_iter().collect();
                             if tx.send((rx, replay)).is_err() {
                                 error!("stateful::create_pipe{name}): subscribe send failed");
+                            } else {
+                                subscribed_tx.send_replace(());
                             }
                         }
                         None => {
@@ -120,11 +127,15 @@ where
 
     drop(out_rx);
 
+    let (subscribed_tx, subscribed_rx) = watch::channel(());
+
     let name = name.into();
 
     let sender: Sender<T> = Sender {
         tx: send_tx,
         name: name.clone(),
+        out_tx: out_tx.clone(),
+        subscribed: subscribed_rx,
     };
     let receiver: Receiver<T> = Receiver {
         tx: receive_tx,
@@ -170,6 +181,8 @@ where
                             let replay: Vec<T> = indexed_data.values().cloned().collect();
                             if tx.send((rx, replay)).is_err() {
                                 error!("stateful::create_indexed_pipe{name}): subscribe send failed");
+                            } else {
+                                subscribed_tx.send_replace(());
                             }
                         }
                         None => {
@@ -1,7 +1,9 @@
 //! Stateful sender code.
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc, watch};
 use tracing::error;
 
+use super::OldNewType;
+
 pub(super) enum SendMessage<T> {
     Set(T),
 }
@@ -12,6 +14,8 @@ pub struct Sender<T> {
     #[allow(dead_code)]
     pub(super) name: String,
     pub(super) tx: mpsc::UnboundedSender<SendMessage<T>>,
+    pub(super) out_tx: broadcast::Sender<OldNewType<T>>,
+    pub(super) subscribed: watch::Receiver<()>,
 }
 
 impl<T> Sender<T> {
@@ -29,6 +33,29 @@ impl<T> Sender<T> {
         self.tx.is_closed()
     }
 
+    /// Does the entity have any subscribers right now?
+    #[must_use]
+    pub fn has_subscribers(&self) -> bool {
+        self.out_tx.receiver_count() > 0
+    }
+
+    /// Completes once the entity has a subscriber.
+    ///
+    /// Use this to avoid producing values that nobody is watching.
+    pub async fn subscribed(&self) {
+        let mut subscribed = self.subscribed.clone();
+        loop {
+            subscribed.borrow_and_update();
+            if self.has_subscribers() {
+                return;
+            }
+            // An error means the entity is closed, nothing will ever subscribe.
+            if subscribed.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
     /// Completes when the entity is closed.
     pub async fn closed(&self) {
         self.tx.closed().await;
@@ -17,6 +17,7 @@ pub(super) use receiver::ReceiveMessage;
 use sender::SendMessage;
 use tokio::sync::broadcast;
 use tokio::sync::mpsc;
+use tokio::sync::watch;
 
 /// Create a stateless entity that sends every message.
 #[must_use]
@@ -30,11 +31,14 @@ where
 
     drop(out_rx);
 
+    let (subscribed_tx, subscribed_rx) = watch::channel(false);
+
     let name = name.into();
 
     let sender: Sender<T> = Sender {
         tx: send_tx,
         name: name.clone(),
+        subscribed: subscribed_rx,
     };
     let receiver: Receiver<T> = Receiver {
         tx: receive_tx,
@@ -71,6 +75,8 @@ where
                             let rx = out_tx.subscribe();
                             if tx.send(rx).is_err() {
                                 error!("stateless::create_pipe({name}): subscribe send failed");
+                            } else {
+                                subscribed_tx.send_replace(true);
                             }
                         }
                         None => {
@@ -1,5 +1,5 @@
 //! Stateless sender code.
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 use tracing::error;
 
 pub(super) enum SendMessage<T> {
@@ -12,6 +12,7 @@ pub struct Sender<T> {
     #[allow(dead_code)]
     pub(super) name: String,
     pub(super) tx: mpsc::Sender<SendMessage<T>>,
+    pub(super) subscribed: watch::Receiver<bool>,
 }
 
 impl<T> Sender<T> {
@@ -29,6 +30,15 @@ impl<T> Sender<T> {
         self.tx.is_closed()
     }
 
+    /// Completes once the entity has had a subscriber.
+    ///
+    /// Messages sent before anything subscribes are dropped, use this to avoid losing them.
+    pub async fn subscribed(&self) {
+        let mut subscribed = self.subscribed.clone();
+        // An error means the entity is closed, nothing will ever subscribe.
+        let _ = subscribed.wait_for(|subscribed| *subscribed).await;
+    }
+
     /// Completes when the entity is closed.
     pub async fn closed(&self)
     where
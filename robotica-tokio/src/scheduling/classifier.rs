@@ -4,16 +4,19 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use chrono::Datelike;
+use chrono::{Datelike, TimeDelta};
 use evalexpr::{
     build_operator_tree, ContextWithMutableFunctions, ContextWithMutableVariables,
-    DefaultNumericTypes, Function, HashMapContext, Node, Value,
+    DefaultNumericTypes, EvalexprError, Function, HashMapContext, Node, Value,
 };
 use serde::{Deserialize, Deserializer};
 use thiserror::Error;
 
 use robotica_common::datetime::{num_days_from_ce, week_day_to_string, Date, Weekday};
 
+use super::holidays::{self, Holidays};
+use super::lint;
+
 /// A compiled evalexpr condition.
 #[derive(Debug, Clone)]
 pub struct Condition(Node);
@@ -30,20 +33,50 @@ impl<'de> Deserialize<'de> for Condition {
     }
 }
 
+fn parse_weekday(value: &Value<DefaultNumericTypes>) -> Result<Weekday, EvalexprError> {
+    let weekday = value.as_string()?;
+    weekday
+        .parse()
+        .map_err(|_| EvalexprError::CustomMessage(format!("Invalid weekday {weekday}")))
+}
+
 fn build_context(
-    days_since_epoch: i32,
-    day_of_week: &str,
+    date: Date,
     classifications: &HashSet<String>,
 ) -> HashMapContext<DefaultNumericTypes> {
     let classifications = classifications.clone();
     let mut ctx = HashMapContext::new();
     ctx.set_value(
         "days_since_epoch".into(),
-        Value::Int(i64::from(days_since_epoch)),
+        Value::Int(i64::from(num_days_from_ce(&date))),
+    )
+    .ok();
+    ctx.set_value(
+        "day_of_week".into(),
+        Value::String(week_day_to_string(date.weekday()).to_lowercase()),
     )
     .ok();
-    ctx.set_value("day_of_week".into(), Value::String(day_of_week.to_string()))
+    ctx.set_value("year".into(), Value::Int(i64::from(date.year())))
+        .ok();
+    ctx.set_value("month".into(), Value::Int(i64::from(date.month())))
+        .ok();
+    ctx.set_value("day".into(), Value::Int(i64::from(date.day())))
         .ok();
+    ctx.set_value(
+        "iso_week".into(),
+        Value::Int(i64::from(date.iso_week().week())),
+    )
+    .ok();
+    ctx.set_value(
+        "week_parity".into(),
+        Value::Int(holidays::week_parity(date)),
+    )
+    .ok();
+    ctx.set_value(
+        "fortnight_parity".into(),
+        Value::Int(holidays::fortnight_parity(date)),
+    )
+    .ok();
     ctx.set_function(
         "classifications".to_string(),
         Function::new(move |arg| {
@@ -52,6 +85,34 @@ fn build_context(
         }),
     )
     .ok();
+    ctx.set_function(
+        "easter".to_string(),
+        Function::new(move |arg| {
+            let offset = arg.as_int()?;
+            let easter = holidays::easter_sunday(date.year())
+                .and_then(|easter| easter.checked_add_signed(TimeDelta::days(offset)));
+            Ok(Value::Boolean(easter == Some(date)))
+        }),
+    )
+    .ok();
+    ctx.set_function(
+        "nth_weekday".to_string(),
+        Function::new(move |arg| {
+            let args = arg.as_fixed_len_tuple(2)?;
+            let n = u32::try_from(args[0].as_int()?).unwrap_or(0);
+            let weekday = parse_weekday(&args[1])?;
+            Ok(Value::Boolean(holidays::is_nth_weekday(date, n, weekday)))
+        }),
+    )
+    .ok();
+    ctx.set_function(
+        "last_weekday".to_string(),
+        Function::new(move |arg| {
+            let weekday = parse_weekday(arg)?;
+            Ok(Value::Boolean(holidays::is_last_weekday(date, weekday)))
+        }),
+    )
+    .ok();
     ctx
 }
 
@@ -71,6 +132,28 @@ pub struct Config {
     delete: Option<Vec<String>>,
 }
 
+impl Config {
+    /// The tags this entry can add.
+    pub(super) fn added_tags(&self) -> impl Iterator<Item = &String> {
+        self.add.iter().flatten()
+    }
+
+    /// The tags this entry depends on.
+    pub(super) fn referenced_tags(&self) -> HashSet<String> {
+        let mut tags: HashSet<String> = self
+            .if_set
+            .iter()
+            .chain(self.if_not_set.iter())
+            .flatten()
+            .cloned()
+            .collect();
+        for cond in self.if_cond.iter().flatten() {
+            tags.extend(lint::function_arguments(&cond.0, "classifications"));
+        }
+        tags
+    }
+}
+
 /// An error loading the Config
 #[derive(Error, Debug)]
 pub enum ConfigError {
@@ -100,13 +183,19 @@ pub fn load_config(filename: &Path) -> Result<Vec<Config>, ConfigError> {
 
 /// Classify a date.
 ///
+/// Tags for any holidays on the date are added before the config is applied.
+///
 /// # Errors
 ///
 /// Returns an error if the environment variable `CLASSIFICATIONS_FILE` is not set or if the file
 /// cannot be read or parsed.
 #[must_use]
-pub fn classify_date_with_config(date: &Date, config: &Vec<Config>) -> HashSet<String> {
-    let mut tags = HashSet::new();
+pub fn classify_date_with_config(
+    date: &Date,
+    config: &Vec<Config>,
+    holidays: &Holidays,
+) -> HashSet<String> {
+    let mut tags = holidays.tags_for_date(*date);
 
     for c in config {
         if let Some(c_date) = c.date {
@@ -138,11 +227,7 @@ pub fn classify_date_with_config(date: &Date, config: &Vec<Config>) -> HashSet<S
             }
         }
         if let Some(if_cond) = &c.if_cond {
-            let ctx = build_context(
-                num_days_from_ce(date),
-                &week_day_to_string(date.weekday()).to_lowercase(),
-                &tags,
-            );
+            let ctx = build_context(*date, &tags);
             if !if_cond.iter().any(|cond| {
                 cond.0.eval_boolean_with_context(&ctx).unwrap_or_else(|e| {
                     tracing::error!("Error evaluating condition: {}", e);
@@ -179,6 +264,7 @@ mod tests {
     use std::str::FromStr;
 
     use super::*;
+    use crate::scheduling::holidays::load_config as load_holidays;
 
     struct Test {
         date: Date,
@@ -229,7 +315,7 @@ mod tests {
         ];
 
         for test in tests {
-            let tags = classify_date_with_config(&test.date, &config);
+            let tags = classify_date_with_config(&test.date, &config, &Holidays::default());
             for include in test.includes {
                 assert!(
                     tags.contains(include),
@@ -277,26 +363,26 @@ mod tests {
         ];
 
         let date = Date::from_ymd_opt(2019, 1, 7).unwrap();
-        let tags = classify_date_with_config(&date, &config);
+        let tags = classify_date_with_config(&date, &config, &Holidays::default());
         assert_eq!(tags, HashSet::from([]));
 
         let date = Date::from_ymd_opt(2020, 1, 1).unwrap();
-        let tags = classify_date_with_config(&date, &config);
+        let tags = classify_date_with_config(&date, &config, &Holidays::default());
         assert_eq!(tags, HashSet::from(["weekday".to_string()]));
 
         let date = Date::from_ymd_opt(2020, 1, 4).unwrap();
-        let tags = classify_date_with_config(&date, &config);
+        let tags = classify_date_with_config(&date, &config, &Holidays::default());
         assert_eq!(tags, HashSet::from([]));
 
         let date = Date::from_ymd_opt(2020, 1, 6).unwrap();
-        let tags = classify_date_with_config(&date, &config);
+        let tags = classify_date_with_config(&date, &config, &Holidays::default());
         assert_eq!(
             tags,
             HashSet::from(["weekday".to_string(), "monday".to_string()])
         );
 
         let date = Date::from_ymd_opt(2021, 1, 4).unwrap();
-        let tags = classify_date_with_config(&date, &config);
+        let tags = classify_date_with_config(&date, &config, &Holidays::default());
         assert_eq!(tags, HashSet::from([]));
     }
 
@@ -324,7 +410,74 @@ mod tests {
         }];
 
         let date = Date::from_ymd_opt(2020, 1, 1).unwrap();
-        let tags = classify_date_with_config(&date, &config);
+        let tags = classify_date_with_config(&date, &config, &Holidays::default());
         assert_eq!(tags, HashSet::from([]));
     }
+
+    fn cond_config(cond: &str, add: &str) -> Config {
+        Config {
+            date: None,
+            start: None,
+            stop: None,
+            week_day: None,
+            day_of_week: None,
+            if_cond: Some(vec![Condition(
+                build_operator_tree::<DefaultNumericTypes>(cond).unwrap(),
+            )]),
+            if_set: None,
+            if_not_set: None,
+            add: Some(vec![add.to_string()]),
+            delete: None,
+        }
+    }
+
+    #[test]
+    fn test_date_functions() {
+        let config = vec![
+            cond_config("easter(-2)", "good_friday"),
+            cond_config(
+                r#"nth_weekday(2, "monday") && month == 6"#,
+                "kings_birthday",
+            ),
+            cond_config(r#"last_weekday("friday")"#, "last_friday"),
+            cond_config("fortnight_parity == 0", "even_week"),
+            cond_config("iso_week == 16 && year == 2025", "week_16"),
+            cond_config(r#"classifications("public_holiday")"#, "day_off"),
+        ];
+
+        let holidays = load_holidays(&[PathBuf::from("test/holidays.yaml")]).unwrap();
+
+        let tags =
+            classify_date_with_config(&Date::from_str("2025-04-18").unwrap(), &config, &holidays);
+        assert!(tags.contains("good_friday"));
+        assert!(tags.contains("week_16"));
+        assert!(tags.contains("day_off"));
+        assert!(!tags.contains("kings_birthday"));
+        assert!(!tags.contains("last_friday"));
+
+        let tags =
+            classify_date_with_config(&Date::from_str("2025-06-09").unwrap(), &config, &holidays);
+        assert!(tags.contains("kings_birthday"));
+        assert!(tags.contains("day_off"));
+        assert!(!tags.contains("good_friday"));
+
+        let tags =
+            classify_date_with_config(&Date::from_str("2025-06-27").unwrap(), &config, &holidays);
+        assert!(tags.contains("last_friday"));
+        assert!(!tags.contains("day_off"));
+
+        // Fortnightly weeks alternate.
+        let week_1 =
+            classify_date_with_config(&Date::from_str("2025-06-23").unwrap(), &config, &holidays);
+        let week_2 =
+            classify_date_with_config(&Date::from_str("2025-06-30").unwrap(), &config, &holidays);
+        assert_ne!(week_1.contains("even_week"), week_2.contains("even_week"));
+
+        // Including over a year with 53 ISO weeks.
+        let week_53 =
+            classify_date_with_config(&Date::from_str("2020-12-28").unwrap(), &config, &holidays);
+        let week_1 =
+            classify_date_with_config(&Date::from_str("2021-01-04").unwrap(), &config, &holidays);
+        assert_ne!(week_53.contains("even_week"), week_1.contains("even_week"));
+    }
 }
@@ -1,30 +1,46 @@
 //! Run tasks based on schedule.
 use std::collections::hash_map::DefaultHasher;
-use std::collections::{HashMap, VecDeque};
-use std::fmt::Debug;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{Debug, Display};
 use std::hash::{Hash, Hasher};
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 
 use chrono::{NaiveDate, TimeDelta, TimeZone, Utc};
-use robotica_common::mqtt::{Json, MqttSerializer, QoS, Retain};
+use robotica_common::mqtt::{Json, MqttMessage, MqttSerializer, QoS, Retain};
 use robotica_macro::time_delta_constant;
 use thiserror::Error;
 use tokio::select;
+use tokio::sync::{mpsc, oneshot};
 use tokio::time::Instant;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 use robotica_common::datetime::{utc_now, Date, DateTime, NaiveDateIter};
-use robotica_common::scheduler::{Importance, Mark, MarkStatus, Status, Tags, TagsForDay};
-
-use crate::pipes::{Subscriber, Subscription};
+use robotica_common::robotica::audio::MessagePriority;
+use robotica_common::robotica::commands::Command;
+use robotica_common::robotica::entities::{AnyId, IdWithRoom};
+use robotica_common::robotica::lights::{FlashPattern, LightCommand};
+use robotica_common::robotica::message::{Audience, Message};
+use robotica_common::robotica::tasks::{Payload, Task};
+use robotica_common::scheduler::{
+    CreateHistoryEntry, HistoryOutcome, HistoryTask, Importance, Mark, MarkStatus, Status, Tags,
+    TagsForDay, TaskOutcome,
+};
+use robotica_common::solar::Location;
+
+use crate::pipes::{generic, stateless, Subscriber, Subscription};
 use crate::scheduling::sequencer::check_schedule;
-use crate::services::mqtt::{MqttTx, Subscriptions};
-use crate::{scheduling::calendar, spawn};
+use crate::services::{
+    self,
+    mqtt::{MqttTx, SubscribeError, Subscriptions},
+};
+use crate::{host_timezone, scheduling::calendar, spawn};
 
 use super::calendar::CalendarEntry;
-use super::sequencer::Sequence;
-use super::{classifier, scheduler, sequencer};
+use super::runtime::{self, check_conditions};
+use super::sequencer::{Expectation, Sequence};
+use super::{classifier, holidays, scheduler, sequencer};
 
 type CalendarToSequence<T> = dyn Fn(CalendarEntry, T) -> Option<Sequence> + Send + Sync + 'static;
 
@@ -40,11 +56,217 @@ pub struct Config {
     /// The filename for the classifier config.
     pub classifications_file: PathBuf,
 
+    /// The filenames for the holiday definitions, typically one per region.
+    #[serde(default)]
+    pub holiday_files: Vec<PathBuf>,
+
     /// The filename for the scheduler config.
     pub schedule_file: PathBuf,
 
     /// The filename for the sequencer config.
     pub sequences_file: PathBuf,
+
+    /// The location used for schedule times relative to the sun.
+    pub location: Option<Location>,
+
+    /// The IANA timezone for the schedule, for example `Australia/Melbourne`.
+    ///
+    /// If not set the timezone of the host is used.
+    pub timezone: Option<chrono_tz::Tz>,
+
+    /// The audience for messages about tasks that could not be confirmed.
+    #[serde(default = "default_failure_audience")]
+    pub failure_audience: Audience,
+
+    /// Reminders for high importance sequences.
+    #[serde(default)]
+    pub reminders: Option<ReminderConfig>,
+}
+
+fn default_failure_audience() -> Audience {
+    Audience::new("everyone")
+}
+
+/// Reminders for high importance sequences.
+///
+/// In templates `{title}` is replaced with the sequence title and `{minutes}` with the minutes
+/// until the start.
+#[derive(serde::Deserialize)]
+pub struct ReminderConfig {
+    /// The audience for reminders.
+    pub audience: Audience,
+
+    /// Send reminders this many minutes before the start, unless the sequence overrides it.
+    #[serde(default = "default_minutes_before")]
+    pub minutes_before: Vec<u32>,
+
+    /// The reminder sent before the start.
+    #[serde(default = "default_before_template")]
+    pub before_template: String,
+
+    /// The reminder sent at the start.
+    #[serde(default = "default_start_template")]
+    pub start_template: String,
+
+    /// Escalate if the sequence is not marked done this long after the start, unless the
+    /// sequence overrides it.
+    #[serde(with = "robotica_common::datetime::with_option_duration")]
+    #[serde(default)]
+    pub escalate_after: Option<Duration>,
+
+    /// The message sent when escalating.
+    #[serde(default = "default_escalate_template")]
+    pub escalate_template: String,
+
+    /// The audience for escalations.
+    pub escalate_audience: Audience,
+
+    /// A light to flash when escalating, instead of asking the message to flash the lights.
+    pub flash_light: Option<IdWithRoom>,
+}
+
+fn default_minutes_before() -> Vec<u32> {
+    vec![10]
+}
+
+fn default_before_template() -> String {
+    "{title} in {minutes} minutes".to_string()
+}
+
+fn default_start_template() -> String {
+    "Time for {title}".to_string()
+}
+
+fn default_escalate_template() -> String {
+    "{title} has not been done".to_string()
+}
+
+impl ReminderConfig {
+    fn minutes_before<'a>(&'a self, sequence: &'a Sequence) -> &'a [u32] {
+        sequence
+            .reminders
+            .as_ref()
+            .and_then(|r| r.minutes_before.as_deref())
+            .unwrap_or(&self.minutes_before)
+    }
+
+    fn escalate_after(&self, sequence: &Sequence) -> Option<Duration> {
+        sequence
+            .reminders
+            .as_ref()
+            .and_then(|r| r.escalate_after)
+            .or(self.escalate_after)
+    }
+
+    /// Get reminder events for a sequence that have not happened yet.
+    fn events(
+        &self,
+        sequence: &Sequence,
+        sequence_index: usize,
+        status: Status,
+        now: DateTime<Utc>,
+    ) -> Vec<Event> {
+        if sequence.importance < Importance::High || status == Status::Cancelled {
+            return Vec::new();
+        }
+
+        let mut events = Vec::new();
+        if status == Status::Pending {
+            // The reminder at the start is sent when the sequence starts.
+            for &minutes in self.minutes_before(sequence).iter().filter(|&&m| m > 0) {
+                events.push(Event {
+                    datetime: sequence.start_time - TimeDelta::minutes(i64::from(minutes)),
+                    sequence_index,
+                    kind: EventKind::Remind { minutes },
+                });
+            }
+        }
+        if let Some(escalate_after) = self.escalate_after(sequence) {
+            if let Ok(escalate_after) = TimeDelta::from_std(escalate_after) {
+                events.push(Event {
+                    datetime: sequence.start_time + escalate_after,
+                    sequence_index,
+                    kind: EventKind::Escalate,
+                });
+            }
+        }
+
+        // Events are rebuilt regularly, past reminders have already been sent.
+        events.retain(|event| event.datetime > now);
+        events
+    }
+
+    fn reminder(&self, sequence: &Sequence, minutes: u32) -> Message {
+        let template = if minutes == 0 {
+            &self.start_template
+        } else {
+            &self.before_template
+        };
+        Message::new(
+            "Reminder",
+            fill_template(template, sequence, minutes),
+            MessagePriority::DaytimeOnly,
+            &self.audience,
+        )
+    }
+
+    /// Get the escalation for a sequence, unless it has been done or cancelled.
+    fn escalation(
+        &self,
+        sequence: &Sequence,
+        mark: Option<&MarkStatus>,
+        status: Status,
+    ) -> Option<(Message, Option<Task>)> {
+        if matches!(mark, Some(MarkStatus::Done | MarkStatus::Cancelled))
+            || status == Status::Cancelled
+        {
+            return None;
+        }
+
+        let body = fill_template(&self.escalate_template, sequence, 0);
+        let mut message = Message::new(
+            "Reminder",
+            body,
+            MessagePriority::Urgent,
+            &self.escalate_audience,
+        );
+        let flash = self.flash_light.as_ref().map(|light| Task {
+            title: format!("Flash {light}"),
+            payload: Payload::Command(Command::Light(LightCommand::Flash(FlashPattern::default()))),
+            qos: QoS::ExactlyOnce,
+            retain: Retain::NoRetain,
+            topics: vec![light.get_command_topic("")],
+        });
+        message.flash_lights = flash.is_none();
+        Some((message, flash))
+    }
+}
+
+fn fill_template(template: &str, sequence: &Sequence, minutes: u32) -> String {
+    template
+        .replace("{title}", &sequence.title)
+        .replace("{minutes}", &minutes.to_string())
+}
+
+impl Config {
+    /// Get the timezone for the schedule, falling back to the timezone of the host.
+    #[must_use]
+    pub fn get_timezone(&self) -> chrono_tz::Tz {
+        self.timezone.unwrap_or_else(host_timezone)
+    }
+
+    /// Load a classifier that tags dates the same way as the executor.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the classifier or holiday config cannot be loaded.
+    pub fn load_date_classifier(&self) -> Result<services::scheduler::Classifier, ExecutorError> {
+        let classifier = classifier::load_config(&self.classifications_file)?;
+        let holidays = holidays::load_config(&self.holiday_files)?;
+        Ok(Arc::new(move |date: Date| {
+            classifier::classify_date_with_config(&date, &classifier, &holidays)
+        }))
+    }
 }
 
 const ONE_DAY: TimeDelta = time_delta_constant!(1 days);
@@ -53,13 +275,14 @@ const LAST_OFFSET: TimeDelta = time_delta_constant!(4 days);
 
 struct InternalConfig<T: TimeZone> {
     classifier: Vec<classifier::Config>,
+    holidays: holidays::Holidays,
     scheduler: Vec<scheduler::Config>,
     sequencer: sequencer::ConfigMap,
     extra: Config,
     calendar_to_sequence: Box<CalendarToSequence<T>>,
     timezone: T,
 }
-impl<T: TimeZone + Copy + Sync> InternalConfig<T> {
+impl<T: TimeZone + Display + Copy + Sync> InternalConfig<T> {
     async fn load_calendar(&self, start: Date, stop: Date) -> Vec<Sequence> {
         let calendar = calendar::load(&self.extra.calendar_url, start, stop, self.timezone)
             .await
@@ -90,8 +313,9 @@ impl<T: TimeZone + Copy + Sync> InternalConfig<T> {
 
     fn get_sequences_for_date(&self, date: NaiveDate) -> Vec<Sequence> {
         let tomorrow = date + ONE_DAY;
-        let c_date = classifier::classify_date_with_config(&date, &self.classifier);
-        let c_tomorrow = classifier::classify_date_with_config(&tomorrow, &self.classifier);
+        let c_date = classifier::classify_date_with_config(&date, &self.classifier, &self.holidays);
+        let c_tomorrow =
+            classifier::classify_date_with_config(&tomorrow, &self.classifier, &self.holidays);
 
         let schedule = scheduler::get_schedule_with_config(
             date,
@@ -99,6 +323,7 @@ impl<T: TimeZone + Copy + Sync> InternalConfig<T> {
             &c_tomorrow,
             &self.scheduler,
             &self.timezone,
+            self.extra.location.as_ref(),
         )
         .unwrap_or_else(|e| {
             error!("Error getting schedule for {date}: {e}");
@@ -118,7 +343,8 @@ impl<T: TimeZone + Copy + Sync> InternalConfig<T> {
 
         let tags = NaiveDateIter::new(first_date, last_date)
             .map(|date| {
-                let tags = classifier::classify_date_with_config(&date, &self.classifier);
+                let tags =
+                    classifier::classify_date_with_config(&date, &self.classifier, &self.holidays);
                 TagsForDay { date, tags }
             })
             .collect();
@@ -137,7 +363,12 @@ impl<T: TimeZone + Copy + Sync> InternalConfig<T> {
         let mut sequences = Vec::new();
         sequences.extend(s);
         sequences.extend(calendar);
+        sequencer::resolve_dependencies(&mut sequences);
         sequences.sort_by_key(|s| (s.start_time, s.end_time));
+        let timezone = self.timezone.to_string();
+        for sequence in &mut sequences {
+            sequence.timezone = Some(timezone.clone());
+        }
         sequences
     }
 }
@@ -151,7 +382,7 @@ impl AllMarks {
 
     fn get(&self, sequence: &Sequence) -> Option<Mark> {
         self.0.get(&sequence.id).and_then(|mark| {
-            if mark.start_time <= sequence.start_time && sequence.end_time < mark.end_time {
+            if mark_applies(mark, sequence) {
                 Some(mark.clone())
             } else {
                 None
@@ -159,35 +390,122 @@ impl AllMarks {
         })
     }
 
+    fn list(&self) -> Vec<Mark> {
+        let mut marks: Vec<Mark> = self.0.values().cloned().collect();
+        marks.sort_by(|a, b| (a.start_time, &a.id).cmp(&(b.start_time, &b.id)));
+        marks
+    }
+
+    fn get_by_id(&self, id: &str) -> Option<Mark> {
+        self.0.get(id).cloned()
+    }
+
     fn insert(&mut self, mark: Mark) {
         self.0.insert(mark.id.clone(), mark);
     }
 
+    fn remove(&mut self, id: &str) -> Option<Mark> {
+        self.0.remove(id)
+    }
+
     fn expire(&mut self, now: &DateTime<Utc>) {
         self.0.retain(|_, mark| mark.end_time > *now);
     }
 }
 
-struct AllStatus(HashMap<Date, HashMap<(String, usize), Status>>);
+fn mark_applies(mark: &Mark, sequence: &Sequence) -> bool {
+    // Use the original times, so a postponed sequence stays within its mark.
+    mark.id == sequence.id
+        && mark.start_time <= sequence.original_start_time()
+        && sequence.original_end_time() < mark.end_time
+}
+
+/// Identifies a single task of a sequence in the schedule.
+#[derive(Debug, Clone)]
+struct TaskKey {
+    schedule_date: Date,
+    id: String,
+    repeat_number: usize,
+    index: usize,
+}
+
+impl TaskKey {
+    fn new(sequence: &Sequence, index: usize) -> Self {
+        Self {
+            schedule_date: sequence.schedule_date,
+            id: sequence.id.clone(),
+            repeat_number: sequence.repeat_number,
+            index,
+        }
+    }
+
+    fn matches(&self, sequence: &Sequence) -> bool {
+        sequence.schedule_date == self.schedule_date
+            && sequence.id == self.id
+            && sequence.repeat_number == self.repeat_number
+    }
+}
+
+struct SequenceStatus {
+    status: Status,
+    outcomes: Vec<TaskOutcome>,
+}
+
+struct AllStatus(HashMap<Date, HashMap<(String, usize), SequenceStatus>>);
 
 impl AllStatus {
     fn new() -> Self {
         AllStatus(HashMap::new())
     }
 
-    fn get(&self, sequence: &Sequence) -> Status {
+    fn get_entry(&self, sequence: &Sequence) -> Option<&SequenceStatus> {
         let id = (sequence.id.clone(), sequence.repeat_number);
-        self.0
-            .get(&sequence.schedule_date)
-            .and_then(|m| m.get(&id))
-            .copied()
-            .unwrap_or(Status::Pending)
+        self.0.get(&sequence.schedule_date).and_then(|m| m.get(&id))
     }
 
-    fn insert(&mut self, sequence: &Sequence, status: Status) {
+    fn get_entry_mut(&mut self, sequence: &Sequence) -> &mut SequenceStatus {
         let id = (sequence.id.clone(), sequence.repeat_number);
         let date = sequence.schedule_date;
-        self.0.entry(date).or_default().insert(id, status);
+        self.0
+            .entry(date)
+            .or_default()
+            .entry(id)
+            .or_insert_with(|| SequenceStatus {
+                status: Status::Pending,
+                outcomes: Vec::new(),
+            })
+    }
+
+    fn get(&self, sequence: &Sequence) -> Status {
+        self.get_entry(sequence)
+            .map_or(Status::Pending, |entry| entry.status)
+    }
+
+    fn get_outcomes(&self, sequence: &Sequence) -> Vec<TaskOutcome> {
+        self.get_entry(sequence)
+            .map(|entry| entry.outcomes.clone())
+            .unwrap_or_default()
+    }
+
+    fn insert(&mut self, sequence: &Sequence, status: Status) {
+        self.get_entry_mut(sequence).status = status;
+    }
+
+    fn set_outcomes(&mut self, sequence: &Sequence, outcomes: Vec<TaskOutcome>) {
+        self.get_entry_mut(sequence).outcomes = outcomes;
+    }
+
+    fn set_task_outcome(&mut self, key: &TaskKey, outcome: TaskOutcome) {
+        let id = (key.id.clone(), key.repeat_number);
+        let slot = self
+            .0
+            .get_mut(&key.schedule_date)
+            .and_then(|m| m.get_mut(&id))
+            .and_then(|entry| entry.outcomes.get_mut(key.index));
+
+        if let Some(slot) = slot {
+            *slot = outcome;
+        }
     }
 
     fn expire(&mut self, start: NaiveDate, end: NaiveDate) {
@@ -197,8 +515,10 @@ impl AllStatus {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 enum EventKind {
+    Remind { minutes: u32 },
     Start,
     Stop,
+    Escalate,
 }
 
 #[derive(Debug, Clone)]
@@ -208,15 +528,37 @@ struct Event {
     kind: EventKind,
 }
 
+/// Things the executor sends to the rest of the application.
+struct Outputs {
+    history: mpsc::UnboundedSender<CreateHistoryEntry>,
+}
+
+impl Outputs {
+    fn record(&self, entry: CreateHistoryEntry) {
+        if let Err(err) = self.history.send(entry) {
+            error!(
+                "Failed to record schedule history {:?}: nothing is recording it",
+                err.0
+            );
+        }
+    }
+}
+
 struct State<T: TimeZone> {
     date: Date,
+    started: DateTime<Utc>,
     timer: Instant,
     sequences: Vec<Sequence>,
     events: VecDeque<Event>,
     all_marks: AllMarks,
     config: InternalConfig<T>,
     mqtt: MqttTx,
+    expect_subscriptions: ExpectSubscriptions,
+    commands: mpsc::Sender<ExecutorCommand>,
+    message_sink: stateless::Sender<Message>,
+    outputs: Outputs,
     all_status: AllStatus,
+    runtime_values: runtime::Values,
     calendar_refresh_time: DateTime<Utc>,
     publish_all_hash: Option<ObjectHash>,
     publish_important_hash: Option<ObjectHash>,
@@ -225,7 +567,10 @@ struct State<T: TimeZone> {
 
 const REFRESH_TIME: TimeDelta = time_delta_constant!(5 minutes);
 
-impl<T: TimeZone + Copy + Send + Sync> State<T> {
+/// Stop following dependencies this deep, in case they form a cycle.
+const MAX_DEPENDENCY_DEPTH: u8 = 8;
+
+impl<T: TimeZone + Display + Copy + Send + Sync> State<T> {
     async fn finalize(&mut self, now: &DateTime<Utc>, publish_sequences: bool) {
         let today = now.with_timezone::<T>(&self.config.timezone).date_naive();
 
@@ -359,10 +704,170 @@ impl<T: TimeZone + Copy + Send + Sync> State<T> {
         if let (Some(start), Some(end)) = (start, end) {
             self.all_status.expire(start, end);
         }
+        self.apply_postponements();
+        self.set_events();
+    }
+
+    fn apply_postponements(&mut self) {
+        // Sequences are regenerated without postponements, so this must always be applied.
+        let offsets: Vec<Option<TimeDelta>> = self
+            .sequences
+            .iter()
+            .map(|sequence| self.get_postponement(sequence, 0))
+            .collect();
+
+        for (sequence, offset) in self.sequences.iter_mut().zip(offsets) {
+            sequence.postpone(offset);
+        }
+
+        self.sequences.sort_by_key(|s| (s.start_time, s.end_time));
+    }
+
+    /// Get the postponement of a sequence, including that of the sequence it starts after.
+    ///
+    /// A dependant step already starts when its source sequence ends, so the source's
+    /// postponement moves it too. A step with its own postponement takes the larger of the
+    /// two offsets, rather than their sum, so postponing both by an hour moves it by an hour.
+    fn get_postponement(&self, sequence: &Sequence, depth: u8) -> Option<TimeDelta> {
+        let own = match self.all_marks.get(sequence) {
+            Some(Mark {
+                status: MarkStatus::Postponed { offset },
+                ..
+            }) => Some(offset),
+            _ => None,
+        };
+
+        let inherited = sequence
+            .after
+            .as_ref()
+            .filter(|_| depth < MAX_DEPENDENCY_DEPTH)
+            .and_then(|after| {
+                let same_day = self
+                    .sequences
+                    .iter()
+                    .filter(|s| s.schedule_date == sequence.schedule_date);
+                let sources: HashSet<&String> = same_day
+                    .clone()
+                    .filter(|s| s.id == after.id && s.sequence_name != sequence.sequence_name)
+                    .map(|s| &s.sequence_name)
+                    .collect();
+                same_day
+                    .filter(|s| sources.contains(&s.sequence_name))
+                    .filter_map(|s| self.get_postponement(s, depth + 1))
+                    .max()
+            });
+
+        own.max(inherited)
+    }
+
+    /// Find a sequence this sequence requires that has been cancelled.
+    fn get_cancelled_requirement(&self, sequence: &Sequence, depth: u8) -> Option<&Sequence> {
+        if sequence.requires.is_empty() || depth >= MAX_DEPENDENCY_DEPTH {
+            return None;
+        }
+
+        self.sequences
+            .iter()
+            .filter(|s| {
+                s.schedule_date == sequence.schedule_date && sequence.requires.contains(&s.id)
+            })
+            .find(|s| self.get_status_with_depth(s, depth + 1) == Status::Cancelled)
+    }
+
+    fn create_mark(&mut self, mark: Mark, now: &DateTime<Utc>) -> Result<Mark, RequestError> {
+        if !self.sequences.iter().any(|s| mark_applies(&mark, s)) {
+            return Err(RequestError::SequenceNotFound(mark.id));
+        }
+        info!("Adding {mark}");
+        self.record_mark(&mark, mark.status.to_history(), *now);
+        self.all_marks.insert(mark.clone());
+        self.marks_changed(now);
+        Ok(mark)
+    }
+
+    fn delete_mark(&mut self, id: &str, now: &DateTime<Utc>) -> Result<(), RequestError> {
+        let mark = self
+            .all_marks
+            .remove(id)
+            .ok_or_else(|| RequestError::MarkNotFound(id.to_string()))?;
+        info!("Removing {mark}");
+        self.record_mark(
+            &mark,
+            (HistoryOutcome::MarkRemoved, Some(mark.status.to_string())),
+            *now,
+        );
+        self.marks_changed(now);
+        Ok(())
+    }
+
+    fn marks_changed(&mut self, now: &DateTime<Utc>) {
+        // A sequence that was cancelled may need its events back.
+        self.apply_postponements();
         self.set_events();
+        self.timer = self.get_next_timer(now);
+        self.publish_all_sequences();
+    }
+
+    fn process_command(&mut self, command: ExecutorCommand) {
+        let now = utc_now();
+        // The requester may have given up waiting, so ignore send errors.
+        match command {
+            ExecutorCommand::ListSequences(tx) => {
+                let sequences = self
+                    .sequences
+                    .iter()
+                    .map(|sequence| self.fill_sequence(sequence.clone()))
+                    .collect();
+                let _ = tx.send(sequences);
+            }
+            ExecutorCommand::ListMarks(tx) => {
+                let _ = tx.send(self.all_marks.list());
+            }
+            ExecutorCommand::GetMark(id, tx) => {
+                let _ = tx.send(self.all_marks.get_by_id(&id));
+            }
+            ExecutorCommand::CreateMark(mark, tx) => {
+                let _ = tx.send(self.create_mark(mark, &now));
+            }
+            ExecutorCommand::DeleteMark(id, tx) => {
+                let _ = tx.send(self.delete_mark(&id, &now));
+            }
+            ExecutorCommand::SetTaskOutcome(key, outcome) => {
+                self.task_outcome(&key, outcome);
+            }
+        }
+    }
+
+    fn task_outcome(&mut self, key: &TaskKey, outcome: TaskOutcome) {
+        if let TaskOutcome::Failed { reason } = &outcome {
+            let sequence = self.sequences.iter().find(|s| key.matches(s));
+            let sequence_title = sequence.map_or(key.id.as_str(), |s| s.title.as_str());
+            let task_title = sequence
+                .and_then(|s| s.tasks.get(key.index))
+                .map_or("Unknown task", |t| t.title.as_str());
+
+            error!("Task {task_title} in {sequence_title} failed: {reason}");
+            self.message_sink.try_send(Message::new(
+                "Task Failed",
+                format!("{task_title} in {sequence_title} failed: {reason}"),
+                MessagePriority::Urgent,
+                &self.config.extra.failure_audience,
+            ));
+        }
+
+        if let Some(sequence) = self.sequences.iter().find(|s| key.matches(s)) {
+            self.record_task(sequence, key.index, outcome.to_history(), utc_now());
+        }
+
+        self.all_status.set_task_outcome(key, outcome);
+        self.publish_all_sequences();
     }
 
     fn get_status_for_sequence(&self, sequence: &Sequence) -> Status {
+        self.get_status_with_depth(sequence, 0)
+    }
+
+    fn get_status_with_depth(&self, sequence: &Sequence, depth: u8) -> Status {
         let status = self.all_status.get(sequence);
         let mark = self.all_marks.get(sequence).map(|m| m.status);
 
@@ -373,7 +878,13 @@ impl<T: TimeZone + Copy + Send + Sync> State<T> {
             (Status::Cancelled, _) => Status::Cancelled,
             (Status::Pending, Some(MarkStatus::Done)) => Status::Completed,
             (Status::Pending, Some(MarkStatus::Cancelled)) => Status::Cancelled,
-            (Status::Pending, None) => Status::Pending,
+            (Status::Pending, Some(MarkStatus::Postponed { .. }) | None) => {
+                if self.get_cancelled_requirement(sequence, depth).is_some() {
+                    Status::Cancelled
+                } else {
+                    Status::Pending
+                }
+            }
         }
     }
 
@@ -381,13 +892,33 @@ impl<T: TimeZone + Copy + Send + Sync> State<T> {
         let mut sequence = sequence;
         sequence.mark = self.all_marks.get(&sequence);
         sequence.status = Some(self.get_status_for_sequence(&sequence));
+
+        // Show why a sequence was cancelled by a sequence it requires.
+        if sequence.mark.is_none() && sequence.status == Some(Status::Cancelled) {
+            if let Some(required) = self.get_cancelled_requirement(&sequence, 0) {
+                debug!(
+                    "{} cancelled because {} was cancelled",
+                    sequence.id, required.id
+                );
+                sequence.mark = Some(Mark {
+                    id: sequence.id.clone(),
+                    status: MarkStatus::Cancelled,
+                    start_time: sequence.original_start_time(),
+                    end_time: sequence.original_end_time(),
+                });
+            }
+        }
+
+        sequence.outcomes = self.all_status.get_outcomes(&sequence);
         sequence
     }
 
     fn set_events(&mut self) {
+        let now = utc_now();
         let mut events = Vec::with_capacity(self.sequences.len() * 2);
         for (index, sequence) in self.sequences.iter().enumerate() {
             let status = self.get_status_for_sequence(sequence);
+            events.extend(self.get_reminder_events(sequence, index, status, now));
             // If the sequence is pending, add a start event.
             if matches!(status, Status::Pending) {
                 let start = Event {
@@ -414,10 +945,173 @@ impl<T: TimeZone + Copy + Send + Sync> State<T> {
         self.events = VecDeque::from(events);
     }
 
+    /// Get reminder events that have not happened yet.
+    fn get_reminder_events(
+        &self,
+        sequence: &Sequence,
+        sequence_index: usize,
+        status: Status,
+        now: DateTime<Utc>,
+    ) -> Vec<Event> {
+        self.config
+            .extra
+            .reminders
+            .as_ref()
+            .map(|reminders| reminders.events(sequence, sequence_index, status, now))
+            .unwrap_or_default()
+    }
+
+    fn is_reminded(&self, sequence: &Sequence) -> bool {
+        self.config.extra.reminders.is_some() && sequence.importance >= Importance::High
+    }
+
+    fn send_reminder(&self, sequence: &Sequence, minutes: u32) {
+        let Some(reminders) = &self.config.extra.reminders else {
+            return;
+        };
+        let message = reminders.reminder(sequence, minutes);
+        info!("Reminder for {}: {}", sequence.id, message.body);
+        self.message_sink.try_send(message);
+    }
+
+    fn escalate(&self, sequence: &Sequence) {
+        let Some(reminders) = &self.config.extra.reminders else {
+            return;
+        };
+        let mark = self.all_marks.get(sequence);
+        let status = self.get_status_for_sequence(sequence);
+        let mark_status = mark.as_ref().map(|m| &m.status);
+        let Some((message, flash)) = reminders.escalation(sequence, mark_status, status) else {
+            return;
+        };
+
+        warn!("Escalating {}: {}", sequence.id, message.body);
+        self.message_sink.try_send(message);
+        for message in flash.iter().flat_map(Task::get_mqtt_messages) {
+            self.mqtt.try_send(message);
+        }
+    }
+
+    fn record_task(
+        &self,
+        sequence: &Sequence,
+        index: usize,
+        (outcome, detail): (HistoryOutcome, Option<String>),
+        now: DateTime<Utc>,
+    ) {
+        let Some(task) = sequence.tasks.get(index) else {
+            return;
+        };
+        self.outputs.record(CreateHistoryEntry {
+            time: now,
+            sequence_id: sequence.id.clone(),
+            sequence_title: sequence.title.clone(),
+            schedule_date: Some(sequence.schedule_date),
+            repeat_number: Some(sequence.repeat_number),
+            task: Some(HistoryTask {
+                index,
+                title: task.title.clone(),
+                topics: task.topics.clone(),
+                payload: task.get_mqtt_payload(),
+            }),
+            outcome,
+            detail,
+        });
+    }
+
+    /// Record every task in the sequence with the same outcome.
+    fn record_tasks(&self, sequence: &Sequence, outcome: HistoryOutcome, now: DateTime<Utc>) {
+        for index in 0..sequence.tasks.len() {
+            self.record_task(sequence, index, (outcome, None), now);
+        }
+    }
+
+    fn record_mark(
+        &self,
+        mark: &Mark,
+        (outcome, detail): (HistoryOutcome, Option<String>),
+        now: DateTime<Utc>,
+    ) {
+        let sequence = self.sequences.iter().find(|s| mark_applies(mark, s));
+        self.outputs.record(CreateHistoryEntry {
+            time: now,
+            sequence_id: mark.id.clone(),
+            sequence_title: sequence.map_or_else(|| mark.id.clone(), |s| s.title.clone()),
+            schedule_date: sequence.map(|s| s.schedule_date),
+            repeat_number: sequence.map(|s| s.repeat_number),
+            task: None,
+            outcome,
+            detail,
+        });
+    }
+
+    fn start_sequence(&self, sequence: &Sequence, now: DateTime<Utc>) -> Vec<TaskOutcome> {
+        let ctx = self.runtime_values.build_context(&now);
+
+        let sequence_check = sequence
+            .run_if
+            .as_ref()
+            .map_or(Ok(()), |run_if| check_conditions(run_if, &ctx));
+
+        sequence
+            .tasks
+            .iter()
+            .enumerate()
+            .map(|(index, task)| {
+                let task_check = sequence_check.clone().and_then(|()| {
+                    sequence
+                        .task_run_if
+                        .get(index)
+                        .and_then(Option::as_ref)
+                        .map_or(Ok(()), |run_if| check_conditions(run_if, &ctx))
+                });
+
+                let expect = sequence.task_expect.get(index).and_then(Option::as_ref);
+
+                match (task_check, expect) {
+                    (Ok(()), Some(expect)) => {
+                        debug!("{now:?}: Sending task {task:?} expecting {expect:?}");
+                        spawn(confirm_task(
+                            self.mqtt.clone(),
+                            self.expect_subscriptions.clone(),
+                            task.clone(),
+                            expect.clone(),
+                            TaskKey::new(sequence, index),
+                            self.commands.clone(),
+                        ));
+                        TaskOutcome::Sent
+                    }
+                    (Ok(()), None) => {
+                        for message in task.get_mqtt_messages() {
+                            debug!("{now:?}: Sending task {message:?}");
+                            self.mqtt.try_send(message);
+                        }
+                        TaskOutcome::Sent
+                    }
+                    (Err(reason), _) => {
+                        info!("{now:?}: Skipping task {task:?}: {reason}");
+                        TaskOutcome::Skipped { reason }
+                    }
+                }
+            })
+            .collect()
+    }
+
     #[must_use]
     #[allow(clippy::cognitive_complexity)]
     fn process_event(&mut self, event: &Event, now: DateTime<Utc>) -> bool {
         match event.kind {
+            EventKind::Remind { minutes } => {
+                let sequence = &self.sequences[event.sequence_index];
+                if self.get_status_for_sequence(sequence) == Status::Pending {
+                    self.send_reminder(sequence, minutes);
+                }
+                false
+            }
+            EventKind::Escalate => {
+                self.escalate(&self.sequences[event.sequence_index]);
+                false
+            }
             EventKind::Start => {
                 let sequence = &self.sequences[event.sequence_index];
                 let status = self.get_status_for_sequence(sequence);
@@ -427,19 +1121,32 @@ impl<T: TimeZone + Copy + Send + Sync> State<T> {
                         sequence = sequence.id,
                         status = status
                     );
+                    if status == Status::Cancelled {
+                        self.record_tasks(sequence, HistoryOutcome::Cancelled, now);
+                    }
                     false
                 } else if now > sequence.latest_time {
+                    if was_due_while_running(sequence, self.started) {
+                        self.record_tasks(sequence, HistoryOutcome::Missed, now);
+                    } else {
+                        debug!(
+                            "Not recording {sequence:?} as missed, it was due before the executor started",
+                            sequence = sequence.id
+                        );
+                    }
                     self.all_status.insert(sequence, Status::InProgress);
                     true
                 } else {
                     info!("Starting {sequence:?}");
-                    for task in &sequence.tasks {
-                        for message in task.get_mqtt_messages() {
-                            debug!("{now:?}: Sending task {message:?}");
-                            self.mqtt.try_send(message.clone());
-                        }
+                    if self.is_reminded(sequence) {
+                        self.send_reminder(sequence, 0);
+                    }
+                    let outcomes = self.start_sequence(sequence, now);
+                    for (index, outcome) in outcomes.iter().enumerate() {
+                        self.record_task(sequence, index, outcome.to_history(), now);
                     }
                     self.all_status.insert(sequence, Status::InProgress);
+                    self.all_status.set_outcomes(sequence, outcomes);
                     true
                 }
             }
@@ -462,6 +1169,104 @@ impl<T: TimeZone + Copy + Send + Sync> State<T> {
     }
 }
 
+/// One subscription per expected state topic, shared by every task.
+#[derive(Clone, Default)]
+struct ExpectSubscriptions(
+    Arc<tokio::sync::Mutex<HashMap<String, generic::Receiver<MqttMessage>>>>,
+);
+
+impl ExpectSubscriptions {
+    async fn get(
+        &self,
+        mqtt: &MqttTx,
+        topic: &str,
+    ) -> Result<generic::Receiver<MqttMessage>, SubscribeError> {
+        let mut subscriptions = self.0.lock().await;
+        if let Some(rx) = subscriptions.get(topic) {
+            return Ok(rx.clone());
+        }
+        let rx = mqtt.subscribe(topic).await?;
+        subscriptions.insert(topic.to_string(), rx.clone());
+        Ok(rx)
+    }
+}
+
+/// Send a task, resending it until the expected state is seen or the retries run out.
+async fn confirm_task(
+    mqtt: MqttTx,
+    subscriptions: ExpectSubscriptions,
+    task: Task,
+    expect: Expectation,
+    key: TaskKey,
+    commands: mpsc::Sender<ExecutorCommand>,
+) {
+    let outcome = match send_and_confirm(&mqtt, &subscriptions, &task, &expect).await {
+        Ok(()) => TaskOutcome::Confirmed,
+        Err(reason) => TaskOutcome::Failed { reason },
+    };
+
+    if commands
+        .send(ExecutorCommand::SetTaskOutcome(key, outcome))
+        .await
+        .is_err()
+    {
+        error!("Executor stopped before task {} was confirmed", task.title);
+    }
+}
+
+async fn send_and_confirm(
+    mqtt: &MqttTx,
+    subscriptions: &ExpectSubscriptions,
+    task: &Task,
+    expect: &Expectation,
+) -> Result<(), String> {
+    // Subscribe before sending, so we cannot miss the state change. The last
+    // known value, which may be retained, is replayed first. It counts, as a
+    // device that is already in the expected state will not publish a change.
+    let rx = subscriptions
+        .get(mqtt, &expect.topic)
+        .await
+        .map_err(|err| format!("Failed to subscribe to {}: {err}", expect.topic))?;
+    let mut s = rx.subscribe().await;
+
+    let attempts = u16::from(expect.retries) + 1;
+
+    for attempt in 1..=attempts {
+        if attempt > 1 {
+            info!(
+                "Resending task {}, attempt {attempt} of {attempts}",
+                task.title
+            );
+        }
+
+        for message in task.get_mqtt_messages() {
+            debug!("Sending task {message:?}");
+            mqtt.try_send(message);
+        }
+
+        let wait = async {
+            loop {
+                match s.recv().await {
+                    Ok(msg) if String::try_from(msg).is_ok_and(|v| v == expect.value) => {
+                        return Ok(());
+                    }
+                    Ok(_) => {}
+                    Err(err) => return Err(format!("Lost {}: {err}", expect.topic)),
+                }
+            }
+        };
+
+        if let Ok(result) = tokio::time::timeout(expect.timeout, wait).await {
+            return result;
+        }
+    }
+
+    Err(format!(
+        "{} did not become {} after {attempts} attempts",
+        expect.topic, expect.value
+    ))
+}
+
 /// An error occurred in the executor.
 #[derive(Error, Debug)]
 pub enum ExecutorError {
@@ -469,6 +1274,10 @@ pub enum ExecutorError {
     #[error("Classifier Config Error: {0}")]
     ClassifierConfigError(#[from] classifier::ConfigError),
 
+    /// A holidays config error occurred.
+    #[error("Holidays Config Error: {0}")]
+    HolidaysConfigError(#[from] holidays::ConfigError),
+
     /// A Scheduler config error occurred.
     #[error("Scheduler Config Error: {0}")]
     SchedulerConfigError(#[from] scheduler::ConfigError),
@@ -482,24 +1291,152 @@ pub enum ExecutorError {
     SequencerConfigCheckError(#[from] sequencer::ConfigCheckError),
 }
 
+/// An error processing a request sent to the executor.
+#[derive(Error, Debug)]
+pub enum RequestError {
+    /// The executor is not running.
+    #[error("Executor is not running")]
+    NotRunning,
+
+    /// The executor did not reply.
+    #[error("Receive error: {0}")]
+    ReceiveError(#[from] oneshot::error::RecvError),
+
+    /// There is no sequence in the current schedule that the mark applies to.
+    #[error("No sequence {0} found in schedule")]
+    SequenceNotFound(String),
+
+    /// There is no mark for the sequence.
+    #[error("No mark found for {0}")]
+    MarkNotFound(String),
+}
+
+#[derive(Debug)]
+enum ExecutorCommand {
+    ListSequences(oneshot::Sender<Vec<Sequence>>),
+    ListMarks(oneshot::Sender<Vec<Mark>>),
+    GetMark(String, oneshot::Sender<Option<Mark>>),
+    CreateMark(Mark, oneshot::Sender<Result<Mark, RequestError>>),
+    DeleteMark(String, oneshot::Sender<Result<(), RequestError>>),
+    SetTaskOutcome(TaskKey, TaskOutcome),
+}
+
+/// Struct used to send requests to the executor.
+#[derive(Clone)]
+pub struct ExecutorTx {
+    commands: mpsc::Sender<ExecutorCommand>,
+}
+
+impl ExecutorTx {
+    async fn request<R>(
+        &self,
+        command: impl FnOnce(oneshot::Sender<R>) -> ExecutorCommand + Send,
+    ) -> Result<R, RequestError>
+    where
+        R: Send,
+    {
+        let (tx, rx) = oneshot::channel();
+        self.commands
+            .send(command(tx))
+            .await
+            .map_err(|_| RequestError::NotRunning)?;
+        Ok(rx.await?)
+    }
+
+    /// Get all sequences in the current schedule, with status and marks filled in.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the executor is not running.
+    pub async fn list_sequences(&self) -> Result<Vec<Sequence>, RequestError> {
+        self.request(ExecutorCommand::ListSequences).await
+    }
+
+    /// Get all current marks.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the executor is not running.
+    pub async fn list_marks(&self) -> Result<Vec<Mark>, RequestError> {
+        self.request(ExecutorCommand::ListMarks).await
+    }
+
+    /// Get the mark for a sequence id.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the executor is not running or there is no mark.
+    pub async fn get_mark(&self, id: impl Into<String> + Send) -> Result<Mark, RequestError> {
+        let id = id.into();
+        self.request(|tx| ExecutorCommand::GetMark(id.clone(), tx))
+            .await?
+            .ok_or(RequestError::MarkNotFound(id))
+    }
+
+    /// Add or replace the mark for a sequence id.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the executor is not running or the mark does not apply to any
+    /// sequence in the current schedule.
+    pub async fn create_mark(&self, mark: Mark) -> Result<Mark, RequestError> {
+        self.request(|tx| ExecutorCommand::CreateMark(mark, tx))
+            .await?
+    }
+
+    /// Remove the mark for a sequence id.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the executor is not running or there is no mark.
+    pub async fn delete_mark(&self, id: impl Into<String> + Send) -> Result<(), RequestError> {
+        let id = id.into();
+        self.request(|tx| ExecutorCommand::DeleteMark(id, tx))
+            .await?
+    }
+}
+
 /// Create a timer that sends outgoing messages at regularly spaced intervals.
 ///
+/// Returns a handle that can be used to send requests to the executor.
+///
+/// Tasks executed and marks changed are sent to `history`, for recording the schedule history.
+///
 /// # Errors
 ///
 /// This function will return an error if the `config` is invalid.
-pub fn executor<T: TimeZone + Copy + Send + Sync + 'static>(
+pub fn executor<T: TimeZone + Display + Copy + Send + Sync + 'static>(
     subscriptions: &mut Subscriptions,
     mqtt: MqttTx,
     extra_config: Config,
     calendar_to_sequence: Box<CalendarToSequence<T>>,
     timezone: T,
-) -> Result<(), ExecutorError> {
-    let mut state = get_initial_state(mqtt, extra_config, calendar_to_sequence, timezone)?;
+    inputs: runtime::Inputs,
+    message_sink: stateless::Sender<Message>,
+    history: mpsc::UnboundedSender<CreateHistoryEntry>,
+) -> Result<ExecutorTx, ExecutorError> {
+    let (tx, mut rx) = mpsc::channel(10);
+    let outputs = Outputs { history };
+    let mut state = get_initial_state(
+        mqtt,
+        tx.clone(),
+        message_sink,
+        outputs,
+        extra_config,
+        calendar_to_sequence,
+        timezone,
+    )?;
     let mark_rx = subscriptions.subscribe_into_stateless::<Json<Mark>>("mark");
 
     spawn(async move {
         let mut mark_s = mark_rx.subscribe().await;
 
+        // Subscribe here, so the current values are known before any sequence can start.
+        let mut inputs_s = inputs.subscribe().await;
+        while let Ok((name, value)) = inputs_s.try_recv() {
+            state.runtime_values.set(name, value);
+        }
+
         state.set_tags(state.date);
         state.set_sequences_all().await;
         // Don't do this here, will happen after first timer.
@@ -545,17 +1482,30 @@ pub fn executor<T: TimeZone + Copy + Send + Sync + 'static>(
                     }
                 },
                 Ok(Json(mark)) = mark_s.recv() => {
+                    let now = utc_now();
+                    info!("Adding {mark}");
+                    state.record_mark(&mark, mark.status.to_history(), now);
                     state.all_marks.insert(mark);
+                    state.marks_changed(&now);
+                },
+                Some((name, value)) = inputs_s.recv() => {
+                    state.runtime_values.set(name, value);
+                },
+                Some(command) = rx.recv() => {
+                    state.process_command(command);
                 },
             }
         }
     });
 
-    Ok(())
+    Ok(ExecutorTx { commands: tx })
 }
 
 fn get_initial_state<T: TimeZone + Copy + 'static>(
     mqtt: MqttTx,
+    commands: mpsc::Sender<ExecutorCommand>,
+    message_sink: stateless::Sender<Message>,
+    outputs: Outputs,
     extra_config: Config,
     calendar_to_sequence: Box<CalendarToSequence<T>>,
     timezone: T,
@@ -566,11 +1516,13 @@ fn get_initial_state<T: TimeZone + Copy + 'static>(
     let state = {
         let config = {
             let classifier = classifier::load_config(&extra_config.classifications_file)?;
+            let holidays = holidays::load_config(&extra_config.holiday_files)?;
             let scheduler = scheduler::load_config(&extra_config.schedule_file)?;
             let sequencer = sequencer::load_config(&extra_config.sequences_file)?;
             check_schedule(&scheduler, &sequencer)?;
             InternalConfig {
                 classifier,
+                holidays,
                 scheduler,
                 sequencer,
                 extra: extra_config,
@@ -583,12 +1535,18 @@ fn get_initial_state<T: TimeZone + Copy + 'static>(
 
         State {
             date,
+            started: now,
             timer,
             sequences: Vec::new(),
             events: VecDeque::new(),
             config,
             mqtt,
+            expect_subscriptions: ExpectSubscriptions::default(),
+            commands,
+            message_sink,
+            outputs,
             all_status: AllStatus::new(),
+            runtime_values: runtime::Values::new(),
             all_marks: AllMarks::new(),
             calendar_refresh_time: now,
             publish_all_hash: None,
@@ -605,6 +1563,14 @@ fn get_initial_state<T: TimeZone + Copy + 'static>(
     Ok(state)
 }
 
+/// Was the sequence due while the executor was running?
+///
+/// Status is only kept in memory, so sequences due before a restart look like they never ran.
+/// They may have, and the history already has what really happened.
+fn was_due_while_running(sequence: &Sequence, started: DateTime<Utc>) -> bool {
+    sequence.start_time >= started
+}
+
 #[derive(PartialEq, Eq, Copy, Clone)]
 struct ObjectHash(u64);
 
@@ -615,3 +1581,313 @@ impl ObjectHash {
         Self(s.finish())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::services::mqtt::{mqtt_channel, run_test_broker};
+
+    use super::*;
+
+    fn task() -> Task {
+        Task {
+            title: "Turn on the pump".to_string(),
+            payload: Payload::String("on".to_string()),
+            qos: QoS::ExactlyOnce,
+            retain: Retain::NoRetain,
+            topics: vec!["command/pump".to_string()],
+        }
+    }
+
+    fn expect(retries: u8) -> Expectation {
+        Expectation {
+            topic: "state/pump".to_string(),
+            value: "on".to_string(),
+            timeout: Duration::from_secs(10),
+            retries,
+        }
+    }
+
+    fn state(value: &str, retain: Retain) -> MqttMessage {
+        MqttMessage::new("state/pump", value.to_string(), retain, QoS::ExactlyOnce)
+    }
+
+    /// Start a broker where the device only answers after `ignore` commands.
+    fn start_broker(retained: Vec<MqttMessage>, ignore: usize) -> (MqttTx, Arc<AtomicUsize>) {
+        let (mqtt, mqtt_rx) = mqtt_channel();
+        let sent = Arc::new(AtomicUsize::new(0));
+        let sent_clone = sent.clone();
+        run_test_broker(mqtt_rx, retained, move |msg| {
+            assert_eq!(msg.topic, "command/pump");
+            let count = sent_clone.fetch_add(1, Ordering::SeqCst) + 1;
+            if count > ignore {
+                vec![state("on", Retain::NoRetain)]
+            } else {
+                vec![]
+            }
+        });
+        (mqtt, sent)
+    }
+
+    fn start_time() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 18, 6, 30, 0).unwrap()
+    }
+
+    fn sequence(importance: Importance, reminders: Option<sequencer::Reminders>) -> Sequence {
+        let end_time = start_time() + TimeDelta::minutes(15);
+        Sequence {
+            title: "Take medicine".to_string(),
+            id: "medicine".to_string(),
+            schedule_date: NaiveDate::from_ymd_opt(2026, 10, 18).unwrap(),
+            importance,
+            sequence_name: "medicine".to_string(),
+            if_cond: None,
+            run_if: None,
+            task_run_if: vec![None],
+            task_expect: vec![None],
+            classifications: None,
+            options: None,
+            zero_time: false,
+            start_time: start_time(),
+            end_time,
+            duration: Duration::from_secs(15 * 60),
+            latest_time: end_time,
+            original_start_time: None,
+            repeat_number: 1,
+            timezone: None,
+            after: None,
+            requires: vec![],
+            reminders,
+            tasks: vec![task()],
+            status: None,
+            mark: None,
+            outcomes: vec![],
+        }
+    }
+
+    fn reminder_config(flash_light: Option<IdWithRoom>) -> ReminderConfig {
+        ReminderConfig {
+            audience: Audience::new("family"),
+            minutes_before: vec![30, 10, 0],
+            before_template: default_before_template(),
+            start_template: default_start_template(),
+            escalate_after: Some(Duration::from_secs(20 * 60)),
+            escalate_template: default_escalate_template(),
+            escalate_audience: Audience::new("parents"),
+            flash_light,
+        }
+    }
+
+    fn event_times(events: &[Event]) -> Vec<(i64, EventKind)> {
+        events
+            .iter()
+            .map(|e| ((e.datetime - start_time()).num_minutes(), e.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_was_due_while_running() {
+        let sequence = sequence(Importance::Medium, None);
+
+        // Restarting after the sequence was due doesn't know what happened to it.
+        let restarted = start_time() + TimeDelta::hours(1);
+        assert!(!was_due_while_running(&sequence, restarted));
+
+        let started = start_time() - TimeDelta::hours(1);
+        assert!(was_due_while_running(&sequence, started));
+    }
+
+    #[test]
+    fn test_reminder_events() {
+        let config = reminder_config(None);
+        let sequence = sequence(Importance::High, None);
+        let before = start_time() - TimeDelta::hours(1);
+
+        // The reminder at the start is sent by the start event.
+        let events = config.events(&sequence, 3, Status::Pending, before);
+        assert_eq!(
+            event_times(&events),
+            vec![
+                (-30, EventKind::Remind { minutes: 30 }),
+                (-10, EventKind::Remind { minutes: 10 }),
+                (20, EventKind::Escalate),
+            ]
+        );
+        assert!(events.iter().all(|e| e.sequence_index == 3));
+
+        // Reminders in the past have already been sent.
+        let now = start_time() - TimeDelta::minutes(20);
+        let events = config.events(&sequence, 3, Status::Pending, now);
+        assert_eq!(
+            event_times(&events),
+            vec![
+                (-10, EventKind::Remind { minutes: 10 }),
+                (20, EventKind::Escalate),
+            ]
+        );
+
+        // Once started only the escalation is left.
+        let events = config.events(&sequence, 3, Status::InProgress, before);
+        assert_eq!(event_times(&events), vec![(20, EventKind::Escalate)]);
+
+        let events = config.events(&sequence, 3, Status::Cancelled, before);
+        assert!(events.is_empty());
+
+        let sequence = self::sequence(Importance::Medium, None);
+        let events = config.events(&sequence, 3, Status::Pending, before);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_reminder_events_override() {
+        let config = reminder_config(None);
+        let reminders = sequencer::Reminders {
+            minutes_before: Some(vec![5]),
+            escalate_after: Some(Duration::from_secs(60 * 60)),
+        };
+        let sequence = sequence(Importance::High, Some(reminders));
+        let now = start_time() - TimeDelta::hours(1);
+
+        let events = config.events(&sequence, 0, Status::Pending, now);
+        assert_eq!(
+            event_times(&events),
+            vec![
+                (-5, EventKind::Remind { minutes: 5 }),
+                (60, EventKind::Escalate),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reminder() {
+        let config = reminder_config(None);
+        let sequence = sequence(Importance::High, None);
+
+        let message = config.reminder(&sequence, 10);
+        assert_eq!(message.body, "Take medicine in 10 minutes");
+        assert_eq!(message.audience, Audience::new("family"));
+
+        let message = config.reminder(&sequence, 0);
+        assert_eq!(message.body, "Time for Take medicine");
+    }
+
+    #[test]
+    fn test_escalation() {
+        let config = reminder_config(None);
+        let sequence = sequence(Importance::High, None);
+
+        let (message, flash) = config
+            .escalation(&sequence, None, Status::InProgress)
+            .unwrap();
+        assert_eq!(message.body, "Take medicine has not been done");
+        assert_eq!(message.audience, Audience::new("parents"));
+        assert_eq!(message.priority, MessagePriority::Urgent);
+        assert!(message.flash_lights);
+        assert!(flash.is_none());
+
+        let postponed = MarkStatus::Postponed {
+            offset: TimeDelta::minutes(10),
+        };
+        assert!(config
+            .escalation(&sequence, Some(&postponed), Status::Pending)
+            .is_some());
+        assert!(config
+            .escalation(&sequence, Some(&MarkStatus::Done), Status::InProgress)
+            .is_none());
+        assert!(config
+            .escalation(&sequence, Some(&MarkStatus::Cancelled), Status::Pending)
+            .is_none());
+        assert!(config
+            .escalation(&sequence, None, Status::Cancelled)
+            .is_none());
+    }
+
+    #[test]
+    fn test_escalation_flash_light() {
+        let light = IdWithRoom {
+            room: "kitchen".to_string(),
+            device: "ceiling".to_string(),
+        };
+        let config = reminder_config(Some(light));
+        let sequence = sequence(Importance::High, None);
+
+        // The configured light is flashed instead of asking the message to flash.
+        let (message, flash) = config
+            .escalation(&sequence, None, Status::InProgress)
+            .unwrap();
+        assert!(!message.flash_lights);
+        // It goes to the light over MQTT like any other task, and only once.
+        let messages = flash.unwrap().get_mqtt_messages();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].topic, "robotica/command/kitchen/ceiling");
+        let command: Command = serde_json::from_slice(&messages[0].payload).unwrap();
+        assert!(matches!(command, Command::Light(LightCommand::Flash(_))));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_send_and_confirm() {
+        let (mqtt, sent) = start_broker(vec![], 0);
+        let result =
+            send_and_confirm(&mqtt, &ExpectSubscriptions::default(), &task(), &expect(2)).await;
+        assert_eq!(result, Ok(()));
+        assert_eq!(sent.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_send_and_confirm_retries() {
+        let (mqtt, sent) = start_broker(vec![], 2);
+        let result =
+            send_and_confirm(&mqtt, &ExpectSubscriptions::default(), &task(), &expect(2)).await;
+        assert_eq!(result, Ok(()));
+        assert_eq!(sent.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_send_and_confirm_timeout() {
+        let (mqtt, sent) = start_broker(vec![], usize::MAX);
+        let start = Instant::now();
+        let result =
+            send_and_confirm(&mqtt, &ExpectSubscriptions::default(), &task(), &expect(1)).await;
+        assert_eq!(
+            result,
+            Err("state/pump did not become on after 2 attempts".to_string())
+        );
+        assert_eq!(sent.load(Ordering::SeqCst), 2);
+        assert!(start.elapsed() >= Duration::from_secs(20));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_send_and_confirm_retained() {
+        // The device is already on, so it never reports a change.
+        let retained = vec![state("on", Retain::Retain)];
+        let (mqtt, sent) = start_broker(retained, usize::MAX);
+        let result =
+            send_and_confirm(&mqtt, &ExpectSubscriptions::default(), &task(), &expect(0)).await;
+        assert_eq!(result, Ok(()));
+        assert_eq!(sent.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_send_and_confirm_reuses_subscription() {
+        // The device only reports a change for the first command.
+        let (mqtt, mqtt_rx) = mqtt_channel();
+        let sent = Arc::new(AtomicUsize::new(0));
+        let sent_clone = sent.clone();
+        run_test_broker(mqtt_rx, vec![], move |_| {
+            if sent_clone.fetch_add(1, Ordering::SeqCst) == 0 {
+                vec![state("on", Retain::NoRetain)]
+            } else {
+                vec![]
+            }
+        });
+        let subscriptions = ExpectSubscriptions::default();
+        for _ in 0..2 {
+            let result = send_and_confirm(&mqtt, &subscriptions, &task(), &expect(0)).await;
+            assert_eq!(result, Ok(()));
+        }
+        assert_eq!(sent.load(Ordering::SeqCst), 2);
+        assert_eq!(subscriptions.0.lock().await.len(), 1);
+    }
+}
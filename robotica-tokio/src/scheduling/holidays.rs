@@ -0,0 +1,498 @@
+//! Public holidays and other dates defined by rules.
+//!
+//! Holidays are loaded from YAML files, typically one per region, and add tags to the
+//! classification of a date before the classifier config is applied. The Victorian public
+//! holidays are in `holidays/au_vic.yaml`.
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use chrono::{Datelike, TimeDelta};
+use serde::Deserialize;
+use thiserror::Error;
+
+use robotica_common::datetime::{Date, Weekday};
+
+/// Get the date of Easter Sunday for the given year.
+///
+/// Uses the anonymous Gregorian algorithm.
+#[must_use]
+pub fn easter_sunday(year: i32) -> Option<Date> {
+    let golden = year.rem_euclid(19);
+    let century = year.div_euclid(100);
+    let year_of_century = year.rem_euclid(100);
+    let leap_centuries = century / 4;
+    let correction = (century + 8) / 25;
+    let moon_correction = (century - correction + 1) / 3;
+    let epact = (19 * golden + century - leap_centuries - moon_correction + 15).rem_euclid(30);
+    let weekday_correction =
+        (32 + 2 * (century % 4) + 2 * (year_of_century / 4) - epact - year_of_century % 4)
+            .rem_euclid(7);
+    let adjust = (golden + 11 * epact + 22 * weekday_correction) / 451;
+    let value = epact + weekday_correction - 7 * adjust + 114;
+
+    let month = u32::try_from(value / 31).ok()?;
+    let day = u32::try_from(value % 31 + 1).ok()?;
+    Date::from_ymd_opt(year, month, day)
+}
+
+/// Get the nth weekday of a month, e.g. the second Monday in June.
+#[must_use]
+pub const fn nth_weekday_of_month(year: i32, month: u32, weekday: Weekday, n: u8) -> Option<Date> {
+    Date::from_weekday_of_month_opt(year, month, weekday, n)
+}
+
+/// Get the last weekday of a month, e.g. the last Monday in May.
+#[must_use]
+pub fn last_weekday_of_month(year: i32, month: u32, weekday: Weekday) -> Option<Date> {
+    let first_of_next = if month == 12 {
+        Date::from_ymd_opt(year + 1, 1, 1)
+    } else {
+        Date::from_ymd_opt(year, month + 1, 1)
+    }?;
+    let last = first_of_next.pred_opt()?;
+    let days_back =
+        (7 + last.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+    last.checked_sub_signed(TimeDelta::days(i64::from(days_back)))
+}
+
+/// Is the date the nth occurrence of its weekday in its month?
+#[must_use]
+pub fn is_nth_weekday(date: Date, n: u32, weekday: Weekday) -> bool {
+    date.weekday() == weekday && (date.day() - 1) / 7 + 1 == n
+}
+
+/// Is the date the last occurrence of its weekday in its month?
+#[must_use]
+pub fn is_last_weekday(date: Date, weekday: Weekday) -> bool {
+    date.weekday() == weekday
+        && date
+            .checked_add_signed(TimeDelta::days(7))
+            .is_none_or(|next| next.month() != date.month())
+}
+
+/// Get the parity of the ISO week number, either 0 or 1.
+///
+/// In years with 53 ISO weeks, week 53 and the following week 1 are both odd, so use
+/// [`fortnight_parity`] for things that happen every second week.
+#[must_use]
+pub fn week_parity(date: Date) -> i64 {
+    i64::from(date.iso_week().week() % 2)
+}
+
+/// Get which week of a fortnight the date is in, either 0 or 1.
+///
+/// Weeks start on Monday and are counted from 0001-01-01, which was a Monday, so the
+/// parity alternates every week, including between years.
+#[must_use]
+pub fn fortnight_parity(date: Date) -> i64 {
+    i64::from(date.num_days_from_ce() - 1).div_euclid(7) % 2
+}
+
+/// An inclusive range of dates.
+#[derive(Deserialize, Debug, Clone)]
+pub struct DateRange {
+    start: Date,
+    stop: Date,
+}
+
+/// A rule for when a holiday occurs.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum Rule {
+    /// The same day every year.
+    Fixed {
+        /// The month, 1 to 12.
+        month: u32,
+        /// The day of the month.
+        day: u32,
+    },
+
+    /// A number of days relative to Easter Sunday.
+    Easter {
+        /// The number of days after Easter Sunday, may be negative.
+        offset: i64,
+    },
+
+    /// The nth weekday of a month.
+    NthWeekday {
+        /// The month, 1 to 12.
+        month: u32,
+        /// Which occurrence of the weekday, starting at 1.
+        n: u8,
+        /// The day of the week.
+        weekday: Weekday,
+    },
+
+    /// The last weekday of a month.
+    LastWeekday {
+        /// The month, 1 to 12.
+        month: u32,
+        /// The day of the week.
+        weekday: Weekday,
+    },
+
+    /// A list of explicit date ranges, for example school terms.
+    Ranges {
+        /// The date ranges.
+        ranges: Vec<DateRange>,
+    },
+}
+
+impl Rule {
+    fn date_for_year(&self, year: i32) -> Option<Date> {
+        match self {
+            Self::Fixed { month, day } => Date::from_ymd_opt(year, *month, *day),
+            Self::Easter { offset } => {
+                easter_sunday(year)?.checked_add_signed(TimeDelta::days(*offset))
+            }
+            Self::NthWeekday { month, n, weekday } => {
+                nth_weekday_of_month(year, *month, *weekday, *n)
+            }
+            Self::LastWeekday { month, weekday } => last_weekday_of_month(year, *month, *weekday),
+            Self::Ranges { .. } => None,
+        }
+    }
+}
+
+/// A holiday definition.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Holiday {
+    /// The name of the holiday, added as a tag on the day.
+    pub name: String,
+
+    /// When the holiday occurs.
+    pub rule: Rule,
+
+    /// Extra tags to add, for example `public_holiday`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// If the holiday falls on a weekend, is a substitute day given on the next free weekday?
+    ///
+    /// The substitute day gets the extra tags and a `<name>_substitute` tag.
+    #[serde(default)]
+    pub substitute: bool,
+}
+
+/// A set of holiday definitions.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Holidays(Vec<Holiday>);
+
+impl Holidays {
+    /// Get every tag that any holiday can add.
+    #[must_use]
+    pub fn all_tags(&self) -> HashSet<String> {
+        let mut tags = HashSet::new();
+
+        for holiday in &self.0 {
+            tags.insert(holiday.name.clone());
+            tags.extend(holiday.tags.iter().cloned());
+            if holiday.substitute {
+                tags.insert(format!("{}_substitute", holiday.name));
+            }
+        }
+
+        tags
+    }
+
+    /// Get the tags for all holidays on the given date.
+    #[must_use]
+    pub fn tags_for_date(&self, date: Date) -> HashSet<String> {
+        let mut tags = HashSet::new();
+
+        for holiday in &self.0 {
+            if let Rule::Ranges { ranges } = &holiday.rule {
+                if ranges.iter().any(|r| r.start <= date && date <= r.stop) {
+                    tags.insert(holiday.name.clone());
+                    tags.extend(holiday.tags.iter().cloned());
+                }
+            }
+        }
+
+        // A substitute day can fall in the following year.
+        for year in [date.year() - 1, date.year()] {
+            for (holiday_date, holiday, substitute) in self.dates_for_year(year) {
+                if holiday_date != date {
+                    continue;
+                }
+                if substitute {
+                    tags.insert(format!("{}_substitute", holiday.name));
+                } else {
+                    tags.insert(holiday.name.clone());
+                }
+                tags.extend(holiday.tags.iter().cloned());
+            }
+        }
+
+        tags
+    }
+
+    fn dates_for_year(&self, year: i32) -> Vec<(Date, &Holiday, bool)> {
+        let mut dates: Vec<(Date, &Holiday, bool)> = self
+            .0
+            .iter()
+            .filter_map(|holiday| {
+                let date = holiday.rule.date_for_year(year)?;
+                Some((date, holiday, false))
+            })
+            .collect();
+
+        // Substitute days are allocated in order, skipping days taken by another holiday.
+        let mut taken: HashSet<Date> = dates.iter().map(|(date, _, _)| *date).collect();
+
+        let weekend: Vec<(Date, &Holiday)> = dates
+            .iter()
+            .filter(|(date, holiday, _)| holiday.substitute && is_weekend(*date))
+            .map(|(date, holiday, _)| (*date, *holiday))
+            .collect();
+
+        for (date, holiday) in weekend {
+            let substitute = std::iter::successors(date.succ_opt(), Date::succ_opt)
+                .find(|d| !is_weekend(*d) && !taken.contains(d));
+            if let Some(substitute) = substitute {
+                taken.insert(substitute);
+                dates.push((substitute, holiday, true));
+            }
+        }
+
+        dates
+    }
+}
+
+fn is_weekend(date: Date) -> bool {
+    matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+}
+
+/// An error loading the holidays
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    /// Error reading the file
+    #[error("Error reading file {0}: {1}")]
+    FileError(PathBuf, std::io::Error),
+
+    /// Error reading the file
+    #[error("Error parsing file {0}: {1}")]
+    YamlError(PathBuf, serde_yaml_ng::Error),
+}
+
+/// Load and combine the holidays from the given files.
+///
+/// # Errors
+///
+/// If any file cannot be read or parsed.
+pub fn load_config(filenames: &[PathBuf]) -> Result<Holidays, ConfigError> {
+    let mut holidays = Vec::new();
+
+    for filename in filenames {
+        holidays.extend(load_file(filename)?);
+    }
+
+    Ok(Holidays(holidays))
+}
+
+fn load_file(filename: &Path) -> Result<Vec<Holiday>, ConfigError> {
+    let f = std::fs::File::open(filename)
+        .map_err(|e| ConfigError::FileError(filename.to_path_buf(), e))?;
+
+    serde_yaml_ng::from_reader(f).map_err(|e| ConfigError::YamlError(filename.to_path_buf(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn date(s: &str) -> Date {
+        Date::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_easter_sunday() {
+        assert_eq!(easter_sunday(2019), Some(date("2019-04-21")));
+        assert_eq!(easter_sunday(2024), Some(date("2024-03-31")));
+        assert_eq!(easter_sunday(2025), Some(date("2025-04-20")));
+        assert_eq!(easter_sunday(2038), Some(date("2038-04-25")));
+        assert_eq!(easter_sunday(2285), Some(date("2285-03-22")));
+    }
+
+    #[test]
+    fn test_weekday_of_month() {
+        assert_eq!(
+            nth_weekday_of_month(2025, 6, Weekday::Mon, 2),
+            Some(date("2025-06-09"))
+        );
+        assert_eq!(nth_weekday_of_month(2025, 2, Weekday::Mon, 5), None);
+        assert_eq!(
+            last_weekday_of_month(2025, 5, Weekday::Mon),
+            Some(date("2025-05-26"))
+        );
+        assert_eq!(
+            last_weekday_of_month(2025, 12, Weekday::Wed),
+            Some(date("2025-12-31"))
+        );
+
+        assert!(is_nth_weekday(date("2025-11-04"), 1, Weekday::Tue));
+        assert!(!is_nth_weekday(date("2025-11-11"), 1, Weekday::Tue));
+        assert!(is_last_weekday(date("2025-05-26"), Weekday::Mon));
+        assert!(!is_last_weekday(date("2025-05-19"), Weekday::Mon));
+    }
+
+    #[test]
+    fn test_week_parity() {
+        assert_eq!(week_parity(date("2025-04-18")), 0);
+        assert_eq!(week_parity(date("2025-04-21")), 1);
+
+        // 2020 has 53 ISO weeks, so two odd weeks are together.
+        assert_eq!(week_parity(date("2020-12-28")), 1);
+        assert_eq!(week_parity(date("2021-01-04")), 1);
+
+        assert_eq!(
+            week_parity(date("2025-06-02")),
+            week_parity(date("2025-06-08"))
+        );
+        assert_ne!(
+            week_parity(date("2025-06-08")),
+            week_parity(date("2025-06-09"))
+        );
+    }
+
+    #[test]
+    fn test_fortnight_parity() {
+        assert_eq!(fortnight_parity(date("0001-01-01")), 0);
+        assert_eq!(fortnight_parity(date("0001-01-08")), 1);
+
+        // Unlike the ISO week, the parity keeps alternating over a 53 week year.
+        assert_ne!(
+            fortnight_parity(date("2020-12-28")),
+            fortnight_parity(date("2021-01-04"))
+        );
+        assert_ne!(
+            fortnight_parity(date("2021-01-04")),
+            fortnight_parity(date("2021-01-11"))
+        );
+
+        assert_eq!(
+            fortnight_parity(date("2025-06-02")),
+            fortnight_parity(date("2025-06-08"))
+        );
+        assert_ne!(
+            fortnight_parity(date("2025-06-08")),
+            fortnight_parity(date("2025-06-09"))
+        );
+    }
+
+    #[test]
+    fn test_load_test_file() {
+        let holidays = load_config(&[PathBuf::from("test/holidays.yaml")]).unwrap();
+
+        let check = |d: &str, expected: &[&str]| {
+            let tags = holidays.tags_for_date(date(d));
+            let expected: HashSet<String> = expected.iter().map(ToString::to_string).collect();
+            assert_eq!(tags, expected, "{d}");
+        };
+
+        check("2025-01-01", &["new_years_day", "public_holiday"]);
+        check("2025-01-02", &[]);
+        check(
+            "2025-03-10",
+            &["labour_day", "public_holiday", "school_term"],
+        );
+        check("2025-04-18", &["good_friday", "public_holiday"]);
+        check("2025-04-21", &["easter_monday", "public_holiday"]);
+        check(
+            "2025-06-09",
+            &["kings_birthday", "public_holiday", "school_term"],
+        );
+        check("2025-11-04", &["melbourne_cup", "public_holiday"]);
+        check("2025-02-03", &["school_term"]);
+        check("2025-04-10", &[]);
+
+        // Australia day on a Sunday.
+        check("2025-01-26", &["australia_day", "public_holiday"]);
+        check(
+            "2025-01-27",
+            &["australia_day_substitute", "public_holiday"],
+        );
+
+        // Christmas on a Saturday and boxing day on a Sunday.
+        check("2021-12-25", &["christmas_day", "public_holiday"]);
+        check("2021-12-26", &["boxing_day", "public_holiday"]);
+        check(
+            "2021-12-27",
+            &["christmas_day_substitute", "public_holiday"],
+        );
+        check("2021-12-28", &["boxing_day_substitute", "public_holiday"]);
+
+        // Christmas on a Sunday, boxing day is already the Monday.
+        check("2022-12-26", &["boxing_day", "public_holiday"]);
+        check(
+            "2022-12-27",
+            &["christmas_day_substitute", "public_holiday"],
+        );
+    }
+
+    #[test]
+    fn test_load_vic_file() {
+        let holidays = load_config(&[PathBuf::from("holidays/au_vic.yaml")]).unwrap();
+
+        let check = |d: &str, expected: &[&str]| {
+            let tags = holidays.tags_for_date(date(d));
+            let expected: HashSet<String> = expected.iter().map(ToString::to_string).collect();
+            assert_eq!(tags, expected, "{d}");
+        };
+
+        check("2025-03-10", &["labour_day", "public_holiday"]);
+        check("2025-04-18", &["good_friday", "public_holiday"]);
+        check("2025-04-19", &["easter_saturday", "public_holiday"]);
+        check("2025-04-20", &["easter_sunday", "public_holiday"]);
+        check("2025-04-21", &["easter_monday", "public_holiday"]);
+        check("2025-06-09", &["kings_birthday", "public_holiday"]);
+        check("2025-09-26", &["grand_final_friday", "public_holiday"]);
+        check("2025-11-04", &["melbourne_cup", "public_holiday"]);
+        check("2025-12-25", &["christmas_day", "public_holiday"]);
+
+        // ANZAC day on a Saturday has no substitute.
+        check("2026-04-25", &["anzac_day", "public_holiday"]);
+        check("2026-04-27", &[]);
+
+        // New year's day on a Saturday.
+        check(
+            "2022-01-03",
+            &["new_years_day_substitute", "public_holiday"],
+        );
+    }
+
+    #[test]
+    fn test_substitute_skips_other_holidays() {
+        let holidays: Vec<Holiday> = serde_yaml_ng::from_str(
+            r"
+- name: australia_day
+  rule:
+    type: fixed
+    month: 1
+    day: 26
+  substitute: true
+- name: picnic_day
+  rule:
+    type: fixed
+    month: 1
+    day: 27
+",
+        )
+        .unwrap();
+        let holidays = Holidays(holidays);
+
+        // Australia day is a Sunday, and the Monday is already picnic day.
+        let tags = holidays.tags_for_date(date("2025-01-27"));
+        assert_eq!(tags, HashSet::from(["picnic_day".to_string()]));
+        let tags = holidays.tags_for_date(date("2025-01-28"));
+        assert_eq!(
+            tags,
+            HashSet::from(["australia_day_substitute".to_string()])
+        );
+    }
+}
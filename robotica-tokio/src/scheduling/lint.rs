@@ -0,0 +1,898 @@
+//! Find problems in the schedule config that loading it does not catch.
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt::{Display, Formatter},
+    path::{Path, PathBuf},
+};
+
+use chrono::{TimeDelta, TimeZone};
+use evalexpr::{build_operator_tree, DefaultNumericTypes, Node, Operator, Value};
+use serde::de::DeserializeOwned;
+
+use robotica_common::datetime::Date;
+use robotica_common::solar::Location;
+
+use super::{classifier, executor, holidays, scheduler, sequencer};
+
+/// How many days of the schedule to check by default.
+pub const DEFAULT_DAYS: u32 = 366;
+
+/// The kind of problem found in the config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintKind {
+    /// The file could not be read or parsed.
+    InvalidFile(String),
+
+    /// A condition is not a valid expression.
+    SyntaxError {
+        /// The condition.
+        expression: String,
+
+        /// Why it could not be parsed.
+        error: String,
+    },
+
+    /// The schedule references a sequence that does not exist.
+    MissingSequence(String),
+
+    /// A sequence is not referenced by any schedule.
+    UnusedSequence(String),
+
+    /// A step depends on a sequence id that does not exist.
+    UnknownDependency {
+        /// The title of the step.
+        title: String,
+
+        /// The missing id.
+        id: String,
+    },
+
+    /// A tag is referenced that no classifier or holiday can produce.
+    UnknownTag(String),
+
+    /// A schedule entry is never used.
+    NeverActive {
+        /// The position of the entry in the schedule, starting from 1.
+        entry: usize,
+
+        /// How many days were checked.
+        days: u32,
+    },
+
+    /// Two sequences run at the same time and send to the same topic.
+    Overlap {
+        /// The sequence that starts first.
+        first: String,
+
+        /// The sequence that starts while the first is still running.
+        second: String,
+
+        /// The topic both sequences send to.
+        topic: String,
+    },
+
+    /// A repeated step lasts longer than the time between repeats.
+    DurationExceedsRepeat {
+        /// The sequence name.
+        sequence: String,
+
+        /// The title of the step.
+        title: String,
+    },
+}
+
+impl Display for LintKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidFile(error) => write!(f, "{error}"),
+            Self::SyntaxError { expression, error } => {
+                write!(f, "Invalid condition `{expression}`: {error}")
+            }
+            Self::MissingSequence(name) => write!(f, "Sequence {name} could not be found"),
+            Self::UnusedSequence(name) => write!(f, "Sequence {name} is never scheduled"),
+            Self::UnknownDependency { title, id } => {
+                write!(f, "Step {title} depends on unknown sequence {id}")
+            }
+            Self::UnknownTag(tag) => write!(f, "Tag {tag} is never set"),
+            Self::NeverActive { entry, days } => {
+                write!(
+                    f,
+                    "Schedule entry {entry} is not used in the next {days} days"
+                )
+            }
+            Self::Overlap {
+                first,
+                second,
+                topic,
+            } => write!(
+                f,
+                "Sequences {first} and {second} overlap sending to {topic}"
+            ),
+            Self::DurationExceedsRepeat { sequence, title } => write!(
+                f,
+                "Step {title} in sequence {sequence} lasts longer than its repeat time"
+            ),
+        }
+    }
+}
+
+/// A problem found in the config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintIssue {
+    /// The file with the problem.
+    pub path: PathBuf,
+
+    /// The line of the entry with the problem, if known.
+    pub line: Option<usize>,
+
+    /// The problem.
+    pub kind: LintKind,
+}
+
+impl Display for LintIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{line}: {}", self.path.display(), self.kind),
+            None => write!(f, "{}: {}", self.path.display(), self.kind),
+        }
+    }
+}
+
+/// A config file and its contents.
+pub struct SourceFile {
+    path: PathBuf,
+    text: String,
+}
+
+impl SourceFile {
+    /// Create a source file from text that has already been read.
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>, text: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            text: text.into(),
+        }
+    }
+
+    fn load(path: &Path, issues: &mut Vec<LintIssue>) -> Option<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Some(Self::new(path, text)),
+            Err(err) => {
+                issues.push(LintIssue {
+                    path: path.to_path_buf(),
+                    line: None,
+                    kind: LintKind::InvalidFile(format!("Error reading file: {err}")),
+                });
+                None
+            }
+        }
+    }
+
+    fn issue(&self, line: Option<usize>, kind: LintKind) -> LintIssue {
+        LintIssue {
+            path: self.path.clone(),
+            line,
+            kind,
+        }
+    }
+
+    /// Find the line of a key in the top level mapping.
+    fn line_of_key(&self, key: &str) -> Option<usize> {
+        let prefixes = [
+            format!("{key}:"),
+            format!("\"{key}\":"),
+            format!("'{key}':"),
+        ];
+        self.text
+            .lines()
+            .position(|line| prefixes.iter().any(|p| line.starts_with(p.as_str())))
+            .map(|n| n + 1)
+    }
+
+    /// Find the line of the nth item, starting from 0, in a top level list.
+    fn line_of_item(&self, index: usize) -> Option<usize> {
+        nth_item(self.text.lines().enumerate(), index)
+    }
+
+    /// Find the line of the nth item, starting from 0, in the list under a top level key.
+    fn line_of_key_item(&self, key: &str, index: usize) -> Option<usize> {
+        let start = self.line_of_key(key)?;
+        let lines = self
+            .text
+            .lines()
+            .enumerate()
+            .skip(start)
+            .take_while(|(_, line)| !is_top_level_key(line));
+        nth_item(lines, index)
+    }
+
+    fn parse<C: DeserializeOwned>(&self, issues: &mut Vec<LintIssue>) -> Option<C> {
+        match serde_yaml_ng::from_str(&self.text) {
+            Ok(config) => Some(config),
+            Err(err) => {
+                let line = err.location().map(|l| l.line());
+                issues.push(self.issue(line, LintKind::InvalidFile(err.to_string())));
+                None
+            }
+        }
+    }
+
+    /// Check every condition in the file, so all syntax errors are reported at once.
+    ///
+    /// Errors are reported at the line of the entry that contains them.
+    fn syntax_issues(&self) -> Vec<LintIssue> {
+        use serde_yaml_ng::Value as Yaml;
+
+        let mut issues = Vec::new();
+        let Ok(value) = serde_yaml_ng::from_str::<Yaml>(&self.text) else {
+            return issues;
+        };
+
+        match &value {
+            Yaml::Sequence(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    self.check_conditions(item, self.line_of_item(index), &mut issues);
+                }
+            }
+            Yaml::Mapping(mapping) => {
+                for (key, value) in mapping {
+                    let key = key.as_str().unwrap_or_default();
+                    if let Yaml::Sequence(items) = value {
+                        for (index, item) in items.iter().enumerate() {
+                            let line = self.line_of_key_item(key, index);
+                            self.check_conditions(item, line, &mut issues);
+                        }
+                    } else {
+                        self.check_conditions(value, self.line_of_key(key), &mut issues);
+                    }
+                }
+            }
+            _ => self.check_conditions(&value, None, &mut issues),
+        }
+        issues
+    }
+
+    fn check_conditions(
+        &self,
+        value: &serde_yaml_ng::Value,
+        line: Option<usize>,
+        issues: &mut Vec<LintIssue>,
+    ) {
+        use serde_yaml_ng::Value as Yaml;
+
+        match value {
+            Yaml::Mapping(mapping) => {
+                for (key, value) in mapping {
+                    let is_condition = matches!(key.as_str(), Some("if" | "run_if"));
+                    match (is_condition, value) {
+                        (true, Yaml::Sequence(conditions)) => {
+                            for expression in conditions.iter().filter_map(Yaml::as_str) {
+                                if let Err(err) =
+                                    build_operator_tree::<DefaultNumericTypes>(expression)
+                                {
+                                    let kind = LintKind::SyntaxError {
+                                        expression: expression.to_string(),
+                                        error: err.to_string(),
+                                    };
+                                    issues.push(self.issue(line, kind));
+                                }
+                            }
+                        }
+                        _ => self.check_conditions(value, line, issues),
+                    }
+                }
+            }
+            Yaml::Sequence(values) => {
+                for value in values {
+                    self.check_conditions(value, line, issues);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Parse the file, reporting syntax errors in conditions individually.
+    fn parse_with_syntax<C: DeserializeOwned>(&self, issues: &mut Vec<LintIssue>) -> Option<C> {
+        let syntax_issues = self.syntax_issues();
+        if syntax_issues.is_empty() {
+            self.parse(issues)
+        } else {
+            issues.extend(syntax_issues);
+            None
+        }
+    }
+}
+
+fn is_top_level_key(line: &str) -> bool {
+    !line.is_empty() && !line.starts_with([' ', '\t', '#', '-'])
+}
+
+/// Find the line of the nth item at the indent of the first item.
+fn nth_item<'a>(lines: impl Iterator<Item = (usize, &'a str)>, index: usize) -> Option<usize> {
+    let items: Vec<(usize, usize)> = lines
+        .filter_map(|(n, line)| {
+            let trimmed = line.trim_start();
+            let is_item = trimmed == "-" || trimmed.starts_with("- ");
+            is_item.then_some((n + 1, line.len() - trimmed.len()))
+        })
+        .collect();
+    let indent = items.first()?.1;
+    items
+        .into_iter()
+        .filter(|(_, i)| *i == indent)
+        .nth(index)
+        .map(|(n, _)| n)
+}
+
+/// Get the string arguments to every call of `function` in a condition.
+pub(super) fn function_arguments(node: &Node, function: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    collect_function_arguments(node, function, &mut arguments);
+    arguments
+}
+
+fn collect_function_arguments(node: &Node, function: &str, arguments: &mut Vec<String>) {
+    match node.operator() {
+        Operator::FunctionIdentifier { identifier } if identifier == function => {
+            for child in node.children() {
+                collect_strings(child, arguments);
+            }
+        }
+        _ => {
+            for child in node.children() {
+                collect_function_arguments(child, function, arguments);
+            }
+        }
+    }
+}
+
+fn collect_strings(node: &Node, strings: &mut Vec<String>) {
+    if let Operator::Const {
+        value: Value::String(string),
+    } = node.operator()
+    {
+        strings.push(string.clone());
+    }
+    for child in node.children() {
+        collect_strings(child, strings);
+    }
+}
+
+/// The config files used by the executor.
+pub struct Sources {
+    /// The classifier config.
+    pub classifier: SourceFile,
+
+    /// The scheduler config.
+    pub scheduler: SourceFile,
+
+    /// The sequencer config.
+    pub sequencer: SourceFile,
+}
+
+/// Check the executor config files.
+///
+/// Schedule entries and overlapping sequences are checked by generating the schedule for
+/// `days` days starting from `start`.
+#[must_use]
+pub fn lint<T: TimeZone>(
+    config: &executor::Config,
+    timezone: &T,
+    start: Date,
+    days: u32,
+) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+
+    let holidays = match holidays::load_config(&config.holiday_files) {
+        Ok(holidays) => Some(holidays),
+        Err(err) => {
+            let (path, line) = match &err {
+                holidays::ConfigError::FileError(path, _) => (path.clone(), None),
+                holidays::ConfigError::YamlError(path, e) => {
+                    (path.clone(), e.location().map(|l| l.line()))
+                }
+            };
+            issues.push(LintIssue {
+                path,
+                line,
+                kind: LintKind::InvalidFile(err.to_string()),
+            });
+            None
+        }
+    };
+
+    let classifier = SourceFile::load(&config.classifications_file, &mut issues);
+    let scheduler = SourceFile::load(&config.schedule_file, &mut issues);
+    let sequencer = SourceFile::load(&config.sequences_file, &mut issues);
+
+    if let (Some(holidays), Some(classifier), Some(scheduler), Some(sequencer)) =
+        (holidays, classifier, scheduler, sequencer)
+    {
+        let sources = Sources {
+            classifier,
+            scheduler,
+            sequencer,
+        };
+        issues.extend(lint_sources(
+            &sources,
+            &holidays,
+            timezone,
+            config.location.as_ref(),
+            start,
+            days,
+        ));
+    }
+
+    issues
+}
+
+/// Check config that has already been read.
+#[must_use]
+pub fn lint_sources<T: TimeZone>(
+    sources: &Sources,
+    holidays: &holidays::Holidays,
+    timezone: &T,
+    location: Option<&Location>,
+    start: Date,
+    days: u32,
+) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+
+    let classifier: Option<Vec<classifier::Config>> =
+        sources.classifier.parse_with_syntax(&mut issues);
+    let scheduler: Option<Vec<scheduler::Config>> =
+        sources.scheduler.parse_with_syntax(&mut issues);
+    let sequencer: Option<sequencer::ConfigMap> = sources.sequencer.parse_with_syntax(&mut issues);
+
+    let (Some(classifier), Some(scheduler), Some(sequencer)) = (classifier, scheduler, sequencer)
+    else {
+        return issues;
+    };
+
+    check_sequence_names(sources, &scheduler, &sequencer, &mut issues);
+    check_tags(
+        sources,
+        holidays,
+        &classifier,
+        &scheduler,
+        &sequencer,
+        &mut issues,
+    );
+    check_repeats(sources, &sequencer, &mut issues);
+    check_dependencies(sources, &sequencer, &mut issues);
+
+    let config = Configs {
+        classifier: &classifier,
+        holidays,
+        scheduler: &scheduler,
+        sequencer: &sequencer,
+    };
+    check_simulation(
+        sources,
+        &config,
+        timezone,
+        location,
+        start,
+        days,
+        &mut issues,
+    );
+
+    issues
+}
+
+fn check_sequence_names(
+    sources: &Sources,
+    scheduler: &[scheduler::Config],
+    sequencer: &sequencer::ConfigMap,
+    issues: &mut Vec<LintIssue>,
+) {
+    let scheduled: BTreeSet<&String> = scheduler
+        .iter()
+        .flat_map(|s| s.get_sequences().keys())
+        .collect();
+
+    for name in &scheduled {
+        if !sequencer.contains_key(*name) {
+            let line = scheduler
+                .iter()
+                .position(|s| s.get_sequences().contains_key(*name))
+                .and_then(|index| sources.scheduler.line_of_item(index));
+            let kind = LintKind::MissingSequence((*name).clone());
+            issues.push(sources.scheduler.issue(line, kind));
+        }
+    }
+
+    let defined: BTreeSet<&String> = sequencer.keys().collect();
+    for name in defined.difference(&scheduled) {
+        let line = sources.sequencer.line_of_key(name);
+        let kind = LintKind::UnusedSequence((*name).clone());
+        issues.push(sources.sequencer.issue(line, kind));
+    }
+}
+
+fn check_tags(
+    sources: &Sources,
+    holidays: &holidays::Holidays,
+    classifier: &[classifier::Config],
+    scheduler: &[scheduler::Config],
+    sequencer: &sequencer::ConfigMap,
+    issues: &mut Vec<LintIssue>,
+) {
+    let mut known = holidays.all_tags();
+    known.extend(classifier.iter().flat_map(|c| c.added_tags().cloned()));
+
+    // Each tag is reported at the first entry that references it.
+    let mut report = |source: &SourceFile, referenced: Vec<(Option<usize>, HashSet<String>)>| {
+        let mut unknown: BTreeMap<String, Option<usize>> = BTreeMap::new();
+        for (line, tags) in referenced {
+            for tag in tags.into_iter().filter(|tag| !known.contains(tag)) {
+                unknown.entry(tag).or_insert(line);
+            }
+        }
+        for (tag, line) in unknown {
+            issues.push(source.issue(line, LintKind::UnknownTag(tag)));
+        }
+    };
+
+    report(
+        &sources.classifier,
+        classifier
+            .iter()
+            .enumerate()
+            .map(|(index, c)| (sources.classifier.line_of_item(index), c.referenced_tags()))
+            .collect(),
+    );
+    report(
+        &sources.scheduler,
+        scheduler
+            .iter()
+            .enumerate()
+            .map(|(index, s)| (sources.scheduler.line_of_item(index), s.referenced_tags()))
+            .collect(),
+    );
+    let names: BTreeSet<&String> = sequencer.keys().collect();
+    report(
+        &sources.sequencer,
+        names
+            .into_iter()
+            .flat_map(|name| {
+                sequencer[name].iter().enumerate().map(move |(index, c)| {
+                    let line = sources.sequencer.line_of_key_item(name, index);
+                    (line, c.referenced_tags().cloned().collect())
+                })
+            })
+            .collect(),
+    );
+}
+
+fn check_repeats(sources: &Sources, sequencer: &sequencer::ConfigMap, issues: &mut Vec<LintIssue>) {
+    let names: BTreeSet<&String> = sequencer.keys().collect();
+
+    for name in names {
+        for (index, config) in sequencer[name]
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.duration_exceeds_repeat())
+        {
+            let line = sources.sequencer.line_of_key_item(name, index);
+            let kind = LintKind::DurationExceedsRepeat {
+                sequence: name.clone(),
+                title: config.title.clone(),
+            };
+            issues.push(sources.sequencer.issue(line, kind));
+        }
+    }
+}
+
+fn check_dependencies(
+    sources: &Sources,
+    sequencer: &sequencer::ConfigMap,
+    issues: &mut Vec<LintIssue>,
+) {
+    let ids = sequencer::step_ids(sequencer);
+    let names: BTreeSet<&String> = sequencer.keys().collect();
+
+    for name in names {
+        for (index, config) in sequencer[name].iter().enumerate() {
+            for id in config.dependencies().filter(|id| !ids.contains(*id)) {
+                let line = sources.sequencer.line_of_key_item(name, index);
+                let kind = LintKind::UnknownDependency {
+                    title: config.title.clone(),
+                    id: id.clone(),
+                };
+                issues.push(sources.sequencer.issue(line, kind));
+            }
+        }
+    }
+}
+
+struct Configs<'a> {
+    classifier: &'a Vec<classifier::Config>,
+    holidays: &'a holidays::Holidays,
+    scheduler: &'a [scheduler::Config],
+    sequencer: &'a sequencer::ConfigMap,
+}
+
+fn check_simulation<T: TimeZone>(
+    sources: &Sources,
+    config: &Configs,
+    timezone: &T,
+    location: Option<&Location>,
+    start: Date,
+    days: u32,
+    issues: &mut Vec<LintIssue>,
+) {
+    let mut active = vec![false; config.scheduler.len()];
+    let mut overlaps = BTreeSet::new();
+
+    let classify = |date: &Date| {
+        classifier::classify_date_with_config(date, config.classifier, config.holidays)
+    };
+
+    let mut tomorrow = classify(&start);
+    for n in 0..days {
+        let date = start + TimeDelta::days(i64::from(n));
+        let today = tomorrow;
+        tomorrow = classify(&(date + TimeDelta::days(1)));
+
+        for (entry, is_active) in config.scheduler.iter().zip(active.iter_mut()) {
+            *is_active = *is_active || entry.is_active(date, &today, &tomorrow);
+        }
+
+        let mut sequences = scheduler::get_schedule_with_config(
+            date,
+            &today,
+            &tomorrow,
+            config.scheduler,
+            timezone,
+            location,
+        )
+        .ok()
+        .and_then(|schedule| {
+            sequencer::schedule_list_to_sequence(
+                config.sequencer,
+                date,
+                &schedule,
+                &today,
+                &tomorrow,
+            )
+            .ok()
+        })
+        .unwrap_or_default();
+        sequencer::resolve_dependencies(&mut sequences);
+        sequences.sort_by_key(|s| (s.start_time, s.end_time));
+
+        for (i, first) in sequences.iter().enumerate() {
+            for second in &sequences[i + 1..] {
+                if second.start_time >= first.end_time {
+                    break;
+                }
+                if second.sequence_name == first.sequence_name {
+                    continue;
+                }
+                let topics: HashSet<&String> = first.tasks.iter().flat_map(|t| &t.topics).collect();
+                for topic in second.tasks.iter().flat_map(|t| &t.topics) {
+                    if topics.contains(topic) {
+                        overlaps.insert((
+                            first.sequence_name.clone(),
+                            second.sequence_name.clone(),
+                            topic.clone(),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    for (entry, _) in active.iter().enumerate().filter(|(_, a)| !**a) {
+        let line = sources.scheduler.line_of_item(entry);
+        let kind = LintKind::NeverActive {
+            entry: entry + 1,
+            days,
+        };
+        issues.push(sources.scheduler.issue(line, kind));
+    }
+
+    for (first, second, topic) in overlaps {
+        let line = sources.sequencer.line_of_key(&second);
+        let kind = LintKind::Overlap {
+            first,
+            second,
+            topic,
+        };
+        issues.push(sources.sequencer.issue(line, kind));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use chrono::{NaiveDate, Utc};
+
+    use super::*;
+
+    const CLASSIFIER: &str = r#"- week_day: true
+  add: ["weekday"]
+- if:
+  - "classifications(\"missing_tag\")"
+  add: ["odd"]
+"#;
+
+    const SCHEDULER: &str = r#"- sequences:
+    "wake_up":
+      time: "08:00:00"
+    "also_wake_up":
+      time: "08:01:00"
+- today: ["never"]
+  sequences:
+    "wake_up":
+      time: "09:00:00"
+"#;
+
+    const SEQUENCER: &str = r#""wake_up":
+  - title: "Wake up"
+    duration: "00:05:00"
+    tasks:
+      - title: "Light on"
+        payload_str: "on"
+        topics: ["light/bedroom"]
+"also_wake_up":
+  - title: "Also wake up"
+    duration: "00:05:00"
+    repeat_count: 2
+    repeat_time: "00:01:00"
+    tasks:
+      - title: "Light on"
+        payload_str: "on"
+        topics: ["light/bedroom"]
+"unused":
+  - title: "Unused"
+    duration: "00:01:00"
+    after: "breakfast"
+    tasks: []
+"#;
+
+    fn sources(classifier: &str) -> Sources {
+        Sources {
+            classifier: SourceFile::new("classifications.yaml", classifier),
+            scheduler: SourceFile::new("schedule.yaml", SCHEDULER),
+            sequencer: SourceFile::new("sequences.yaml", SEQUENCER),
+        }
+    }
+
+    fn issue(path: &str, line: usize, kind: LintKind) -> LintIssue {
+        LintIssue {
+            path: PathBuf::from(path),
+            line: Some(line),
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_lint() {
+        let start = NaiveDate::from_ymd_opt(2025, 1, 6).unwrap();
+        let issues = lint_sources(
+            &sources(CLASSIFIER),
+            &holidays::Holidays::default(),
+            &Utc,
+            None,
+            start,
+            7,
+        );
+
+        let expected = vec![
+            issue(
+                "sequences.yaml",
+                17,
+                LintKind::UnusedSequence("unused".to_string()),
+            ),
+            issue(
+                "classifications.yaml",
+                3,
+                LintKind::UnknownTag("missing_tag".to_string()),
+            ),
+            issue(
+                "schedule.yaml",
+                6,
+                LintKind::UnknownTag("never".to_string()),
+            ),
+            issue(
+                "sequences.yaml",
+                9,
+                LintKind::DurationExceedsRepeat {
+                    sequence: "also_wake_up".to_string(),
+                    title: "Also wake up".to_string(),
+                },
+            ),
+            issue(
+                "sequences.yaml",
+                18,
+                LintKind::UnknownDependency {
+                    title: "Unused".to_string(),
+                    id: "breakfast".to_string(),
+                },
+            ),
+            issue(
+                "schedule.yaml",
+                6,
+                LintKind::NeverActive { entry: 2, days: 7 },
+            ),
+            issue(
+                "sequences.yaml",
+                8,
+                LintKind::Overlap {
+                    first: "wake_up".to_string(),
+                    second: "also_wake_up".to_string(),
+                    topic: "light/bedroom".to_string(),
+                },
+            ),
+        ];
+        assert_eq!(issues, expected);
+    }
+
+    #[test]
+    fn test_lint_syntax_errors() {
+        let classifier = r#"- if:
+  - "1 2"
+  add: ["a"]
+- if:
+  - "(("
+  add: ["b"]
+"#;
+        let start = NaiveDate::from_ymd_opt(2025, 1, 6).unwrap();
+        let issues = lint_sources(
+            &sources(classifier),
+            &holidays::Holidays::default(),
+            &Utc,
+            None,
+            start,
+            7,
+        );
+
+        let lines: Vec<_> = issues
+            .iter()
+            .filter(|issue| matches!(issue.kind, LintKind::SyntaxError { .. }))
+            .map(|issue| issue.line)
+            .collect();
+        assert_eq!(lines, vec![Some(1), Some(4)]);
+    }
+
+    #[test]
+    fn test_lint_lines_ignore_earlier_matches() {
+        // The missing id is also the title of an earlier step.
+        let sequencer = r#""cook":
+  - title: "breakfast"
+    duration: "00:05:00"
+    tasks: []
+"eat":
+  - title: "Eat"
+    duration: "00:05:00"
+    tasks: []
+  - title: "Wash up"
+    duration: "00:05:00"
+    after: "breakfast"
+    tasks: []
+"#;
+        let sources = Sources {
+            sequencer: SourceFile::new("sequences.yaml", sequencer),
+            ..sources(CLASSIFIER)
+        };
+        let start = NaiveDate::from_ymd_opt(2025, 1, 6).unwrap();
+        let issues = lint_sources(
+            &sources,
+            &holidays::Holidays::default(),
+            &Utc,
+            None,
+            start,
+            7,
+        );
+
+        let lines: Vec<_> = issues
+            .iter()
+            .filter(|issue| matches!(issue.kind, LintKind::UnknownDependency { .. }))
+            .map(|issue| issue.line)
+            .collect();
+        assert_eq!(lines, vec![Some(9)]);
+    }
+}
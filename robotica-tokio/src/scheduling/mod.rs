@@ -3,5 +3,8 @@
 pub mod calendar;
 pub mod classifier;
 pub mod executor;
+pub mod holidays;
+pub mod lint;
+pub mod runtime;
 pub mod scheduler;
 pub mod sequencer;
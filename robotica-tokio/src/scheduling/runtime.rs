@@ -0,0 +1,409 @@
+//! Conditions that are evaluated when a sequence is started.
+//!
+//! Unlike the `if` conditions in the sequencer config, which are evaluated when the schedule
+//! is generated, runtime conditions are evaluated against the latest values of live inputs
+//! at the time the sequence starts.
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+
+use evalexpr::{
+    build_operator_tree, ContextWithMutableVariables, DefaultNumericTypes, HashMapContext, Node,
+    Value,
+};
+use serde::{Deserialize, Deserializer};
+use tokio::sync::mpsc;
+use tracing::debug;
+
+use crate::pipes::stateful;
+use crate::spawn;
+
+/// A live value that runtime conditions can refer to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeValue {
+    /// A boolean value.
+    Boolean(bool),
+
+    /// An integer value.
+    Int(i64),
+
+    /// A floating point value.
+    Float(f64),
+
+    /// A string value.
+    String(String),
+
+    /// No value is available.
+    Empty,
+}
+
+impl From<bool> for RuntimeValue {
+    fn from(value: bool) -> Self {
+        Self::Boolean(value)
+    }
+}
+
+impl From<i64> for RuntimeValue {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<u8> for RuntimeValue {
+    fn from(value: u8) -> Self {
+        Self::Int(i64::from(value))
+    }
+}
+
+impl From<f64> for RuntimeValue {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<f32> for RuntimeValue {
+    fn from(value: f32) -> Self {
+        Self::Float(f64::from(value))
+    }
+}
+
+impl From<String> for RuntimeValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<&str> for RuntimeValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl<T: Into<RuntimeValue>> From<Option<T>> for RuntimeValue {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Empty, Into::into)
+    }
+}
+
+impl From<RuntimeValue> for Value<DefaultNumericTypes> {
+    fn from(value: RuntimeValue) -> Self {
+        match value {
+            RuntimeValue::Boolean(value) => Value::Boolean(value),
+            RuntimeValue::Int(value) => Value::Int(value),
+            RuntimeValue::Float(value) => Value::Float(value),
+            RuntimeValue::String(value) => Value::String(value),
+            RuntimeValue::Empty => Value::Empty,
+        }
+    }
+}
+
+type TimedFn = Arc<dyn Fn(&DateTime<Utc>) -> RuntimeValue + Send + Sync>;
+
+/// The value of an input.
+#[derive(Clone)]
+pub(super) enum InputValue {
+    /// A value that stays the same until the input changes.
+    Fixed(RuntimeValue),
+
+    /// A value that depends on the time it is looked up.
+    Timed(TimedFn),
+}
+
+impl InputValue {
+    fn get(&self, now: &DateTime<Utc>) -> RuntimeValue {
+        match self {
+            Self::Fixed(value) => value.clone(),
+            Self::Timed(f) => f(now),
+        }
+    }
+}
+
+impl PartialEq for InputValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Fixed(a), Self::Fixed(b)) => a == b,
+            (Self::Timed(a), Self::Timed(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Debug for InputValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fixed(value) => write!(f, "{value:?}"),
+            Self::Timed(_) => write!(f, "Timed"),
+        }
+    }
+}
+
+/// A compiled evalexpr condition that keeps its source for reporting.
+#[derive(Debug, Clone)]
+pub struct RuntimeCondition {
+    source: String,
+    node: Node,
+}
+
+impl RuntimeCondition {
+    /// Compile a new runtime condition.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the expression cannot be parsed.
+    pub fn new(source: impl Into<String>) -> Result<Self, evalexpr::EvalexprError> {
+        let source = source.into();
+        let node = build_operator_tree::<DefaultNumericTypes>(&source)?;
+        Ok(Self { source, node })
+    }
+
+    fn evaluate(&self, ctx: &HashMapContext<DefaultNumericTypes>) -> Result<bool, String> {
+        self.node
+            .eval_boolean_with_context(ctx)
+            .map_err(|e| format!("`{}` failed: {e}", self.source))
+    }
+}
+
+impl Display for RuntimeCondition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl<'de> Deserialize<'de> for RuntimeCondition {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s: String = Deserialize::deserialize(deserializer)?;
+        Self::new(s).map_err(|e| serde::de::Error::custom(format!("Error parsing condition: {e}")))
+    }
+}
+
+/// The named live inputs that runtime conditions can refer to.
+///
+/// Each input is available as a variable of the same name in the condition.
+#[derive(Default)]
+pub struct Inputs(HashMap<String, stateful::Receiver<InputValue>>);
+
+impl Inputs {
+    /// Create an empty set of inputs.
+    #[must_use]
+    pub fn new() -> Self {
+        Self(HashMap::new())
+    }
+
+    /// Add a named input.
+    ///
+    /// If an input with the same name already exists it will be replaced.
+    pub fn add<T>(&mut self, name: impl Into<String>, rx: stateful::Receiver<T>)
+    where
+        T: Into<RuntimeValue> + Clone + Send + 'static,
+    {
+        let rx = rx.map(|(_, value)| InputValue::Fixed(value.into()));
+        self.0.insert(name.into(), rx);
+    }
+
+    /// Add a named input whose value depends on when it is looked up.
+    ///
+    /// `f` is called with the latest value of `rx` every time conditions are checked, for
+    /// example to find the price for the current interval from a list of prices.
+    ///
+    /// If an input with the same name already exists it will be replaced.
+    pub fn add_timed<T, F>(&mut self, name: impl Into<String>, rx: stateful::Receiver<T>, f: F)
+    where
+        T: Clone + Send + Sync + 'static,
+        F: Fn(&T, &DateTime<Utc>) -> RuntimeValue + Send + Sync + 'static,
+    {
+        let f = Arc::new(f);
+        let rx = rx.map(move |(_, value)| {
+            let f = f.clone();
+            InputValue::Timed(Arc::new(move |now: &DateTime<Utc>| f(&value, now)))
+        });
+        self.0.insert(name.into(), rx);
+    }
+
+    /// Subscribe to all inputs, merging them into a single stream of name/value updates.
+    ///
+    /// The current value of every input is queued before this returns, so the caller sees
+    /// every input that already has a value straight away, e.g. with `try_recv`.
+    pub(super) async fn subscribe(self) -> mpsc::UnboundedReceiver<(String, InputValue)> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        for (name, input) in self.0 {
+            let mut s = input.subscribe().await;
+            if let Ok(Some((_, value))) = s.try_recv_old_new() {
+                tx.send((name.clone(), value)).ok();
+            }
+            let tx = tx.clone();
+            spawn(async move {
+                while let Ok((_, value)) = s.recv_old_new().await {
+                    if tx.send((name.clone(), value)).is_err() {
+                        debug!("Runtime input {name}: executor has gone, exiting");
+                        break;
+                    }
+                }
+            });
+        }
+        rx
+    }
+}
+
+/// The latest values of all inputs.
+#[derive(Default)]
+pub(super) struct Values(HashMap<String, InputValue>);
+
+impl Values {
+    pub(super) fn new() -> Self {
+        Self(HashMap::new())
+    }
+
+    pub(super) fn set(&mut self, name: String, value: InputValue) {
+        debug!("Runtime value {name} = {value:?}");
+        self.0.insert(name, value);
+    }
+
+    /// Get the values as they are at `now`.
+    pub(super) fn build_context(&self, now: &DateTime<Utc>) -> HashMapContext<DefaultNumericTypes> {
+        let mut ctx = HashMapContext::new();
+        for (name, value) in &self.0 {
+            ctx.set_value(name.clone(), value.get(now).into()).ok();
+        }
+        ctx
+    }
+}
+
+/// Check a list of runtime conditions.
+///
+/// As with the `if` conditions, the check passes if any condition is true.
+///
+/// # Errors
+///
+/// Returns the reason the check failed.
+pub(super) fn check_conditions(
+    conditions: &[RuntimeCondition],
+    ctx: &HashMapContext<DefaultNumericTypes>,
+) -> Result<(), String> {
+    let mut reasons = Vec::with_capacity(conditions.len());
+
+    for condition in conditions {
+        match condition.evaluate(ctx) {
+            Ok(true) => return Ok(()),
+            Ok(false) => reasons.push(format!("`{condition}` was false")),
+            Err(reason) => reasons.push(reason),
+        }
+    }
+
+    Err(reasons.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+
+    fn values() -> Values {
+        let mut values = Values::new();
+        values.set("anyone_home".to_string(), InputValue::Fixed(true.into()));
+        values.set("amber_price".to_string(), InputValue::Fixed(25.5f64.into()));
+        values.set(
+            "car_state".to_string(),
+            InputValue::Fixed("Charging".into()),
+        );
+        values
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+    }
+
+    #[test]
+    fn test_condition_true() {
+        let ctx = values().build_context(&now());
+        let conditions = vec![RuntimeCondition::new("anyone_home && amber_price > 20.0").unwrap()];
+        assert_eq!(check_conditions(&conditions, &ctx), Ok(()));
+    }
+
+    #[test]
+    fn test_condition_false() {
+        let ctx = values().build_context(&now());
+        let conditions = vec![
+            RuntimeCondition::new("!anyone_home").unwrap(),
+            RuntimeCondition::new("car_state == \"Disconnected\"").unwrap(),
+        ];
+        assert_eq!(
+            check_conditions(&conditions, &ctx),
+            Err("`!anyone_home` was false, `car_state == \"Disconnected\"` was false".to_string())
+        );
+    }
+
+    #[test]
+    fn test_condition_any() {
+        let ctx = values().build_context(&now());
+        let conditions = vec![
+            RuntimeCondition::new("!anyone_home").unwrap(),
+            RuntimeCondition::new("car_state == \"Charging\"").unwrap(),
+        ];
+        assert_eq!(check_conditions(&conditions, &ctx), Ok(()));
+    }
+
+    #[test]
+    fn test_condition_missing_value() {
+        let ctx = values().build_context(&now());
+        let conditions = vec![RuntimeCondition::new("unknown_value").unwrap()];
+        let result = check_conditions(&conditions, &ctx);
+        assert!(result.unwrap_err().starts_with("`unknown_value` failed:"));
+    }
+
+    #[test]
+    fn test_timed_value() {
+        // The price changes at `now`, without the input changing.
+        let mut values = Values::new();
+        let price = Arc::new(|dt: &DateTime<Utc>| {
+            RuntimeValue::Float(if *dt < now() { 30.0 } else { 10.0 })
+        });
+        values.set("amber_price".to_string(), InputValue::Timed(price));
+        let conditions = vec![RuntimeCondition::new("amber_price < 20.0").unwrap()];
+
+        let before = now() - chrono::TimeDelta::minutes(5);
+        let ctx = values.build_context(&before);
+        assert!(check_conditions(&conditions, &ctx).is_err());
+
+        let ctx = values.build_context(&now());
+        assert_eq!(check_conditions(&conditions, &ctx), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_replays_current_values() {
+        let (tx, rx) = stateful::create_pipe("anyone_home");
+        tx.try_send(true);
+        let mut inputs = Inputs::new();
+        inputs.add("anyone_home", rx);
+
+        // Wait for the value to make it through the input.
+        let input = inputs.0["anyone_home"].clone();
+        input.subscribe().await.recv_old_new().await.unwrap();
+
+        // The value arrived before subscribing, but is still available straight away.
+        let mut s = inputs.subscribe().await;
+        let (name, value) = s.try_recv().unwrap();
+        assert_eq!(name, "anyone_home");
+        assert_eq!(value, InputValue::Fixed(true.into()));
+
+        tx.try_send(false);
+        let (name, value) = s.recv().await.unwrap();
+        assert_eq!(name, "anyone_home");
+        assert_eq!(value, InputValue::Fixed(false.into()));
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let conditions: Vec<RuntimeCondition> =
+            serde_yaml_ng::from_str("- anyone_home\n- amber_price < 10.0\n").unwrap();
+        assert_eq!(conditions.len(), 2);
+        assert_eq!(conditions[1].to_string(), "amber_price < 10.0");
+
+        let result: Result<Vec<RuntimeCondition>, _> = serde_yaml_ng::from_str("- \"(\"\n");
+        assert!(result.is_err());
+    }
+}
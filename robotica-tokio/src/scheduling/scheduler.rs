@@ -7,9 +7,8 @@ use evalexpr::{
     build_operator_tree, ContextWithMutableFunctions, ContextWithMutableVariables,
     DefaultNumericTypes, Function, HashMapContext, Node, Value,
 };
-use robotica_common::datetime::{
-    convert_date_time_to_utc_or_default, week_day_to_string, DateTime, Time,
-};
+use robotica_common::datetime::{week_day_to_string, DateTime};
+use robotica_common::solar::{Location, TimeOfDay};
 use serde::{Deserialize, Deserializer};
 use std::{
     collections::{HashMap, HashSet},
@@ -17,6 +16,8 @@ use std::{
 };
 use thiserror::Error;
 
+use super::lint;
+
 /// A compiled evalexpr condition.
 #[derive(Debug, Clone)]
 pub struct Condition(Node);
@@ -50,7 +51,7 @@ pub struct Schedule {
 /// A sequence from the Config.
 #[derive(Deserialize, Debug, Clone)]
 pub struct Sequence {
-    time: Time,
+    time: TimeOfDay,
     options: Option<Vec<String>>,
 }
 
@@ -70,6 +71,32 @@ impl Config {
     pub const fn get_sequences(&self) -> &HashMap<String, Sequence> {
         &self.sequences
     }
+
+    /// The tags this entry depends on.
+    pub(super) fn referenced_tags(&self) -> HashSet<String> {
+        let mut tags: HashSet<String> = self
+            .today
+            .iter()
+            .chain(self.tomorrow.iter())
+            .flatten()
+            .cloned()
+            .collect();
+        for cond in self.if_cond.iter().flatten() {
+            tags.extend(lint::function_arguments(&cond.0, "today"));
+            tags.extend(lint::function_arguments(&cond.0, "tomorrow"));
+        }
+        tags
+    }
+
+    /// Is this entry used on the given date?
+    pub(super) fn is_active(
+        &self,
+        date: NaiveDate,
+        today: &HashSet<String>,
+        tomorrow: &HashSet<String>,
+    ) -> bool {
+        is_condition_ok(self, date, today, tomorrow) && is_tags_ok(self, today, tomorrow)
+    }
 }
 
 fn build_context(
@@ -167,6 +194,9 @@ pub enum ScheduleError {}
 
 /// Create a schedule for given date based on the given tags.
 ///
+/// Sequences with times relative to solar events need a `location`. If the time cannot be
+/// calculated the sequence is skipped for this date.
+///
 /// # Errors
 ///
 /// Returns an error if a date time cannot be converted to UTC.
@@ -177,6 +207,7 @@ pub fn get_schedule_with_config<T: TimeZone>(
     tomorrow: &HashSet<String>,
     config_list: &[Config],
     timezone: &T,
+    location: Option<&Location>,
 ) -> Result<Vec<Schedule>, ScheduleError> {
     let schedule = config_list.iter().filter(|config| {
         is_condition_ok(config, date, today, tomorrow) && is_tags_ok(config, today, tomorrow)
@@ -188,17 +219,24 @@ pub fn get_schedule_with_config<T: TimeZone>(
             acc
         })
         .into_iter()
-        .map(|(name, seq)| {
+        .filter_map(|(name, seq)| {
+            let datetime = match seq.time.to_utc(date, timezone, location) {
+                Ok(datetime) => datetime,
+                Err(e) => {
+                    tracing::error!("Cannot schedule {name} at {} on {date}: {e}", seq.time);
+                    return None;
+                }
+            };
             let options = seq
                 .options
                 .as_ref()
                 .map_or_else(HashSet::new, |o| o.iter().cloned().collect());
             let schedule = Schedule {
-                datetime: convert_date_time_to_utc_or_default(date, seq.time, timezone),
+                datetime,
                 sequence_name: name,
                 options,
             };
-            Ok(schedule)
+            Some(Ok(schedule))
         })
         .collect();
 
@@ -258,7 +296,7 @@ mod tests {
     #![allow(clippy::unwrap_used)]
 
     use chrono::FixedOffset;
-    use robotica_common::datetime::convert_date_time_to_utc;
+    use robotica_common::datetime::{convert_date_time_to_utc, Time};
 
     use super::*;
 
@@ -338,7 +376,8 @@ mod tests {
         let timezone = FixedOffset::east_opt(60 * 60 * 10).unwrap();
 
         let schedule =
-            get_schedule_with_config(date, &today, &tomorrow, &config_list, &timezone).unwrap();
+            get_schedule_with_config(date, &today, &tomorrow, &config_list, &timezone, None)
+                .unwrap();
 
         let expected: Vec<ExpectedResult> = vec![
             ExpectedResult {
@@ -368,7 +407,8 @@ mod tests {
         let timezone = FixedOffset::east_opt(60 * 60 * 10).unwrap();
 
         let schedule =
-            get_schedule_with_config(date, &today, &tomorrow, &config_list, &timezone).unwrap();
+            get_schedule_with_config(date, &today, &tomorrow, &config_list, &timezone, None)
+                .unwrap();
 
         let expected: Vec<ExpectedResult> = vec![
             ExpectedResult {
@@ -403,7 +443,8 @@ mod tests {
         let timezone = FixedOffset::east_opt(60 * 60 * 10).unwrap();
 
         let schedule =
-            get_schedule_with_config(date, &today, &tomorrow, &config_list, &timezone).unwrap();
+            get_schedule_with_config(date, &today, &tomorrow, &config_list, &timezone, None)
+                .unwrap();
 
         let expected: Vec<ExpectedResult> = vec![
             ExpectedResult {
@@ -421,6 +462,63 @@ mod tests {
         check_results(&schedule, &expected, date, timezone);
     }
 
+    #[test]
+    fn test_solar() {
+        let config_list = vec![Config {
+            if_cond: None,
+            today: None,
+            tomorrow: None,
+            sequences: HashMap::from([
+                (
+                    "close_blinds".to_string(),
+                    Sequence {
+                        time: "sunset + 00:30".parse().unwrap(),
+                        options: None,
+                    },
+                ),
+                (
+                    "wake_up".to_string(),
+                    Sequence {
+                        time: "08:30:00".parse().unwrap(),
+                        options: None,
+                    },
+                ),
+            ]),
+        }];
+
+        let date = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+        let today: HashSet<String> = HashSet::from([]);
+        let tomorrow: HashSet<String> = HashSet::from([]);
+        let timezone = FixedOffset::east_opt(60 * 60 * 10).unwrap();
+        let melbourne = Location {
+            latitude: -37.8136,
+            longitude: 144.9631,
+        };
+
+        let schedule = get_schedule_with_config(
+            date,
+            &today,
+            &tomorrow,
+            &config_list,
+            &timezone,
+            Some(&melbourne),
+        )
+        .unwrap();
+
+        assert_eq!(schedule.len(), 2);
+        assert_eq!(schedule[0].sequence_name, "wake_up");
+        assert_eq!(schedule[1].sequence_name, "close_blinds");
+        let local = schedule[1].datetime.with_timezone(&timezone);
+        assert_eq!(local.format("%H:%M").to_string(), "17:38");
+
+        // Without a location the solar sequence cannot be scheduled.
+        let schedule =
+            get_schedule_with_config(date, &today, &tomorrow, &config_list, &timezone, None)
+                .unwrap();
+        assert_eq!(schedule.len(), 1);
+        assert_eq!(schedule[0].sequence_name, "wake_up");
+    }
+
     fn check_results(
         schedule: &[Schedule],
         expected: &[ExpectedResult],
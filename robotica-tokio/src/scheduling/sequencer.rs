@@ -5,7 +5,7 @@ use std::{
     time::Duration,
 };
 
-use chrono::{NaiveDate, Utc};
+use chrono::{NaiveDate, TimeDelta, Utc};
 use evalexpr::{
     build_operator_tree, ContextWithMutableFunctions, DefaultNumericTypes, Function,
     HashMapContext, Node, Value,
@@ -17,9 +17,10 @@ use robotica_common::{
     datetime::{duration, DateTime},
     mqtt::{QoS, Retain},
     robotica::tasks::{Payload, Task},
-    scheduler::{Importance, Mark, Status},
+    scheduler::{Importance, Mark, Status, TaskOutcome},
 };
 
+use super::runtime::RuntimeCondition;
 use super::scheduler::{self};
 
 /// A compiled evalexpr condition.
@@ -56,6 +57,42 @@ pub struct ConfigTask {
 
     /// The topics this task will send to.
     topics: Vec<String>,
+
+    /// The runtime conditions that must be true when the task is sent.
+    run_if: Option<Vec<RuntimeCondition>>,
+
+    /// The state change that confirms the task was delivered.
+    expect: Option<Expectation>,
+}
+
+/// A state change expected after a task is sent.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Expectation {
+    /// The state topic to watch.
+    pub topic: String,
+
+    /// The value the state topic must have.
+    pub value: String,
+
+    /// How long to wait for the value after each attempt.
+    #[serde(with = "robotica_common::datetime::with_duration")]
+    pub timeout: Duration,
+
+    /// How many times to resend the task if the value is not seen.
+    #[serde(default)]
+    pub retries: u8,
+}
+
+/// Per sequence changes to the reminders for important sequences.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reminders {
+    /// Send reminders this many minutes before the start.
+    pub minutes_before: Option<Vec<u32>>,
+
+    /// Escalate if the sequence is not marked done this long after the start.
+    #[serde(with = "robotica_common::datetime::with_option_duration")]
+    #[serde(default)]
+    pub escalate_after: Option<Duration>,
 }
 
 /// The source schedule loaded from the config file.
@@ -75,6 +112,9 @@ pub struct Config {
     #[serde(rename = "if")]
     if_cond: Option<Vec<Condition>>,
 
+    /// The runtime conditions that must be true when the sequence is started.
+    run_if: Option<Vec<RuntimeCondition>>,
+
     /// The required classifications for this step.
     classifications: Option<HashSet<String>>,
 
@@ -101,6 +141,20 @@ pub struct Config {
     /// How many times this step should be repeated.
     repeat_count: Option<u8>,
 
+    /// Start this step when the sequence with this id finishes.
+    ///
+    /// Later steps keep their time relative to this step.
+    after: Option<String>,
+
+    /// The ids of sequences that must not be cancelled for this step to run.
+    requires: Option<Vec<String>>,
+
+    /// If false, cancelling the `after` sequence does not cancel this step.
+    cascade: Option<bool>,
+
+    /// Changes to the reminders, if this is a high importance step.
+    reminders: Option<Reminders>,
+
     /// The tasks to execute.
     tasks: Vec<ConfigTask>,
 }
@@ -112,11 +166,49 @@ impl Config {
     fn repeat_time(&self) -> Duration {
         self.repeat_time.unwrap_or_else(|| duration::minutes(1))
     }
+
+    /// The classifications this step depends on.
+    pub(super) fn referenced_tags(&self) -> impl Iterator<Item = &String> {
+        self.classifications.iter().flatten()
+    }
+
+    /// The ids of sequences this step depends on.
+    pub(super) fn dependencies(&self) -> impl Iterator<Item = &String> {
+        self.after.iter().chain(self.requires.iter().flatten())
+    }
+
+    /// Does each repeat of this step last longer than the time between repeats?
+    pub(super) fn duration_exceeds_repeat(&self) -> bool {
+        self.repeat_count() > 1 && self.duration > self.repeat_time()
+    }
 }
 
 /// The configuration for a sequence.
 pub type ConfigMap = HashMap<String, Vec<Config>>;
 
+/// The ids of every step in the config.
+pub(super) fn step_ids(config: &ConfigMap) -> HashSet<String> {
+    config
+        .iter()
+        .flat_map(|(name, steps)| {
+            steps
+                .iter()
+                .enumerate()
+                .map(move |(n, step)| step.id.clone().unwrap_or_else(|| format!("{name}_{n}")))
+        })
+        .collect()
+}
+
+/// When a step starts relative to the end of another sequence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct After {
+    /// The id of the sequence.
+    pub id: String,
+
+    /// How long after the end of that sequence to start.
+    pub offset: TimeDelta,
+}
+
 /// The schedule with all values completed.
 ///
 /// Note this is a copy of the struct from robotica-common, because
@@ -144,6 +236,18 @@ pub struct Sequence {
     #[serde(skip)]
     pub if_cond: Option<Vec<Condition>>,
 
+    /// The runtime conditions that must be true when this is started.
+    #[serde(skip)]
+    pub run_if: Option<Vec<RuntimeCondition>>,
+
+    /// The runtime conditions for each task, in the same order as `tasks`.
+    #[serde(skip)]
+    pub task_run_if: Vec<Option<Vec<RuntimeCondition>>>,
+
+    /// The delivery expectations for each task, in the same order as `tasks`.
+    #[serde(skip)]
+    pub task_expect: Vec<Option<Expectation>>,
+
     /// The required classifications for this step.
     #[serde(skip)]
     pub classifications: Option<HashSet<String>>,
@@ -169,9 +273,28 @@ pub struct Sequence {
     /// The latest time this step can be started.
     pub latest_time: DateTime<Utc>,
 
+    /// The start time before it was postponed, if it was postponed.
+    pub original_start_time: Option<DateTime<Utc>>,
+
     /// The number of the repeat, starting from 1.
     pub repeat_number: usize,
 
+    /// The IANA timezone the schedule was calculated in - set by executor.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+
+    /// Start after another sequence finishes - for use by executor.
+    #[serde(skip)]
+    pub after: Option<After>,
+
+    /// Cancel this step if any of these sequences are cancelled - for use by executor.
+    #[serde(skip)]
+    pub requires: Vec<String>,
+
+    /// Changes to the reminders - for use by executor.
+    #[serde(skip)]
+    pub reminders: Option<Reminders>,
+
     /// The tasks to execute.
     pub tasks: Vec<Task>,
 
@@ -180,6 +303,9 @@ pub struct Sequence {
 
     /// The mark for this task - for use by executor.
     pub mark: Option<Mark>,
+
+    /// The outcome of each task - for use by executor.
+    pub outcomes: Vec<TaskOutcome>,
 }
 
 impl Sequence {
@@ -188,6 +314,41 @@ impl Sequence {
     pub fn is_done(&self) -> bool {
         self.status.is_some_and(|status| status.is_done())
     }
+
+    /// The start time before any postponement.
+    #[must_use]
+    pub fn original_start_time(&self) -> DateTime<Utc> {
+        self.original_start_time.unwrap_or(self.start_time)
+    }
+
+    /// The end time before any postponement.
+    #[must_use]
+    pub fn original_end_time(&self) -> DateTime<Utc> {
+        self.end_time - (self.start_time - self.original_start_time())
+    }
+
+    /// Move the sequence by the given offset from its original time.
+    ///
+    /// An offset of `None` moves the sequence back to its original time.
+    pub fn postpone(&mut self, offset: Option<TimeDelta>) {
+        let original = self.original_start_time();
+        let current = self.start_time - original;
+        let change = offset.unwrap_or_default() - current;
+
+        self.start_time += change;
+        self.end_time += change;
+        self.latest_time += change;
+        self.original_start_time = offset.map(|_| original);
+    }
+
+    fn shift(&mut self, change: TimeDelta) {
+        self.start_time += change;
+        self.end_time += change;
+        self.latest_time += change;
+        if let Some(original) = &mut self.original_start_time {
+            *original += change;
+        }
+    }
 }
 
 fn build_context(
@@ -307,6 +468,18 @@ fn config_to_sequence(
     schedule_date: NaiveDate,
     repeat_number: usize,
 ) -> Sequence {
+    let task_run_if = config
+        .tasks
+        .iter()
+        .map(|src_task| src_task.run_if.clone())
+        .collect();
+
+    let task_expect = config
+        .tasks
+        .iter()
+        .map(|src_task| src_task.expect.clone())
+        .collect();
+
     let tasks = config
         .tasks
         .into_iter()
@@ -332,6 +505,9 @@ fn config_to_sequence(
         importance: config.importance,
         sequence_name: sequence_name.to_string(),
         if_cond: config.if_cond,
+        run_if: config.run_if,
+        task_run_if,
+        task_expect,
         classifications: config.classifications,
         options: config.options,
         zero_time: config.zero_time.unwrap_or(false),
@@ -339,10 +515,16 @@ fn config_to_sequence(
         end_time: *start_time + config.duration,
         duration: config.duration,
         latest_time,
+        original_start_time: None,
         repeat_number,
+        timezone: None,
+        after: None,
+        requires: config.requires.unwrap_or_default(),
+        reminders: config.reminders,
         tasks,
         mark: None,
         status: None,
+        outcomes: Vec::new(),
     }
 }
 
@@ -379,12 +561,19 @@ pub fn get_sequence_with_config(
     let mut start_time = get_corrected_start_time(start_time, &expanded_list);
     let mut sequences: Vec<Sequence> = Vec::with_capacity(expanded_list.len());
 
+    // The step with `after` set, which later steps move with.
+    let mut anchor: Option<(&String, DateTime<Utc>, bool)> = None;
+
     for expanded in &expanded_list {
         let id = expanded.config.id.as_ref().map_or_else(
             || format!("{sequence_name}_{}", expanded.number),
             std::clone::Clone::clone,
         );
-        let sequence = config_to_sequence(
+        if let Some(after) = &expanded.config.after {
+            let cascade = expanded.config.cascade.unwrap_or(true);
+            anchor = Some((after, start_time, cascade));
+        }
+        let mut sequence = config_to_sequence(
             sequence_name,
             expanded.config.clone(),
             &start_time,
@@ -392,6 +581,15 @@ pub fn get_sequence_with_config(
             schedule_date,
             expanded.repeat_number,
         );
+        if let Some((after, anchor_time, cascade)) = anchor {
+            sequence.after = Some(After {
+                id: after.clone(),
+                offset: start_time - anchor_time,
+            });
+            if cascade && !sequence.requires.contains(after) {
+                sequence.requires.push(after.clone());
+            }
+        }
         sequences.push(sequence);
         start_time += expanded.duration;
     }
@@ -498,6 +696,52 @@ pub fn schedule_list_to_sequence(
     Ok(sequences)
 }
 
+/// Move steps that start after another sequence to when that sequence finishes.
+///
+/// The `after` id names a step, the step waits for every step in the same source sequence to
+/// finish. Only sequences with the same schedule date are considered. If the sequence is not
+/// scheduled, the step keeps its own time.
+pub fn resolve_dependencies(sequences: &mut [Sequence]) {
+    // Each pass resolves one more level of chained dependencies.
+    for _ in 0..sequences.len() {
+        let mut changed = false;
+
+        for i in 0..sequences.len() {
+            let sequence = &sequences[i];
+            let Some(after) = &sequence.after else {
+                continue;
+            };
+            let same_day = sequences
+                .iter()
+                .filter(|s| s.schedule_date == sequence.schedule_date);
+            let sources: HashSet<&String> = same_day
+                .clone()
+                .filter(|s| s.id == after.id && s.sequence_name != sequence.sequence_name)
+                .map(|s| &s.sequence_name)
+                .collect();
+            let end_time = same_day
+                .filter(|s| sources.contains(&s.sequence_name))
+                .map(|s| s.end_time)
+                .max();
+            let Some(end_time) = end_time else {
+                continue;
+            };
+
+            let change = end_time + after.offset - sequence.start_time;
+            if change != TimeDelta::zero() {
+                sequences[i].shift(change);
+                changed = true;
+            }
+        }
+
+        if !changed {
+            return;
+        }
+    }
+
+    tracing::error!("Sequence dependencies did not settle, check for cycles");
+}
+
 fn get_corrected_start_time(
     start_time: DateTime<Utc>,
     expanded_list: &Vec<ExpandedConfig>,
@@ -529,6 +773,89 @@ mod tests {
     fn test_load_test_file() {
         let config = load_config(Path::new("test/sequences.yaml")).unwrap();
         assert!(config.contains_key("open_presents"));
+
+        let open_presents = &config["open_presents"];
+        assert!(open_presents[0].run_if.is_none());
+        assert!(open_presents[1].run_if.is_some());
+        assert!(open_presents[1].tasks[0].run_if.is_some());
+        assert!(open_presents[1].tasks[0].expect.is_none());
+
+        let expect = open_presents[2].tasks[0].expect.as_ref().unwrap();
+        assert_eq!(expect.topic, "state/kids/robotica/music");
+        assert_eq!(expect.value, "wake_up");
+        assert_eq!(expect.timeout, Duration::from_secs(30));
+        assert_eq!(expect.retries, 2);
+    }
+
+    #[test]
+    fn test_postpone() {
+        let config = vec![Config {
+            title: "test".to_string(),
+            id: None,
+            importance: Importance::Medium,
+            classifications: None,
+            options: None,
+            if_cond: None,
+            run_if: None,
+            zero_time: Some(true),
+            duration: duration::minutes(5),
+            latest_time: None,
+            repeat_count: Some(2),
+            repeat_time: Some(duration::minutes(15)),
+            after: None,
+            requires: None,
+            cascade: None,
+            reminders: None,
+            tasks: vec![],
+        }];
+
+        let config_map = ConfigMap::from([("test".to_string(), config)]);
+        let mut sequence = get_sequence_with_config(
+            &config_map,
+            NaiveDate::from_ymd_opt(2020, 12, 25).unwrap(),
+            "test",
+            &HashSet::new(),
+            &HashSet::new(),
+            &HashSet::new(),
+            &Utc.with_ymd_and_hms(2020, 12, 25, 0, 0, 0).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(sequence.len(), 2);
+
+        let offset = TimeDelta::minutes(30);
+        for s in &mut sequence {
+            s.postpone(Some(offset));
+        }
+
+        let original = Utc.with_ymd_and_hms(2020, 12, 25, 0, 0, 0).unwrap();
+        assert_eq!(sequence[0].start_time, original + offset);
+        assert_eq!(
+            sequence[0].end_time,
+            original + offset + TimeDelta::minutes(5)
+        );
+        assert_eq!(
+            sequence[0].latest_time,
+            original + offset + TimeDelta::minutes(1)
+        );
+        assert_eq!(sequence[0].original_start_time, Some(original));
+        assert_eq!(
+            sequence[0].original_end_time(),
+            original + TimeDelta::minutes(5)
+        );
+
+        let original = Utc.with_ymd_and_hms(2020, 12, 25, 0, 15, 0).unwrap();
+        assert_eq!(sequence[1].start_time, original + offset);
+        assert_eq!(sequence[1].original_start_time, Some(original));
+
+        // Changing the offset is relative to the original time.
+        sequence[1].postpone(Some(-offset));
+        assert_eq!(sequence[1].start_time, original - offset);
+        assert_eq!(sequence[1].original_start_time, Some(original));
+
+        sequence[1].postpone(None);
+        assert_eq!(sequence[1].start_time, original);
+        assert_eq!(sequence[1].end_time, original + TimeDelta::minutes(5));
+        assert_eq!(sequence[1].original_start_time, None);
     }
 
     #[test]
@@ -541,17 +868,24 @@ mod tests {
                 classifications: Some(HashSet::from(["christmas".to_string()])),
                 options: Some(HashSet::from(["boxing".to_string()])),
                 if_cond: None,
+                run_if: None,
                 zero_time: Some(true),
                 duration: duration::minutes(30),
                 latest_time: None,
                 repeat_count: None,
                 repeat_time: None,
+                after: None,
+                requires: None,
+                cascade: None,
+                reminders: None,
                 tasks: vec![ConfigTask {
                     title: "task 1".to_string(),
                     payload: None,
                     qos: None,
                     retain: None,
                     topics: vec!["test/test".to_string()],
+                    run_if: None,
+                    expect: None,
                 }],
             },
             Config {
@@ -561,17 +895,24 @@ mod tests {
                 classifications: None,
                 options: None,
                 if_cond: None,
+                run_if: None,
                 zero_time: Some(false),
                 duration: duration::minutes(30),
                 latest_time: None,
                 repeat_count: None,
                 repeat_time: None,
+                after: None,
+                requires: None,
+                cascade: None,
+                reminders: None,
                 tasks: vec![ConfigTask {
                     title: "task 2".to_string(),
                     payload: None,
                     qos: None,
                     retain: None,
                     topics: vec!["test/test".to_string()],
+                    run_if: None,
+                    expect: None,
                 }],
             },
         ];
@@ -624,17 +965,24 @@ mod tests {
                 classifications: Some(HashSet::from(["christmas".to_string()])),
                 options: Some(HashSet::from(["boxing".to_string()])),
                 if_cond: None,
+                run_if: None,
                 zero_time: Some(false),
                 duration: duration::minutes(15),
                 latest_time: None,
                 repeat_count: Some(2),
                 repeat_time: None,
+                after: None,
+                requires: None,
+                cascade: None,
+                reminders: None,
                 tasks: vec![ConfigTask {
                     title: "task 1".to_string(),
                     payload: None,
                     qos: None,
                     retain: None,
                     topics: vec!["test/test".to_string()],
+                    run_if: None,
+                    expect: None,
                 }],
             },
             Config {
@@ -644,17 +992,24 @@ mod tests {
                 classifications: None,
                 options: None,
                 if_cond: None,
+                run_if: None,
                 zero_time: Some(false),
                 duration: duration::minutes(15),
                 latest_time: None,
                 repeat_count: Some(2),
                 repeat_time: None,
+                after: None,
+                requires: None,
+                cascade: None,
+                reminders: None,
                 tasks: vec![ConfigTask {
                     title: "task 2".to_string(),
                     payload: None,
                     qos: None,
                     retain: None,
                     topics: vec!["test/test".to_string()],
+                    run_if: None,
+                    expect: None,
                 }],
             },
         ];
@@ -693,17 +1048,24 @@ mod tests {
             classifications: Some(HashSet::from(["christmas".to_string()])),
             options: Some(HashSet::from(["boxing".to_string()])),
             if_cond: None,
+            run_if: None,
             zero_time: Some(false),
             duration: duration::minutes(15),
             latest_time: None,
             repeat_count: Some(0),
             repeat_time: Some(duration::minutes(5)),
+            after: None,
+            requires: None,
+            cascade: None,
+            reminders: None,
             tasks: vec![ConfigTask {
                 title: "task 1".to_string(),
                 payload: None,
                 qos: None,
                 retain: None,
                 topics: vec!["test/test".to_string()],
+                run_if: None,
+                expect: None,
             }],
         }];
 
@@ -722,17 +1084,24 @@ mod tests {
             classifications: Some(HashSet::from(["christmas".to_string()])),
             options: Some(HashSet::from(["boxing".to_string()])),
             if_cond: None,
+            run_if: None,
             zero_time: Some(false),
             duration: duration::minutes(15),
             latest_time: None,
             repeat_count: Some(1),
             repeat_time: Some(duration::minutes(5)),
+            after: None,
+            requires: None,
+            cascade: None,
+            reminders: None,
             tasks: vec![ConfigTask {
                 title: "task 1".to_string(),
                 payload: None,
                 qos: None,
                 retain: None,
                 topics: vec!["test/test".to_string()],
+                run_if: None,
+                expect: None,
             }],
         }];
 
@@ -756,17 +1125,24 @@ mod tests {
                 classifications: Some(HashSet::from(["christmas".to_string()])),
                 options: Some(HashSet::from(["boxing".to_string()])),
                 if_cond: None,
+                run_if: None,
                 zero_time: Some(false),
                 duration: duration::minutes(15),
                 latest_time: None,
                 repeat_count: Some(3),
                 repeat_time: Some(duration::minutes(5)),
+                after: None,
+                requires: None,
+                cascade: None,
+                reminders: None,
                 tasks: vec![ConfigTask {
                     title: "task 1".to_string(),
                     payload: None,
                     qos: None,
                     retain: None,
                     topics: vec!["test/test".to_string()],
+                    run_if: None,
+                    expect: None,
                 }],
             },
             Config {
@@ -776,17 +1152,24 @@ mod tests {
                 classifications: None,
                 options: None,
                 if_cond: None,
+                run_if: None,
                 zero_time: Some(false),
                 duration: duration::minutes(15),
                 latest_time: None,
                 repeat_count: Some(3),
                 repeat_time: None,
+                after: None,
+                requires: None,
+                cascade: None,
+                reminders: None,
                 tasks: vec![ConfigTask {
                     title: "task 2".to_string(),
                     payload: None,
                     qos: None,
                     retain: None,
                     topics: vec!["test/test".to_string()],
+                    run_if: None,
+                    expect: None,
                 }],
             },
         ];
@@ -833,17 +1216,24 @@ mod tests {
             classifications: Some(HashSet::from(["christmas".to_string()])),
             options: Some(HashSet::from(["boxing".to_string()])),
             if_cond: None,
+            run_if: None,
             zero_time: Some(false),
             duration: duration::minutes(15),
             latest_time: None,
             repeat_count: Some(2),
             repeat_time: None,
+            after: None,
+            requires: None,
+            cascade: None,
+            reminders: None,
             tasks: vec![ConfigTask {
                 title: "task 1".to_string(),
                 payload: None,
                 qos: None,
                 retain: None,
                 topics: vec!["test/test".to_string()],
+                run_if: None,
+                expect: None,
             }],
         };
 
@@ -880,17 +1270,24 @@ mod tests {
             classifications: Some(HashSet::from(["christmas".to_string()])),
             options: Some(HashSet::from(["boxing".to_string()])),
             if_cond: None,
+            run_if: None,
             zero_time: Some(true),
             duration: duration::minutes(15),
             latest_time: None,
             repeat_count: Some(2),
             repeat_time: None,
+            after: None,
+            requires: None,
+            cascade: None,
+            reminders: None,
             tasks: vec![ConfigTask {
                 title: "task 1".to_string(),
                 payload: None,
                 qos: None,
                 retain: None,
                 topics: vec!["test/test".to_string()],
+                run_if: None,
+                expect: None,
             }],
         };
 
@@ -927,17 +1324,24 @@ mod tests {
             classifications: Some(HashSet::from(["christmas".to_string()])),
             options: Some(HashSet::from(["boxing".to_string()])),
             if_cond: None,
+            run_if: None,
             zero_time: Some(false),
             duration: duration::minutes(15),
             latest_time: None,
             repeat_count: Some(2),
             repeat_time: None,
+            after: None,
+            requires: None,
+            cascade: None,
+            reminders: None,
             tasks: vec![ConfigTask {
                 title: "task 1".to_string(),
                 payload: None,
                 qos: None,
                 retain: None,
                 topics: vec!["test/test".to_string()],
+                run_if: None,
+                expect: None,
             }],
         };
 
@@ -980,17 +1384,24 @@ mod tests {
                 classifications: Some(HashSet::from(["christmas".to_string()])),
                 options: Some(HashSet::from(["boxing".to_string()])),
                 if_cond: None,
+                run_if: None,
                 zero_time: Some(false),
                 duration: duration::minutes(30),
                 latest_time: None,
                 repeat_count: None,
                 repeat_time: None,
+                after: None,
+                requires: None,
+                cascade: None,
+                reminders: None,
                 tasks: vec![ConfigTask {
                     title: "task 1".to_string(),
                     payload: None,
                     qos: None,
                     retain: None,
                     topics: vec!["test/test".to_string()],
+                    run_if: None,
+                    expect: None,
                 }],
             },
             Config {
@@ -1000,17 +1411,24 @@ mod tests {
                 classifications: None,
                 options: None,
                 if_cond: None,
+                run_if: None,
                 zero_time: Some(true),
                 duration: duration::minutes(30),
                 latest_time: None,
                 repeat_count: None,
                 repeat_time: None,
+                after: None,
+                requires: None,
+                cascade: None,
+                reminders: None,
                 tasks: vec![ConfigTask {
                     title: "task 2".to_string(),
                     payload: None,
                     qos: None,
                     retain: None,
                     topics: vec!["test/test".to_string()],
+                    run_if: None,
+                    expect: None,
                 }],
             },
         ];
@@ -1060,17 +1478,24 @@ mod tests {
             classifications: Some(HashSet::from(["christmas".to_string()])),
             options: Some(HashSet::from(["boxing".to_string()])),
             if_cond: None,
+            run_if: None,
             zero_time: Some(true),
             duration: duration::minutes(30),
             latest_time: None,
             repeat_count: Some(2),
             repeat_time: Some(duration::minutes(10)),
+            after: None,
+            requires: None,
+            cascade: None,
+            reminders: None,
             tasks: vec![ConfigTask {
                 title: "task 1".to_string(),
                 payload: None,
                 qos: None,
                 retain: None,
                 topics: vec!["test/test".to_string()],
+                run_if: None,
+                expect: None,
             }],
         }];
 
@@ -1110,6 +1535,77 @@ mod tests {
         assert_eq!(sequence[1].tasks.len(), 1);
     }
 
+    #[test]
+    fn test_resolve_dependencies() {
+        let step = |id: &str, minutes: u64, after: Option<&str>, cascade: Option<bool>| Config {
+            title: id.to_string(),
+            id: Some(id.to_string()),
+            importance: Importance::Medium,
+            classifications: None,
+            options: None,
+            if_cond: None,
+            run_if: None,
+            zero_time: None,
+            duration: duration::minutes(minutes),
+            latest_time: None,
+            repeat_count: None,
+            repeat_time: None,
+            after: after.map(ToString::to_string),
+            requires: None,
+            cascade,
+            reminders: None,
+            tasks: vec![],
+        };
+
+        let config_map = ConfigMap::from([
+            ("wake_up".to_string(), vec![step("wake_up", 30, None, None)]),
+            (
+                "breakfast".to_string(),
+                vec![
+                    step("breakfast", 10, Some("wake_up"), None),
+                    step("dishes", 5, None, None),
+                ],
+            ),
+            (
+                "news".to_string(),
+                vec![step("news", 5, Some("breakfast"), Some(false))],
+            ),
+        ]);
+
+        let date = NaiveDate::from_ymd_opt(2020, 12, 25).unwrap();
+        let get = |name: &str, hour: u32| {
+            get_sequence_with_config(
+                &config_map,
+                date,
+                name,
+                &HashSet::new(),
+                &HashSet::new(),
+                &HashSet::new(),
+                &Utc.with_ymd_and_hms(2020, 12, 25, hour, 0, 0).unwrap(),
+            )
+            .unwrap()
+        };
+
+        let mut sequences = get("news", 9);
+        sequences.extend(get("breakfast", 8));
+        sequences.extend(get("wake_up", 6));
+        resolve_dependencies(&mut sequences);
+
+        let time = |minute| Utc.with_ymd_and_hms(2020, 12, 25, 6, minute, 0).unwrap();
+        let find = |id: &str| sequences.iter().find(|s| s.id == id).unwrap();
+
+        assert_eq!(find("wake_up").start_time, time(0));
+        assert_eq!(find("breakfast").start_time, time(30));
+        assert_eq!(find("dishes").start_time, time(40));
+
+        // News waits for all of breakfast, including the dishes.
+        assert_eq!(find("news").start_time, time(45));
+
+        assert_eq!(find("breakfast").requires, vec!["wake_up".to_string()]);
+        assert_eq!(find("dishes").requires, vec!["wake_up".to_string()]);
+        assert!(find("news").requires.is_empty());
+    }
+
     #[allow(clippy::too_many_lines)]
     #[test]
     fn test_schedule_to_sequence() {
@@ -1134,17 +1630,24 @@ mod tests {
                 classifications: None,
                 options: None,
                 if_cond: None,
+                run_if: None,
                 zero_time: Some(true),
                 duration: duration::minutes(30),
                 latest_time: None,
                 repeat_count: None,
                 repeat_time: None,
+                after: None,
+                requires: None,
+                cascade: None,
+                reminders: None,
                 tasks: vec![ConfigTask {
                     title: "task 1".to_string(),
                     payload: None,
                     qos: None,
                     retain: None,
                     topics: vec!["test/test".to_string()],
+                    run_if: None,
+                    expect: None,
                 }],
             },
             Config {
@@ -1154,17 +1657,24 @@ mod tests {
                 classifications: None,
                 options: None,
                 if_cond: None,
+                run_if: None,
                 zero_time: Some(false),
                 duration: duration::minutes(30),
                 latest_time: None,
                 repeat_count: None,
                 repeat_time: None,
+                after: None,
+                requires: None,
+                cascade: None,
+                reminders: None,
                 tasks: vec![ConfigTask {
                     title: "task 2".to_string(),
                     payload: None,
                     qos: None,
                     retain: None,
                     topics: vec!["test/test".to_string()],
+                    run_if: None,
+                    expect: None,
                 }],
             },
         ];
@@ -1177,17 +1687,24 @@ mod tests {
                 classifications: None,
                 options: None,
                 if_cond: None,
+                run_if: None,
                 zero_time: Some(true),
                 duration: duration::minutes(30),
                 latest_time: None,
                 repeat_count: None,
                 repeat_time: None,
+                after: None,
+                requires: None,
+                cascade: None,
+                reminders: None,
                 tasks: vec![ConfigTask {
                     title: "task 3".to_string(),
                     payload: None,
                     qos: None,
                     retain: None,
                     topics: vec!["test/test".to_string()],
+                    run_if: None,
+                    expect: None,
                 }],
             },
             Config {
@@ -1197,17 +1714,24 @@ mod tests {
                 classifications: None,
                 options: None,
                 if_cond: None,
+                run_if: None,
                 zero_time: Some(false),
                 duration: duration::minutes(30),
                 latest_time: None,
                 repeat_count: None,
                 repeat_time: None,
+                after: None,
+                requires: None,
+                cascade: None,
+                reminders: None,
                 tasks: vec![ConfigTask {
                     title: "task 4".to_string(),
                     payload: None,
                     qos: None,
                     retain: None,
                     topics: vec!["test/test".to_string()],
+                    run_if: None,
+                    expect: None,
                 }],
             },
         ];
@@ -1299,17 +1823,24 @@ mod tests {
                     classifications: None,
                     options: None,
                     if_cond: None,
+                    run_if: None,
                     zero_time: Some(true),
                     duration: duration::minutes(30),
                     latest_time: None,
                     repeat_count: None,
                     repeat_time: None,
+                    after: None,
+                    requires: None,
+                    cascade: None,
+                    reminders: None,
                     tasks: vec![ConfigTask {
                         title: "task".to_string(),
                         payload: None,
                         qos: None,
                         retain: None,
                         topics: vec!["test/test".to_string()],
+                        run_if: None,
+                        expect: None,
                     }],
                 }],
             ),
@@ -1322,17 +1853,24 @@ mod tests {
                     classifications: None,
                     options: None,
                     if_cond: None,
+                    run_if: None,
                     zero_time: Some(false),
                     duration: duration::minutes(30),
                     latest_time: None,
                     repeat_count: None,
                     repeat_time: None,
+                    after: None,
+                    requires: None,
+                    cascade: None,
+                    reminders: None,
                     tasks: vec![ConfigTask {
                         title: "task 1".to_string(),
                         payload: None,
                         qos: None,
                         retain: None,
                         topics: vec!["test/test".to_string()],
+                        run_if: None,
+                        expect: None,
                     }],
                 }],
             ),
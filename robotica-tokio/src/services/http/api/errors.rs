@@ -7,6 +7,9 @@ use robotica_common::robotica::http_api::api_error;
 use thiserror::Error;
 use tracing::error;
 
+#[cfg(feature = "scheduler")]
+use crate::scheduling::executor::RequestError;
+
 #[derive(Debug, Error)]
 pub enum ResponseError {
     #[error("Authentication failed")]
@@ -20,6 +23,10 @@ pub enum ResponseError {
 
     #[error("Object does not exist")]
     NotFoundError(),
+
+    #[cfg(feature = "scheduler")]
+    #[error("Executor error: {0}")]
+    ExecutorError(#[from] RequestError),
 }
 
 impl IntoResponse for ResponseError {
@@ -42,6 +49,19 @@ impl IntoResponse for ResponseError {
                 let error = api_error("Not Found");
                 (StatusCode::NOT_FOUND, Json(error)).into_response()
             }
+            #[cfg(feature = "scheduler")]
+            Self::ExecutorError(
+                err @ (RequestError::SequenceNotFound(_) | RequestError::MarkNotFound(_)),
+            ) => {
+                let error = api_error(err.to_string());
+                (StatusCode::NOT_FOUND, Json(error)).into_response()
+            }
+            #[cfg(feature = "scheduler")]
+            Self::ExecutorError(err) => {
+                error!("Executor Error: {}", err);
+                let error = api_error("Executor Error");
+                (StatusCode::SERVICE_UNAVAILABLE, Json(error)).into_response()
+            }
         }
     }
 }
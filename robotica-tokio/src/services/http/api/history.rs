@@ -0,0 +1,55 @@
+use axum::extract::{Query, State};
+use axum::routing::get;
+use axum::Json;
+use chrono::{DateTime, Utc};
+use robotica_common::robotica::http_api::ApiResponse;
+use robotica_common::scheduler::HistoryPage;
+use serde::Deserialize;
+use tap::Pipe;
+use tower_sessions::Session;
+
+use crate::database::schedule_history::{list_history, HistoryFilter};
+
+use super::super::{get_user, HttpState};
+use super::errors::ResponseError;
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+
+pub fn router(state: HttpState) -> axum::Router {
+    axum::Router::new()
+        .route("/", get(list_handler))
+        .with_state(state)
+}
+
+#[derive(Deserialize)]
+pub struct HistoryQuery {
+    sequence_id: Option<String>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    before: Option<i64>,
+    limit: Option<u32>,
+}
+
+pub async fn list_handler(
+    State(postgres): State<sqlx::PgPool>,
+    session: Session,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<ApiResponse<HistoryPage>>, ResponseError> {
+    if get_user(&session).await.is_none() {
+        return Err(ResponseError::AuthenticationFailed);
+    }
+
+    let filter = HistoryFilter {
+        sequence_id: query.sequence_id,
+        from: query.from,
+        to: query.to,
+        before: query.before,
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+
+    list_history(&postgres, &filter, limit)
+        .await?
+        .pipe(ApiResponse::success)
+        .pipe(Json)
+        .pipe(Ok)
+}
@@ -0,0 +1,92 @@
+use axum::extract::Path;
+use axum::routing::{delete, get, post};
+use axum::{extract::State, Json};
+use robotica_common::robotica::http_api::ApiResponse;
+use robotica_common::scheduler::Mark;
+use tap::Pipe;
+use tower_sessions::Session;
+
+use crate::scheduling::executor::{ExecutorTx, RequestError};
+
+use super::super::{get_user, HttpState};
+use super::errors::ResponseError;
+
+pub fn router(state: HttpState) -> axum::Router {
+    axum::Router::new()
+        .route("/", get(list_handler))
+        .route("/", post(create_handler))
+        .route("/{id}", get(get_handler))
+        .route("/{id}", delete(delete_handler))
+        .with_state(state)
+}
+
+fn get_executor(executor: Option<ExecutorTx>) -> Result<ExecutorTx, ResponseError> {
+    executor.ok_or(ResponseError::ExecutorError(RequestError::NotRunning))
+}
+
+pub async fn list_handler(
+    State(executor): State<Option<ExecutorTx>>,
+    session: Session,
+) -> Result<Json<ApiResponse<Vec<Mark>>>, ResponseError> {
+    if get_user(&session).await.is_none() {
+        return Err(ResponseError::AuthenticationFailed);
+    }
+
+    get_executor(executor)?
+        .list_marks()
+        .await?
+        .pipe(ApiResponse::success)
+        .pipe(Json)
+        .pipe(Ok)
+}
+
+pub async fn get_handler(
+    State(executor): State<Option<ExecutorTx>>,
+    session: Session,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<Mark>>, ResponseError> {
+    if get_user(&session).await.is_none() {
+        return Err(ResponseError::AuthenticationFailed);
+    }
+
+    get_executor(executor)?
+        .get_mark(id)
+        .await?
+        .pipe(ApiResponse::success)
+        .pipe(Json)
+        .pipe(Ok)
+}
+
+async fn create_handler(
+    State(executor): State<Option<ExecutorTx>>,
+    session: Session,
+    Json(mark): Json<Mark>,
+) -> Result<Json<ApiResponse<Mark>>, ResponseError> {
+    if get_user(&session).await.is_none() {
+        return Err(ResponseError::AuthenticationFailed);
+    }
+
+    get_executor(executor)?
+        .create_mark(mark)
+        .await?
+        .pipe(ApiResponse::success)
+        .pipe(Json)
+        .pipe(Ok)
+}
+
+async fn delete_handler(
+    State(executor): State<Option<ExecutorTx>>,
+    session: Session,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<()>>, ResponseError> {
+    if get_user(&session).await.is_none() {
+        return Err(ResponseError::AuthenticationFailed);
+    }
+
+    get_executor(executor)?
+        .delete_mark(id)
+        .await?
+        .pipe(ApiResponse::success)
+        .pipe(Json)
+        .pipe(Ok)
+}
@@ -1,2 +1,8 @@
 pub(super) mod errors;
+#[cfg(feature = "scheduler")]
+pub(super) mod history;
+#[cfg(feature = "scheduler")]
+pub(super) mod marks;
+#[cfg(feature = "scheduler")]
+pub(super) mod schedule;
 pub(super) mod zones;
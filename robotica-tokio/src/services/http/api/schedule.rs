@@ -0,0 +1,215 @@
+use std::fmt::Write;
+use std::sync::Arc;
+
+use axum::extract::{Query, State};
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use icalendar::{Calendar, Component, Event, EventLike, EventStatus};
+use robotica_common::scheduler::{Importance, Status};
+use serde::Deserialize;
+use subtle::ConstantTimeEq;
+use tower_sessions::Session;
+
+use crate::scheduling::executor::{ExecutorTx, RequestError};
+use crate::scheduling::sequencer::Sequence;
+
+use super::super::{get_user, Config, HttpState};
+use super::errors::ResponseError;
+
+pub fn router(state: HttpState) -> axum::Router {
+    axum::Router::new()
+        .route("/schedule.ics", get(ics_handler))
+        .with_state(state)
+}
+
+#[derive(Deserialize)]
+pub struct IcsQuery {
+    token: Option<String>,
+}
+
+/// Calendar apps cannot log in, so they can use the configured token instead.
+async fn is_authorized(session: &Session, config: &Config, query: &IcsQuery) -> bool {
+    let token_ok = match (&config.ics_token, &query.token) {
+        (Some(expected), Some(token)) => token_matches(expected, token),
+        _ => false,
+    };
+    token_ok || get_user(session).await.is_some()
+}
+
+/// Compare in constant time, so the token cannot be guessed from response times.
+fn token_matches(expected: &str, token: &str) -> bool {
+    expected.as_bytes().ct_eq(token.as_bytes()).into()
+}
+
+fn sequence_to_event(sequence: &Sequence) -> Event {
+    let uid = format!(
+        "{}-{}-{}@robotica",
+        sequence.schedule_date, sequence.id, sequence.repeat_number
+    );
+
+    let mut description = String::new();
+    for (i, task) in sequence.tasks.iter().enumerate() {
+        let _ = write!(description, "{}: {task}", task.title);
+        if let Some(outcome) = sequence.outcomes.get(i) {
+            let _ = write!(description, " ({outcome})");
+        }
+        description.push('\n');
+    }
+    if let Some(mark) = &sequence.mark {
+        let _ = writeln!(description, "Mark: {}", mark.status);
+    }
+
+    let status = match sequence.status {
+        Some(Status::Cancelled) => EventStatus::Cancelled,
+        _ => EventStatus::Confirmed,
+    };
+
+    Event::new()
+        .uid(&uid)
+        .summary(&sequence.title)
+        .description(description.trim_end())
+        .starts(sequence.start_time)
+        .ends(sequence.end_time)
+        .status(status)
+        .done()
+}
+
+/// Sequences less important than `min_importance` are left out.
+fn sequences_to_calendar(sequences: &[Sequence], min_importance: Importance) -> Calendar {
+    let mut calendar = Calendar::new();
+    calendar.name("Robotica");
+    for sequence in sequences
+        .iter()
+        .filter(|sequence| sequence.importance >= min_importance)
+    {
+        calendar.push(sequence_to_event(sequence));
+    }
+    calendar.done()
+}
+
+pub async fn ics_handler(
+    State(executor): State<Option<ExecutorTx>>,
+    State(config): State<Arc<Config>>,
+    session: Session,
+    Query(query): Query<IcsQuery>,
+) -> Result<impl IntoResponse, ResponseError> {
+    if !is_authorized(&session, &config, &query).await {
+        return Err(ResponseError::AuthenticationFailed);
+    }
+
+    let sequences = executor
+        .ok_or(ResponseError::ExecutorError(RequestError::NotRunning))?
+        .list_sequences()
+        .await?;
+
+    let calendar = sequences_to_calendar(&sequences, config.ics_min_importance);
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        calendar.to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use robotica_common::robotica::tasks::{Payload, Task};
+    use robotica_common::{
+        mqtt::{QoS, Retain},
+        scheduler::TaskOutcome,
+    };
+
+    use super::*;
+
+    fn sequence(id: &str, repeat_number: usize, importance: Importance) -> Sequence {
+        let start_time = Utc.with_ymd_and_hms(2026, 10, 18, 6, 30, 0).unwrap();
+        let end_time = Utc.with_ymd_and_hms(2026, 10, 18, 6, 45, 0).unwrap();
+        Sequence {
+            title: format!("Title {id}"),
+            id: id.to_string(),
+            schedule_date: NaiveDate::from_ymd_opt(2026, 10, 18).unwrap(),
+            importance,
+            sequence_name: id.to_string(),
+            if_cond: None,
+            run_if: None,
+            task_run_if: vec![None],
+            task_expect: vec![None],
+            classifications: None,
+            options: None,
+            zero_time: false,
+            start_time,
+            end_time,
+            duration: std::time::Duration::from_secs(15 * 60),
+            latest_time: end_time,
+            original_start_time: None,
+            repeat_number,
+            timezone: None,
+            after: None,
+            requires: vec![],
+            reminders: None,
+            tasks: vec![Task {
+                title: "Pump".to_string(),
+                payload: Payload::String("on".to_string()),
+                qos: QoS::ExactlyOnce,
+                retain: Retain::NoRetain,
+                topics: vec!["command/pump".to_string()],
+            }],
+            status: None,
+            mark: None,
+            outcomes: vec![TaskOutcome::Confirmed],
+        }
+    }
+
+    #[test]
+    fn test_token_matches() {
+        assert!(token_matches("secret", "secret"));
+        assert!(!token_matches("secret", "secreT"));
+        assert!(!token_matches("secret", "secret2"));
+        assert!(!token_matches("secret", ""));
+    }
+
+    #[test]
+    fn test_sequence_to_event() {
+        let event = sequence_to_event(&sequence("pump", 1, Importance::High));
+        assert_eq!(event.get_uid(), Some("2026-10-18-pump-1@robotica"));
+
+        // The UID must not change when the sequence is rendered again.
+        let again = sequence_to_event(&sequence("pump", 1, Importance::High));
+        assert_eq!(event.get_uid(), again.get_uid());
+
+        // But each repeat is a different event.
+        let repeat = sequence_to_event(&sequence("pump", 2, Importance::High));
+        assert_ne!(event.get_uid(), repeat.get_uid());
+
+        let mut calendar = Calendar::new();
+        calendar.push(event);
+        let text = calendar.done().to_string();
+        assert!(text.contains("DTSTART:20261018T063000Z"), "{text}");
+        assert!(text.contains("DTEND:20261018T064500Z"), "{text}");
+        assert!(text.contains("SUMMARY:Title pump"), "{text}");
+        assert!(text.contains("STATUS:CONFIRMED"), "{text}");
+    }
+
+    #[test]
+    fn test_sequences_to_calendar() {
+        let sequences = vec![
+            sequence("low", 1, Importance::Low),
+            sequence("medium", 1, Importance::Medium),
+            sequence("high", 1, Importance::High),
+        ];
+
+        let text = sequences_to_calendar(&sequences, Importance::Medium).to_string();
+        assert!(!text.contains("UID:2026-10-18-low-1@robotica"), "{text}");
+        assert!(text.contains("UID:2026-10-18-medium-1@robotica"), "{text}");
+        assert!(text.contains("UID:2026-10-18-high-1@robotica"), "{text}");
+
+        let text = sequences_to_calendar(&sequences, Importance::High).to_string();
+        assert!(!text.contains("UID:2026-10-18-medium-1@robotica"), "{text}");
+        assert!(text.contains("UID:2026-10-18-high-1@robotica"), "{text}");
+
+        let text = sequences_to_calendar(&sequences, Importance::Low).to_string();
+        assert!(text.contains("UID:2026-10-18-low-1@robotica"), "{text}");
+    }
+}
@@ -31,13 +31,18 @@ use tower_sessions::{Expiry, Session, SessionManagerLayer};
 use tower_sessions_sqlx_store::PostgresStore;
 use tracing::error;
 
+use robotica_common::scheduler::Importance;
 use robotica_common::user::User;
 
+#[cfg(feature = "scheduler")]
+use crate::scheduling::executor::ExecutorTx;
 use crate::services::http::websocket::websocket_handler;
 use crate::services::mqtt::MqttTx;
 use crate::spawn;
 
 use self::api::zones;
+#[cfg(feature = "scheduler")]
+use self::api::{history, marks, schedule};
 use self::errors::ResponseError;
 use self::oidc::Client;
 
@@ -70,6 +75,13 @@ pub struct Config {
 
     /// The server hostname in MQTT topics.
     pub instance: String,
+
+    /// Token that allows calendar apps to fetch the schedule feed without logging in.
+    pub ics_token: Option<String>,
+
+    /// Sequences less important than this are left out of the schedule feed.
+    #[serde(default)]
+    pub ics_min_importance: Importance,
 }
 
 impl Config {
@@ -131,6 +143,8 @@ struct HttpState {
     oidc_client: Arc<ArcSwap<Option<Client>>>,
     manifest: Arc<Manifest>,
     postgres: sqlx::PgPool,
+    #[cfg(feature = "scheduler")]
+    executor: Option<ExecutorTx>,
 }
 
 /// An error running the HTTP service.
@@ -143,11 +157,19 @@ pub enum HttpError {
 
 /// Run the HTTP service.
 ///
+/// With the `scheduler` feature, if an executor is supplied, the API can be used
+/// to manage its marks and the schedule is published as an iCal feed.
+///
 /// # Errors
 ///
 /// This function will return an error if there is a problem configuring the HTTP service.
 #[allow(clippy::unused_async)]
-pub async fn run(mqtt: MqttTx, config: Config, postgres: sqlx::PgPool) -> Result<(), HttpError> {
+pub async fn run(
+    mqtt: MqttTx,
+    config: Config,
+    postgres: sqlx::PgPool,
+    #[cfg(feature = "scheduler")] executor: Option<ExecutorTx>,
+) -> Result<(), HttpError> {
     let session_store = PostgresStore::new(postgres.clone());
 
     tokio::task::spawn(
@@ -203,6 +225,8 @@ pub async fn run(mqtt: MqttTx, config: Config, postgres: sqlx::PgPool) -> Result
         oidc_client,
         manifest,
         postgres,
+        #[cfg(feature = "scheduler")]
+        executor,
     };
 
     let http_listener = state.config.http_listener.clone();
@@ -212,7 +236,15 @@ pub async fn run(mqtt: MqttTx, config: Config, postgres: sqlx::PgPool) -> Result
         .route("/websocket", get(websocket_handler))
         .route("/logout", get(logout_handler))
         .fallback(fallback_handler)
-        .with_state(state.clone())
+        .with_state(state.clone());
+
+    #[cfg(feature = "scheduler")]
+    let app = app
+        .nest("/api/history", history::router(state.clone()))
+        .nest("/api/marks", marks::router(state.clone()))
+        .nest("/api/schedule", schedule::router(state.clone()));
+
+    let app = app
         .nest("/api/zones", zones::router(state))
         .layer(session_layer)
         .layer(ServiceBuilder::new().layer(TraceLayer::new_for_http()));
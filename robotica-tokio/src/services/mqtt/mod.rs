@@ -235,6 +235,52 @@ pub fn mqtt_channel() -> (MqttTx, MqttRx) {
     (MqttTx(tx.clone()), MqttRx { tx, rx })
 }
 
+/// Answer subscriptions locally instead of connecting to a broker, for tests.
+///
+/// Retained messages are delivered when their topic is subscribed. Every sent
+/// message is passed to `on_send`, which returns the messages to publish in reply.
+#[cfg(test)]
+pub(crate) fn run_test_broker<F>(channel: MqttRx, retained: Vec<MqttMessage>, mut on_send: F)
+where
+    F: FnMut(&MqttMessage) -> Vec<MqttMessage> + Send + 'static,
+{
+    type Pipe = (generic::Sender<MqttMessage>, generic::Receiver<MqttMessage>);
+
+    spawn(async move {
+        let mut rx = channel.rx;
+        let mut pipes: std::collections::HashMap<String, Pipe> = std::collections::HashMap::new();
+
+        while let Some(command) = rx.recv().await {
+            let publish = match command {
+                MqttCommand::Subscribe(topic, tx) => {
+                    let (_, pipe_rx) = pipes
+                        .entry(topic.clone())
+                        .or_insert_with(|| generic::create_pipe(&topic));
+                    let _ = tx.send(Ok(pipe_rx.clone()));
+                    retained
+                        .iter()
+                        .filter(|msg| msg.topic == topic)
+                        .cloned()
+                        .collect()
+                }
+                MqttCommand::MqttOut(msg) => on_send(&msg),
+                MqttCommand::Unsubscribe(_) => Vec::new(),
+            };
+
+            for msg in publish {
+                if let Some((pipe_tx, _)) = pipes.get(&msg.topic) {
+                    // Like a real broker, deliver after the subscriber is listening.
+                    let pipe_tx = pipe_tx.clone();
+                    spawn(async move {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                        pipe_tx.try_send(msg);
+                    });
+                }
+            }
+        }
+    });
+}
+
 /// Credentials for MQTT
 #[derive(Deserialize, Default)]
 #[serde(tag = "type")]
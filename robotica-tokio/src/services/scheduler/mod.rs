@@ -1,126 +1,224 @@
 //! Scheduler service for sending scheduled values at specific times of day.
 //!
-//! Uses local time for scheduling, but converts to UTC for actual scheduling to handle DST changes.
-use chrono::{NaiveTime, TimeZone};
-use robotica_common::datetime::{duration, utc_now};
-use tracing::debug;
+//! Uses the given timezone for scheduling, but converts to UTC for actual scheduling to handle DST
+//! changes.
+//! Times may also be relative to solar events, in which case a location is required.
+//!
+//! Entries may be restricted to certain days, and may ramp towards their value from the previous
+//! entry instead of changing in a single step.
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use chrono::{Datelike, NaiveTime, TimeDelta, TimeZone};
+use chrono_tz::Tz;
+use robotica_common::datetime::{utc_now, Date, Weekday};
+use robotica_common::solar::{Location, TimeOfDay};
+use robotica_macro::time_delta_constant;
+use serde::Deserialize;
+use tracing::{debug, error};
 
 use crate::{pipes::stateful, spawn};
 
+/// Get the classification tags for a date.
+pub type Classifier = Arc<dyn Fn(Date) -> HashSet<String> + Send + Sync>;
+
+/// How an entry's value is reached from the previous entry.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    /// Change to the value at the scheduled time.
+    #[default]
+    Step,
+    /// Ramp at a constant rate from the previous value, arriving at the scheduled time.
+    Linear,
+    /// Ramp from the previous value, easing in and out.
+    Smooth,
+}
+
+impl Interpolation {
+    fn ease(self, fraction: f64) -> f64 {
+        let fraction = fraction.clamp(0.0, 1.0);
+        match self {
+            Self::Step => 0.0,
+            Self::Linear => fraction,
+            Self::Smooth => fraction * fraction * 2.0f64.mul_add(-fraction, 3.0),
+        }
+    }
+}
+
+/// A value that can be interpolated between two scheduled values.
+pub trait Interpolate: Sized {
+    /// Get the value `fraction` of the way from `self` to `other`.
+    #[must_use]
+    fn interpolate(&self, other: &Self, fraction: f64) -> Self;
+}
+
+impl Interpolate for f64 {
+    fn interpolate(&self, other: &Self, fraction: f64) -> Self {
+        (other - self).mul_add(fraction, *self)
+    }
+}
+
+impl Interpolate for f32 {
+    #[allow(clippy::cast_possible_truncation)]
+    fn interpolate(&self, other: &Self, fraction: f64) -> Self {
+        f64::from(*self).interpolate(&f64::from(*other), fraction) as Self
+    }
+}
+
+macro_rules! interpolate_integer {
+    ($($t:ty),*) => {
+        $(
+            impl Interpolate for $t {
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                fn interpolate(&self, other: &Self, fraction: f64) -> Self {
+                    f64::from(*self)
+                        .interpolate(&f64::from(*other), fraction)
+                        .round() as Self
+                }
+            }
+        )*
+    };
+}
+
+interpolate_integer!(u8, u16, u32, i32);
+
 /// An entry in the scheduler.
-#[derive(Debug)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Entry<T> {
     /// The time of day to send the value.
-    pub scheduled_time: NaiveTime,
+    #[serde(rename = "time")]
+    pub scheduled_time: TimeOfDay,
     /// The value to send.
     pub value: T,
+    /// How the value is reached from the previous entry.
+    #[serde(default)]
+    pub interpolation: Interpolation,
+    /// Only use this entry on these days of the week.
+    #[serde(default)]
+    pub days: Option<Vec<Weekday>>,
+    /// Only use this entry on or after this date.
+    #[serde(default)]
+    pub start: Option<Date>,
+    /// Only use this entry on or before this date.
+    #[serde(default)]
+    pub stop: Option<Date>,
+    /// Only use this entry if the date has any of these tags.
+    #[serde(default)]
+    pub today: Option<Vec<String>>,
+    /// Only use this entry if the next date has any of these tags.
+    #[serde(default)]
+    pub tomorrow: Option<Vec<String>>,
 }
+
+impl<T> Entry<T> {
+    /// Create an entry that is used every day.
+    pub const fn new(scheduled_time: TimeOfDay, value: T) -> Self {
+        Self {
+            scheduled_time,
+            value,
+            interpolation: Interpolation::Step,
+            days: None,
+            start: None,
+            stop: None,
+            today: None,
+            tomorrow: None,
+        }
+    }
+
+    const fn needs_tags(&self) -> bool {
+        self.today.is_some() || self.tomorrow.is_some()
+    }
+
+    fn is_active(&self, date: Date, today: &HashSet<String>, tomorrow: &HashSet<String>) -> bool {
+        let days_ok = self
+            .days
+            .as_ref()
+            .is_none_or(|days| days.contains(&date.weekday()));
+        let start_ok = self.start.is_none_or(|start| date >= start);
+        let stop_ok = self.stop.is_none_or(|stop| date <= stop);
+        days_ok
+            && start_ok
+            && stop_ok
+            && is_tag_ok(today, self.today.as_ref())
+            && is_tag_ok(tomorrow, self.tomorrow.as_ref())
+    }
+}
+
+fn is_tag_ok(tags: &HashSet<String>, requirements: Option<&Vec<String>>) -> bool {
+    requirements.is_none_or(|requirements| requirements.iter().any(|r| tags.contains(r)))
+}
+
+#[derive(Debug)]
 struct UtcEntry<T> {
     scheduled_time: chrono::DateTime<chrono::Utc>,
-    latest_time: chrono::DateTime<chrono::Utc>,
+    interpolation: Interpolation,
     value: T,
 }
 
 const MIDNIGHT: NaiveTime = NaiveTime::from_hms_opt(0, 0, 0).unwrap();
 const FALLBACK: chrono::NaiveTime = NaiveTime::from_hms_opt(3, 0, 0).unwrap();
 
+/// How often to update the value while ramping between entries.
+const RAMP_STEP: TimeDelta = time_delta_constant!(1 minutes);
+
 /// Create a scheduler pipe that sends the scheduled values at the specified times of day.
+///
+/// Times of day and dates are in `timezone`.
+///
+/// Entries restricted by `today` or `tomorrow` tags need a `classifier`; without one they are
+/// never used.
 #[must_use]
-pub fn scheduler<T>(name: &str, entries: Vec<Entry<T>>) -> stateful::Receiver<T>
+pub fn scheduler<T>(
+    name: &str,
+    entries: Vec<Entry<T>>,
+    location: Option<Location>,
+    classifier: Option<Classifier>,
+    timezone: Tz,
+) -> stateful::Receiver<T>
 where
-    T: std::fmt::Debug + Clone + PartialEq + Send + Sync + 'static,
+    T: Interpolate + std::fmt::Debug + Clone + PartialEq + Send + Sync + 'static,
 {
     let (tx_out, rx_out) = stateful::create_pipe(name);
     let name = name.to_string();
 
-    spawn(async move {
-        let now = utc_now();
-        let mut got_date = now.with_timezone(&chrono::Local).date_naive();
-        let mut utc_entries = Vec::with_capacity(entries.len() * 2);
-
-        {
-            let yesterday = utc_now().date_naive().pred_opt();
-            if let Some(yesterday) = yesterday {
-                utc_entries.extend(get_utc_entries_for_date(&entries, yesterday));
-            }
-
-            utc_entries.extend(get_utc_entries_for_date(&entries, got_date));
-            utc_entries.sort_by_key(|e| e.scheduled_time);
-        }
+    if classifier.is_none() && entries.iter().any(Entry::needs_tags) {
+        error!("{name}: Entries require tags but there is no classifier, they will be ignored.");
+    }
 
-        let latest_time = utc_entries
-            .iter()
-            .filter(|e| e.scheduled_time < now)
-            .max_by_key(|e| e.scheduled_time);
-
-        if let Some(latest_time) = latest_time {
-            // If we found one, send it as the initial value
-            debug!(
-                "{name}: Sending initial scheduled entry at {}: {:?}",
-                latest_time.scheduled_time, latest_time.value
-            );
-            tx_out.try_send(latest_time.value.clone());
-        }
+    spawn(async move {
+        let mut got_date = None;
+        let mut utc_entries = Vec::new();
 
         loop {
             let now = utc_now();
-            let date = now.with_timezone(&chrono::Local).date_naive();
+            let date = now.with_timezone(&timezone).date_naive();
 
-            if date != got_date {
+            if got_date != Some(date) {
                 debug!("{name}: Date changed, recalculating schedule.");
-                utc_entries.clear();
-                utc_entries.extend(get_utc_entries_for_date(&entries, date));
-                utc_entries.sort_by_key(|e| e.scheduled_time);
-                got_date = date;
+                utc_entries = get_utc_entries_around_date(
+                    &entries,
+                    date,
+                    location.as_ref(),
+                    classifier.as_ref(),
+                    &timezone,
+                );
+                got_date = Some(date);
             }
 
-            // Get and remove the first entry in utc_entries
-            let next_entry = loop {
-                match utc_entries.first() {
-                    // If the entry is in the past, skip it.
-                    Some(e) if e.latest_time < now => {
-                        // This should never fail, but just in case
-                        if let Some(e) = safe_remove_first(&mut utc_entries) {
-                            debug!(
-                                "{name}: Skipping past scheduled entry at {}: {:?}",
-                                e.scheduled_time, e.value
-                            );
-                        }
-                    }
-                    // If the entry is currently due, send it.
-                    Some(e) if e.scheduled_time <= now => {
-                        // This should never fail, but just in case
-                        if let Some(e) = safe_remove_first(&mut utc_entries) {
-                            debug!(
-                                "{name}: Sending scheduled entry at {}: {:?}",
-                                e.scheduled_time, e.value
-                            );
-                            tx_out.try_send(e.value.clone());
-                        }
-                    }
-                    // If the entry is in the future, return it.
-                    // It is important that we do not remove it from the list yet.
-                    Some(e) => break Some(e),
-                    // No more entries
-                    None => break None,
-                }
-            };
+            let (value, next_change) = get_value_at(&utc_entries, now);
+
+            if let Some(value) = value {
+                debug!("{name}: Scheduled value is {value:?}");
+                tx_out.try_send(value);
+            }
 
             // Determine when to sleep until
-            let sleep_until = next_entry.map_or_else(
-                || {
-                    let midnight = get_next_midnight_from_date(now, date);
-                    debug!("{name}: No more scheduled entries today, sleeping until {midnight}.");
-                    midnight
-                },
-                |entry| {
-                    debug!(
-                        "Next scheduled entry at {}, sleeping until then.",
-                        entry.scheduled_time
-                    );
-                    entry.scheduled_time
-                },
-            );
+            let sleep_until = next_change.unwrap_or_else(|| {
+                let midnight = get_next_midnight_from_date(now, date, &timezone);
+                debug!("{name}: No more scheduled entries today, sleeping until {midnight}.");
+                midnight
+            });
 
             // Calculate duration of sleep
             let duration = sleep_until - now;
@@ -141,47 +239,116 @@ where
     rx_out
 }
 
-fn safe_remove_first<T>(v: &mut Vec<T>) -> Option<T> {
-    if v.is_empty() {
-        None
-    } else {
-        Some(v.remove(0))
+/// Get the value at `now`, and when it will next change.
+fn get_value_at<T: Interpolate + Clone>(
+    utc_entries: &[UtcEntry<T>],
+    now: chrono::DateTime<chrono::Utc>,
+) -> (Option<T>, Option<chrono::DateTime<chrono::Utc>>) {
+    let split = utc_entries.partition_point(|e| e.scheduled_time <= now);
+    let prev = split.checked_sub(1).and_then(|i| utc_entries.get(i));
+    let next = utc_entries.get(split);
+
+    match (prev, next) {
+        (Some(prev), Some(next)) if next.interpolation != Interpolation::Step => {
+            let total = (next.scheduled_time - prev.scheduled_time).num_milliseconds();
+            let elapsed = (now - prev.scheduled_time).num_milliseconds();
+            #[allow(clippy::cast_precision_loss)]
+            let fraction = if total > 0 {
+                elapsed as f64 / total as f64
+            } else {
+                1.0
+            };
+            let value = prev
+                .value
+                .interpolate(&next.value, next.interpolation.ease(fraction));
+            let next_change = (now + RAMP_STEP).min(next.scheduled_time);
+            (Some(value), Some(next_change))
+        }
+        (prev, next) => (
+            prev.map(|e| e.value.clone()),
+            next.map(|e| e.scheduled_time),
+        ),
     }
 }
 
-fn get_utc_entries_for_date<T: Clone>(
+/// Get the entries for the day before, the day, and the day after, so that values carry over
+/// and ramp across midnight.
+fn get_utc_entries_around_date<T: Clone>(
     entries: &[Entry<T>],
-    date: chrono::NaiveDate,
-) -> impl Iterator<Item = UtcEntry<T>> + '_ {
-    let utc_entries = entries.iter().map(move |e| {
-        let scheduled_time = chrono::Local
-            .from_local_datetime(&chrono::NaiveDateTime::new(date, e.scheduled_time))
-            .earliest()
-            .or_else(|| {
-                debug!("Could not convert local datetime to UTC, using 3am as fallback.");
-                chrono::Local
-                    .from_local_datetime(&chrono::NaiveDateTime::new(date, FALLBACK))
-                    .earliest()
-            })
-            .map_or_else(
-                || {
-                    debug!("Could not convert 3am to UTC, using now as fallback.");
-                    utc_now()
-                },
-                |dt| dt.with_timezone(&chrono::Utc),
-            );
-        UtcEntry {
+    date: Date,
+    location: Option<&Location>,
+    classifier: Option<&Classifier>,
+    timezone: &Tz,
+) -> Vec<UtcEntry<T>> {
+    let classify = |date: Date| classifier.map(|c| c(date)).unwrap_or_default();
+    let dates = [date.pred_opt(), Some(date), date.succ_opt()];
+
+    let mut utc_entries = Vec::with_capacity(entries.len() * dates.len());
+    for date in dates.into_iter().flatten() {
+        let today = classify(date);
+        let tomorrow = date.succ_opt().map(classify).unwrap_or_default();
+        let active = entries
+            .iter()
+            .filter(|e| e.is_active(date, &today, &tomorrow));
+        utc_entries.extend(get_utc_entries_for_date(active, date, location, timezone));
+    }
+    utc_entries.sort_by_key(|e| e.scheduled_time);
+    utc_entries
+}
+
+fn get_utc_entries_for_date<'a, T: Clone + 'a>(
+    entries: impl Iterator<Item = &'a Entry<T>> + 'a,
+    date: Date,
+    location: Option<&'a Location>,
+    timezone: &'a Tz,
+) -> impl Iterator<Item = UtcEntry<T>> + 'a {
+    let utc_entries = entries.filter_map(move |e| {
+        let scheduled_time = match e.scheduled_time {
+            TimeOfDay::Fixed(time) => get_utc_time_for_fixed(date, time, timezone),
+            time @ TimeOfDay::Solar { .. } => match time.to_utc(date, timezone, location) {
+                Ok(scheduled_time) => scheduled_time,
+                Err(err) => {
+                    error!("Could not get time for {time} on {date}, skipping: {err}");
+                    return None;
+                }
+            },
+        };
+        Some(UtcEntry {
             scheduled_time,
-            latest_time: scheduled_time + duration::minutes(1),
+            interpolation: e.interpolation,
             value: e.value.clone(),
-        }
+        })
     });
     utc_entries
 }
 
+fn get_utc_time_for_fixed(
+    date: chrono::NaiveDate,
+    time: NaiveTime,
+    timezone: &Tz,
+) -> chrono::DateTime<chrono::Utc> {
+    timezone
+        .from_local_datetime(&chrono::NaiveDateTime::new(date, time))
+        .earliest()
+        .or_else(|| {
+            debug!("Could not convert local datetime to UTC, using 3am as fallback.");
+            timezone
+                .from_local_datetime(&chrono::NaiveDateTime::new(date, FALLBACK))
+                .earliest()
+        })
+        .map_or_else(
+            || {
+                debug!("Could not convert 3am to UTC, using now as fallback.");
+                utc_now()
+            },
+            |dt| dt.with_timezone(&chrono::Utc),
+        )
+}
+
 fn get_next_midnight_from_date(
     now: chrono::DateTime<chrono::Utc>,
     date: chrono::NaiveDate,
+    timezone: &Tz,
 ) -> chrono::DateTime<chrono::Utc> {
     date.succ_opt().map_or_else(
         || {
@@ -190,10 +357,106 @@ fn get_next_midnight_from_date(
         },
         |next_date| {
             let local = chrono::NaiveDateTime::new(next_date, MIDNIGHT);
-            chrono::Local
+            timezone
                 .from_local_datetime(&local)
                 .earliest()
                 .map_or(now, |dt| dt.with_timezone(&chrono::Utc))
         },
     )
 }
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+
+    fn utc(hour: u32, minute: u32) -> chrono::DateTime<chrono::Utc> {
+        Date::from_ymd_opt(2025, 1, 1)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+            .and_utc()
+    }
+
+    fn utc_entry(hour: u32, value: u16, interpolation: Interpolation) -> UtcEntry<u16> {
+        UtcEntry {
+            scheduled_time: utc(hour, 0),
+            interpolation,
+            value,
+        }
+    }
+
+    #[test]
+    fn test_get_value_at_step() {
+        let entries = [
+            utc_entry(8, 100, Interpolation::Step),
+            utc_entry(20, 10, Interpolation::Step),
+        ];
+
+        assert_eq!(get_value_at(&entries, utc(7, 0)), (None, Some(utc(8, 0))));
+        assert_eq!(
+            get_value_at(&entries, utc(8, 0)),
+            (Some(100), Some(utc(20, 0)))
+        );
+        assert_eq!(get_value_at(&entries, utc(19, 59)).0, Some(100));
+        assert_eq!(get_value_at(&entries, utc(21, 0)), (Some(10), None));
+    }
+
+    #[test]
+    fn test_get_value_at_interpolated() {
+        let entries = [
+            utc_entry(20, 100, Interpolation::Step),
+            utc_entry(22, 0, Interpolation::Linear),
+            utc_entry(23, 100, Interpolation::Smooth),
+        ];
+
+        assert_eq!(
+            get_value_at(&entries, utc(21, 0)),
+            (Some(50), Some(utc(21, 1)))
+        );
+        assert_eq!(get_value_at(&entries, utc(21, 30)).0, Some(25));
+        assert_eq!(
+            get_value_at(&entries, utc(22, 59)),
+            (Some(100), Some(utc(23, 0)))
+        );
+        assert_eq!(get_value_at(&entries, utc(22, 15)).0, Some(16));
+        assert_eq!(get_value_at(&entries, utc(22, 30)).0, Some(50));
+    }
+
+    #[test]
+    fn test_get_utc_time_for_fixed() {
+        let date = Date::from_ymd_opt(2025, 1, 1).unwrap();
+        let time = NaiveTime::from_hms_opt(8, 0, 0).unwrap();
+
+        let melbourne = get_utc_time_for_fixed(date, time, &chrono_tz::Australia::Melbourne);
+        assert_eq!(melbourne, utc(21, 0) - TimeDelta::days(1));
+
+        let utc_time = get_utc_time_for_fixed(date, time, &chrono_tz::UTC);
+        assert_eq!(utc_time, utc(8, 0));
+    }
+
+    #[test]
+    fn test_entry_is_active() {
+        let yaml = r"
+            time: '21:00:00'
+            value: 10
+            days: [Sun, Mon, Tue, Wed, Thu]
+            start: 2025-01-01
+            tomorrow: [school]
+        ";
+        let entry: Entry<u16> = serde_yaml_ng::from_str(yaml).unwrap();
+        let school: HashSet<String> = HashSet::from(["school".to_string()]);
+        let none = HashSet::new();
+
+        // 2025-01-06 is a Monday.
+        let monday = Date::from_ymd_opt(2025, 1, 6).unwrap();
+        let friday = Date::from_ymd_opt(2025, 1, 10).unwrap();
+        let before = Date::from_ymd_opt(2024, 12, 30).unwrap();
+
+        assert!(entry.is_active(monday, &none, &school));
+        assert!(!entry.is_active(monday, &none, &none));
+        assert!(!entry.is_active(friday, &none, &school));
+        assert!(!entry.is_active(before, &none, &school));
+    }
+}
@@ -1,9 +1,16 @@
 //! Sources that use timers to produce async data.
 use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+use robotica_common::datetime::utc_now;
+use robotica_macro::time_delta_constant;
+use serde::Deserialize;
 use tokio::time;
 use tokio::time::Instant;
+use tracing::{debug, error};
 
 use crate::pipes::stateless;
+use crate::services::persistent_state::PersistentStateRow;
 use crate::spawn;
 
 /// Create a timer that sends outgoing messages at regularly spaced intervals.
@@ -24,12 +31,251 @@ pub fn timer(duration: Duration, name: &str) -> stateless::Receiver<Instant> {
     rx
 }
 
+/// When a [`scheduled_timer`] should tick.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "type", content = "value")]
+pub enum Schedule {
+    /// A cron expression including seconds, for example `0 */5 * * * *`.
+    Cron(Box<cron::Schedule>),
+
+    /// Every `period`, aligned to local midnight plus `offset`.
+    ///
+    /// For example a period of 5 minutes ticks at 00:00, 00:05, 00:10 and so on.
+    Aligned {
+        /// The time between ticks.
+        #[serde(with = "robotica_common::datetime::with_time_delta")]
+        period: TimeDelta,
+
+        /// The offset from midnight of the first tick.
+        #[serde(default, with = "robotica_common::datetime::with_time_delta")]
+        offset: TimeDelta,
+    },
+}
+
+impl Schedule {
+    /// Get the first tick strictly after `after`.
+    fn next_after<Tz: TimeZone>(
+        &self,
+        after: DateTime<Utc>,
+        timezone: &Tz,
+    ) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Cron(schedule) => schedule
+                .after(&after.with_timezone(timezone))
+                .next()
+                .map(|dt| dt.with_timezone(&Utc)),
+            Self::Aligned { period, offset } => next_aligned(after, *period, *offset, timezone),
+        }
+    }
+}
+
+fn local_midnight<Tz: TimeZone>(date: chrono::NaiveDate, timezone: &Tz) -> Option<DateTime<Utc>> {
+    date.and_hms_opt(0, 0, 0)
+        .and_then(|midnight| timezone.from_local_datetime(&midnight).earliest())
+        .map(|midnight| midnight.with_timezone(&Utc))
+}
+
+/// Ticks restart at midnight every day, so periods that don't divide a day stay aligned.
+fn next_aligned<Tz: TimeZone>(
+    after: DateTime<Utc>,
+    period: TimeDelta,
+    offset: TimeDelta,
+    timezone: &Tz,
+) -> Option<DateTime<Utc>> {
+    let period_ms = period.num_milliseconds();
+    if period_ms <= 0 {
+        return None;
+    }
+
+    let date = after.with_timezone(timezone).date_naive();
+    let dates = [date.pred_opt(), Some(date), date.succ_opt()];
+
+    for date in dates.into_iter().flatten() {
+        let start = local_midnight(date, timezone)? + offset;
+        let end = local_midnight(date.succ_opt()?, timezone)? + offset;
+        if after >= end {
+            continue;
+        }
+        let periods = if after < start {
+            0
+        } else {
+            (after - start).num_milliseconds() / period_ms + 1
+        };
+        let next = start + TimeDelta::milliseconds(period_ms.checked_mul(periods)?);
+        if next < end {
+            return Some(next);
+        }
+    }
+
+    None
+}
+
+/// What to do with ticks that were missed, for example while suspended or restarting.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CatchUp {
+    /// Drop missed ticks.
+    #[default]
+    Skip,
+    /// Send only the most recent missed tick.
+    Latest,
+    /// Send every missed tick, oldest first.
+    All,
+}
+
+/// A tick from a [`scheduled_timer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tick {
+    /// When the tick was scheduled.
+    pub scheduled: DateTime<Utc>,
+
+    /// The tick was missed and is being sent late.
+    pub late: bool,
+}
+
+/// Configuration for a [`scheduled_timer`].
+pub struct ScheduledTimerConfig<Tz> {
+    /// When to tick.
+    pub schedule: Schedule,
+
+    /// The timezone used for the schedule.
+    pub timezone: Tz,
+
+    /// What to do with missed ticks.
+    pub catch_up: CatchUp,
+
+    /// Remember the last tick, so ticks missed while not running can be caught up.
+    pub state: Option<PersistentStateRow<DateTime<Utc>>>,
+}
+
+/// How late a tick can be before it counts as missed.
+const LATE_TOLERANCE: TimeDelta = time_delta_constant!(5 seconds);
+
+/// Don't try to catch up more ticks than this.
+const MAX_CATCH_UP: usize = 1000;
+
+/// Get the ticks in `(last, now]`, applying the catch up policy.
+fn get_due_ticks<Tz: TimeZone>(
+    schedule: &Schedule,
+    timezone: &Tz,
+    catch_up: CatchUp,
+    last: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Vec<Tick> {
+    let mut missed = Vec::new();
+    let mut on_time = Vec::new();
+
+    let mut after = last;
+    while let Some(scheduled) = schedule.next_after(after, timezone) {
+        if scheduled > now {
+            break;
+        }
+        if missed.len() >= MAX_CATCH_UP {
+            error!("Too many missed ticks since {last}, skipping the rest");
+            break;
+        }
+        if now - scheduled > LATE_TOLERANCE {
+            missed.push(Tick {
+                scheduled,
+                late: true,
+            });
+        } else {
+            on_time.push(Tick {
+                scheduled,
+                late: false,
+            });
+        }
+        after = scheduled;
+    }
+
+    let missed = match catch_up {
+        CatchUp::Skip => Vec::new(),
+        CatchUp::Latest => missed.pop().into_iter().collect(),
+        CatchUp::All => missed,
+    };
+
+    missed.into_iter().chain(on_time).collect()
+}
+
+/// Create a timer that ticks according to a schedule in wall clock time.
+///
+/// Unlike [`timer`] this follows the clock, so it keeps to the schedule across DST changes and
+/// suspends.
+///
+/// Ticking starts once the receiver has a subscriber, so ticks caught up at startup are not lost.
+#[must_use]
+pub fn scheduled_timer<Tz>(
+    name: &str,
+    config: ScheduledTimerConfig<Tz>,
+) -> stateless::Receiver<Tick>
+where
+    Tz: TimeZone + Send + Sync + 'static,
+    Tz::Offset: Send + Sync,
+{
+    let (tx, rx) = stateless::create_pipe(name);
+    let name = name.to_string();
+
+    spawn(async move {
+        let ScheduledTimerConfig {
+            schedule,
+            timezone,
+            catch_up,
+            state,
+        } = config;
+
+        // Don't send or save ticks until somebody is listening.
+        tx.subscribed().await;
+        if tx.is_closed() {
+            return;
+        }
+
+        let mut last = state
+            .as_ref()
+            .and_then(|state| state.load().ok())
+            .unwrap_or_else(utc_now);
+
+        loop {
+            let now = utc_now();
+
+            for tick in get_due_ticks(&schedule, &timezone, catch_up, last, now) {
+                debug!("{name}: Tick {tick:?}");
+                tx.try_send(tick);
+            }
+
+            last = last.max(now);
+            if let Some(state) = &state {
+                if let Err(err) = state.save(&last) {
+                    error!("{name}: Failed to save state: {err}");
+                }
+            }
+
+            let Some(next) = schedule.next_after(last, &timezone) else {
+                error!("{name}: Schedule has no more ticks, stopping");
+                break;
+            };
+
+            // Clamp to max 5 minutes to notice clock changes and suspends
+            let duration = (next - utc_now())
+                .clamp(TimeDelta::zero(), TimeDelta::minutes(5))
+                .to_std()
+                .unwrap_or_default();
+
+            time::sleep(duration).await;
+        }
+    });
+
+    rx
+}
+
 #[cfg(test)]
 mod tests {
     #![allow(clippy::unwrap_used)]
-    use tokio::time::sleep;
+    use robotica_common::robotica::entities::Id;
+    use tokio::time::{sleep, timeout};
 
     use crate::pipes::{Subscriber, Subscription};
+    use crate::services::persistent_state::{self, PersistentStateDatabase};
 
     use super::*;
 
@@ -49,4 +295,127 @@ mod tests {
         let _v = rx.try_recv().unwrap();
         // assert!(matches!(v, true));
     }
+
+    fn utc(hour: u32, minute: u32, second: u32) -> DateTime<Utc> {
+        chrono::NaiveDate::from_ymd_opt(2025, 1, 1)
+            .unwrap()
+            .and_hms_opt(hour, minute, second)
+            .unwrap()
+            .and_utc()
+    }
+
+    #[test]
+    fn test_next_aligned() {
+        let schedule = Schedule::Aligned {
+            period: TimeDelta::minutes(5),
+            offset: TimeDelta::zero(),
+        };
+        assert_eq!(
+            schedule.next_after(utc(10, 2, 0), &Utc),
+            Some(utc(10, 5, 0))
+        );
+        assert_eq!(
+            schedule.next_after(utc(10, 5, 0), &Utc),
+            Some(utc(10, 10, 0))
+        );
+        assert_eq!(
+            schedule.next_after(utc(23, 58, 0), &Utc),
+            Some(utc(0, 0, 0) + TimeDelta::days(1))
+        );
+
+        // Periods that don't divide a day restart at midnight.
+        let schedule = Schedule::Aligned {
+            period: TimeDelta::hours(7),
+            offset: TimeDelta::minutes(30),
+        };
+        assert_eq!(
+            schedule.next_after(utc(22, 0, 0), &Utc),
+            Some(utc(0, 30, 0) + TimeDelta::days(1))
+        );
+        assert_eq!(schedule.next_after(utc(0, 0, 0), &Utc), Some(utc(0, 30, 0)));
+    }
+
+    #[test]
+    fn test_cron() {
+        let schedule: Schedule =
+            serde_yaml_ng::from_str("{type: cron, value: '30 */15 * * * *'}").unwrap();
+        assert_eq!(
+            schedule.next_after(utc(10, 2, 0), &Utc),
+            Some(utc(10, 15, 30))
+        );
+    }
+
+    #[test]
+    fn test_get_due_ticks() {
+        let schedule = Schedule::Aligned {
+            period: TimeDelta::minutes(5),
+            offset: TimeDelta::zero(),
+        };
+        let last = utc(10, 1, 0);
+        let now = utc(10, 20, 2);
+
+        let due = |catch_up| get_due_ticks(&schedule, &Utc, catch_up, last, now);
+        let on_time = Tick {
+            scheduled: utc(10, 20, 0),
+            late: false,
+        };
+        let late = |minute| Tick {
+            scheduled: utc(10, minute, 0),
+            late: true,
+        };
+
+        assert_eq!(due(CatchUp::Skip), vec![on_time]);
+        assert_eq!(due(CatchUp::Latest), vec![late(15), on_time]);
+        assert_eq!(
+            due(CatchUp::All),
+            vec![late(5), late(10), late(15), on_time]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_timer_catch_up_after_late_subscribe() {
+        let state_path = std::env::temp_dir().join(format!(
+            "robotica-test-scheduled-timer-{}",
+            std::process::id()
+        ));
+        let config = persistent_state::Config {
+            state_path: state_path.clone(),
+        };
+        let psd = PersistentStateDatabase::new(&config).unwrap();
+        let id = Id::new("timer").unwrap();
+        let last = utc_now() - TimeDelta::hours(3);
+        psd.for_name(&id, "last").save(&last).unwrap();
+
+        let ticks = scheduled_timer(
+            "test",
+            ScheduledTimerConfig {
+                schedule: Schedule::Aligned {
+                    period: TimeDelta::hours(1),
+                    offset: TimeDelta::zero(),
+                },
+                timezone: Utc,
+                catch_up: CatchUp::Latest,
+                state: Some(psd.for_name(&id, "last")),
+            },
+        );
+
+        // Give the timer a chance to run before anything subscribes.
+        sleep(Duration::from_millis(100)).await;
+        let saved: DateTime<Utc> = psd.for_name(&id, "last").load().unwrap();
+        assert_eq!(saved, last);
+
+        let mut rx = ticks.subscribe().await;
+        let tick = timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(tick.late);
+        assert!(tick.scheduled > last);
+
+        sleep(Duration::from_millis(100)).await;
+        let saved: DateTime<Utc> = psd.for_name(&id, "last").load().unwrap();
+        assert!(saved > last);
+
+        std::fs::remove_dir_all(state_path).unwrap();
+    }
 }